@@ -0,0 +1,181 @@
+//! `lex` against the lexer it replaced, which allocated a `String` per
+//! identifier, keyword and operator. Run with `cargo bench --bench lex`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+/// The `String`-per-token lexer, kept verbatim for comparison.
+#[allow(dead_code)]
+mod legacy {
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Token {
+        Keyword(String),
+        Ident(String),
+        Number(i64),
+        Str(String),
+        Symbol(char),
+        Operator(String),
+    }
+
+    #[derive(Debug)]
+    pub enum LexError {
+        UnexpectedChar(char, usize),
+        Unterminated,
+        NumberOverflow(usize),
+    }
+
+    fn is_ident_start(c: char) -> bool {
+        c.is_alphabetic() || c == '_'
+    }
+    fn is_ident_continue(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
+        let keywords = [
+            "rule","if","then","in","and","or","not",
+            "delete","mask","notify","encrypt","now","any","all",
+        ];
+
+        let mut tokens = Vec::new();
+        let mut chars = input.chars().peekable();
+        let mut idx = 0usize;
+
+        while let Some(&c) = chars.peek() {
+            // skip whitespace
+            if c.is_whitespace() {
+                chars.next(); idx += 1; continue;
+            }
+
+            // identifier / keyword
+            if is_ident_start(c) {
+                let mut s = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if is_ident_continue(ch) { s.push(ch); chars.next(); idx += 1; }
+                    else { break; }
+                }
+                if keywords.contains(&s.as_str()) {
+                    tokens.push(Token::Keyword(s));
+                } else {
+                    tokens.push(Token::Ident(s));
+                }
+                continue;
+            }
+
+            // number
+            if c.is_ascii_digit() {
+                let start = idx;
+                let mut s = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_ascii_digit() { s.push(ch); chars.next(); idx += 1; }
+                    else { break; }
+                }
+                let n: i64 = s.parse().map_err(|_| LexError::NumberOverflow(start))?;
+                tokens.push(Token::Number(n));
+                continue;
+            }
+
+            // string literal, with `\"` and `\\` escapes
+            if c == '"' {
+                chars.next(); idx += 1;
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => { idx += 1; break; }
+                        Some('\\') => match chars.next() {
+                            Some(esc @ ('"' | '\\')) => { s.push(esc); idx += 2; }
+                            Some(other) => return Err(LexError::UnexpectedChar(other, idx + 1)),
+                            None => return Err(LexError::Unterminated),
+                        },
+                        Some(ch) => { s.push(ch); idx += 1; }
+                        None => return Err(LexError::Unterminated),
+                    }
+                }
+                tokens.push(Token::Str(s));
+                continue;
+            }
+
+            let two = {
+                let mut it = chars.clone();
+                let first = it.next();
+                let second = it.next();
+                match (first, second) {
+                    (Some(a), Some(b)) => Some(format!("{a}{b}")),
+                    _ => None,
+                }
+            };
+
+            if let Some(op2) = two
+                && ["==","!=" ,">=","<=","**"].contains(&op2.as_str()) {
+                // consume 2
+                chars.next(); chars.next(); idx += 2;
+                tokens.push(Token::Operator(op2));
+                continue;
+            }
+            if ['=','>','<','+','-','*','/','%'].contains(&c) {
+                chars.next(); idx += 1;
+                tokens.push(Token::Operator(c.to_string()));
+                continue;
+            }
+
+            // symbols
+            if "{}()[],.;:".contains(c) {
+                chars.next(); idx += 1;
+                tokens.push(Token::Symbol(c));
+                continue;
+            }
+
+            // unknown
+            return Err(LexError::UnexpectedChar(c, idx));
+        }
+
+        Ok(tokens)
+    }
+}
+
+/// A generated policy of `rules` rules in the shape of the sample policies.
+fn policy(rules: usize) -> String {
+    let mut src = String::new();
+    for i in 0..rules {
+        src.push_str(&format!(r#"
+rule retention_{i} {{
+    if record.age_in_days > {i} days and record.kind in [invoice, receipt] then delete;
+    if now - record.created_at >= 30 days or user.country == "blocked_{i}" then notify;
+    if field in [ssn, credit_card] and not user.is_admin == true then mask;
+    if lower(user.email) ends_with "@example.com" and user.failed_logins * 2 != 7 then encrypt
+}}
+"#));
+    }
+    src
+}
+
+fn lexers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for rules in [10, 1_000] {
+        let src = policy(rules);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+        group.bench_with_input(BenchmarkId::new("legacy", rules), &src, |b, src| {
+            b.iter(|| legacy::lex(black_box(src)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("collect", rules), &src, |b, src| {
+            b.iter(|| lexer::lex(black_box(src)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("stream", rules), &src, |b, src| {
+            b.iter(|| lexer::Lexer::new(black_box(src)).try_fold(0usize, |n, t| t.map(|_| n + 1)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn lex_and_parse(c: &mut Criterion) {
+    let src = policy(1_000);
+    let mut group = c.benchmark_group("lex_and_parse");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.bench_function("1000_rules", |b| {
+        b.iter(|| lexer::Parser::new(lexer::lex(black_box(&src)).unwrap()).parse_program().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lexers, lex_and_parse);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![no_main]
+
+// Run with `cargo +nightly fuzz run lex_parse` from the `lexer` directory.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let src = String::from_utf8_lossy(data);
+    if let Ok(tokens) = lexer::lex(&src) {
+        let _ = lexer::Parser::new(tokens).parse_program();
+    }
+});
@@ -0,0 +1,124 @@
+# Tests of the Python module:
+#
+#   cargo build --lib --release --features python
+#   python3 python/test_policy.py [path/to/liblexer.so]
+
+import datetime
+import importlib.machinery
+import importlib.util
+import os
+import pathlib
+import sys
+import tempfile
+import unittest
+
+HERE = pathlib.Path(__file__).parent
+PATH = sys.argv.pop(1) if len(sys.argv) > 1 else str(HERE / "../target/release/liblexer.so")
+
+loader = importlib.machinery.ExtensionFileLoader("policy", PATH)
+policy = importlib.util.module_from_spec(importlib.util.spec_from_loader("policy", loader))
+loader.exec_module(policy)
+
+UTC = datetime.timezone.utc
+NOW = datetime.datetime(2024, 1, 10, tzinfo=UTC)
+
+SOURCE = """/// Old records go.
+rule retention [owner: "data-team", tags: [gdpr], grace: 2 days] {
+    if now - record.created_at > 30 days then delete;
+    if record.contacts.*.email ends_with ".ru" then mask(email);
+    if record.card starts_with "4" then encrypt
+}
+"""
+
+
+class Loading(unittest.TestCase):
+    def test_parse_and_check(self):
+        p = policy.check(SOURCE)
+        self.assertEqual(p.rules, ["retention"])
+        self.assertIn("then mask(email)", str(p))
+        self.assertEqual(repr(p), "<Policy with 1 rules>")
+        self.assertEqual(policy.parse("rule r { if 1 > 2 then notify }").lints()[0]["code"], "never-fires")
+
+    def test_lex_error(self):
+        with self.assertRaises(policy.LexError) as cm:
+            policy.parse("rule r {\n  if x # 1 then delete\n}")
+        e = cm.exception
+        self.assertIsInstance(e, policy.PolicyError)
+        self.assertEqual((e.offset, e.line, e.col), (16, 2, 8))
+
+    def test_parse_error(self):
+        with self.assertRaises(policy.ParseError) as cm:
+            policy.parse("rule r {\n  if x > then delete\n}")
+        self.assertEqual((cm.exception.line, cm.exception.col), (2, 10))
+        self.assertIn("Then", str(cm.exception))
+
+    def test_check_error(self):
+        with self.assertRaises(policy.CheckError) as cm:
+            policy.check("rule r {\n  if x > 1 then notify;\n  if nope(x) then delete\n}")
+        self.assertEqual((cm.exception.rule, cm.exception.statement), ("r", 1))
+        policy.parse("rule r { if nope(x) then delete }")  # parse does not type-check
+
+
+class Evaluating(unittest.TestCase):
+    def test_evaluate(self):
+        p = policy.check(SOURCE)
+        record = {"record": {"created_at": "2020-01-01T00:00:00Z", "contacts": [{"email": "a@b.ru"}]}}
+        meta = {"owner": "data-team", "tags": ["gdpr"], "grace": datetime.timedelta(days=2)}
+        self.assertEqual(p.evaluate(record, now=NOW), [
+            {"rule": "retention", "statement": 0, "action": "delete", "targets": [], "meta": meta},
+            {"rule": "retention", "statement": 1, "action": "mask", "targets": ["record.contacts[0].email"], "meta": meta},
+        ])
+        self.assertEqual(p.evaluate({"record": {"created_at": datetime.date(2024, 1, 1)}}, now=int(NOW.timestamp())), [])
+
+    def test_eval_error(self):
+        p = policy.check("rule r { if record.n / 0 > 1 then delete }")
+        with self.assertRaises(policy.EvalError):
+            p.evaluate({"record": {"n": 1}})
+
+    def test_unsupported_values(self):
+        p = policy.check(SOURCE)
+        with self.assertRaises(TypeError):
+            p.evaluate({"record": {"x": {1.5}}})
+        with self.assertRaises(TypeError):
+            p.evaluate({"record": {1: "x"}})
+
+
+class Applying(unittest.TestCase):
+    def test_apply(self):
+        records = [
+            {"record": {"created_at": datetime.datetime(2020, 1, 1), "contacts": []}},
+            {"record": {"created_at": "2024-01-05", "contacts": [{"email": "ann@x.ru"}, {"email": "bo@y.de"}], "card": ""}},
+            {"record": {"created_at": "2024-01-05", "age": datetime.timedelta(days=2), "price": 9.99}},
+        ]
+        out, actions = policy.apply(policy.check(SOURCE), records, now=NOW)
+        self.assertIsNone(out[0])
+        self.assertEqual([a["action"] for a in actions[0]], ["delete"])
+        self.assertEqual(out[1]["record"]["contacts"], [{"email": "a**@x.ru"}, {"email": "bo@y.de"}])
+        self.assertEqual(out[1]["record"]["created_at"], "2024-01-05")
+        self.assertEqual(out[2]["record"]["age"], datetime.timedelta(days=2))
+        self.assertEqual(out[2]["record"]["price"], 9.99)
+        self.assertEqual(actions[2], [])
+
+    def test_encrypt_needs_a_keyring(self):
+        p = policy.check(SOURCE)
+        record = {"record": {"created_at": "2024-01-05", "card": "4111111111111111"}}
+        with self.assertRaisesRegex(policy.PolicyError, "record 0: `encrypt` fired but no keyring was given"):
+            policy.apply(p, [record], now=NOW)
+        with tempfile.TemporaryDirectory() as dir:
+            keyring = os.path.join(dir, "keys")
+            with open(keyring, "w") as f:
+                f.write("k1 " + "ab" * 32 + "\ntokenize record.card k1\n")
+            os.chmod(keyring, 0o600)
+            [out], _ = policy.apply(p, [record], now=NOW, keyring=keyring)
+            self.assertTrue(out["record"]["card"].startswith("enc:v1:k1:"))
+            [out], _ = policy.apply(p, [record], now=NOW, keyring=keyring, tokenize=["record.card"])
+            self.assertEqual(len(out["record"]["card"]), 16)
+            self.assertNotEqual(out["record"]["card"], "4111111111111111")
+
+    def test_bad_record_is_named(self):
+        with self.assertRaisesRegex(TypeError, "record 1: unsupported value of type set"):
+            policy.apply(policy.check(SOURCE), [{}, {"x": {0.5}}], now=NOW)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,479 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::ast::*;
+use crate::eval::{compare, counted};
+use crate::glob;
+use crate::value::Value;
+
+//
+// ===== STATIC ANALYSIS =====
+//
+
+/// Strip `Group`s and push `Not` inward with De Morgan's laws, so `Not` only
+/// wraps predicates. `not any x in l: p` becomes `all x in l: not p`.
+pub fn normalize(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Group(e) => normalize(e),
+        Expr::Or(a, b) => Expr::Or(Box::new(normalize(a)), Box::new(normalize(b))),
+        Expr::And(a, b) => Expr::And(Box::new(normalize(a)), Box::new(normalize(b))),
+        Expr::Not(e) => negate(e),
+        Expr::Quantified { quantifier, var, list, body } => Expr::Quantified {
+            quantifier: *quantifier, var: var.clone(), list: list.clone(), body: Box::new(normalize(body)),
+        },
+        other => other.clone(),
+    }
+}
+
+fn negate(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Group(e) => negate(e),
+        Expr::Not(e) => normalize(e),
+        Expr::Or(a, b) => Expr::And(Box::new(negate(a)), Box::new(negate(b))),
+        Expr::And(a, b) => Expr::Or(Box::new(negate(a)), Box::new(negate(b))),
+        Expr::Quantified { quantifier, var, list, body } => Expr::Quantified {
+            quantifier: match quantifier { Quantifier::Any => Quantifier::All, Quantifier::All => Quantifier::Any },
+            var: var.clone(), list: list.clone(), body: Box::new(negate(body)),
+        },
+        other => Expr::Not(Box::new(other.clone())),
+    }
+}
+
+//
+// Value sets. A constraint is the set of values a field may hold for a
+// predicate to be true, tracked separately for numbers, durations and strings.
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind { Number, Duration, Str }
+
+#[derive(Debug, Clone, PartialEq)]
+enum Domain {
+    /// Sorted, disjoint, inclusive intervals of half-steps: `2n` is the
+    /// integer `n` and `2n + 1` the open interval `(n, n + 1)`, since a float
+    /// can fall strictly between the integer literals it is compared with.
+    Reals(Vec<(i128, i128)>),
+    /// Exactly these strings.
+    Only(BTreeSet<String>),
+    /// Any string but these.
+    Except(BTreeSet<String>),
+}
+
+impl Domain {
+    fn complement(&self) -> Domain {
+        match self {
+            Domain::Reals(ranges) => {
+                let mut out = Vec::new();
+                let mut next = Some(i128::MIN);
+                for &(lo, hi) in ranges {
+                    if let Some(n) = next && n < lo { out.push((n, lo - 1)); }
+                    next = hi.checked_add(1);
+                }
+                if let Some(n) = next { out.push((n, i128::MAX)); }
+                Domain::Reals(out)
+            }
+            Domain::Only(s) => Domain::Except(s.clone()),
+            Domain::Except(s) => Domain::Only(s.clone()),
+        }
+    }
+
+    fn intersect(&self, other: &Domain) -> Domain {
+        match (self, other) {
+            (Domain::Reals(a), Domain::Reals(b)) => {
+                let mut out = Vec::new();
+                let (mut i, mut j) = (0, 0);
+                while i < a.len() && j < b.len() {
+                    let (lo, hi) = (a[i].0.max(b[j].0), a[i].1.min(b[j].1));
+                    if lo <= hi { out.push((lo, hi)); }
+                    if a[i].1 < b[j].1 { i += 1 } else { j += 1 }
+                }
+                Domain::Reals(out)
+            }
+            (Domain::Only(a), Domain::Only(b)) => Domain::Only(a.intersection(b).cloned().collect()),
+            (Domain::Only(a), Domain::Except(b)) | (Domain::Except(b), Domain::Only(a)) => {
+                Domain::Only(a.difference(b).cloned().collect())
+            }
+            (Domain::Except(a), Domain::Except(b)) => Domain::Except(a.union(b).cloned().collect()),
+            // constraints are keyed by kind, so numbers never meet strings
+            _ => unreachable!("mismatched constraint kinds"),
+        }
+    }
+
+    fn union(&self, other: &Domain) -> Domain {
+        self.complement().intersect(&other.complement()).complement()
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Domain::Reals(r) => r.is_empty(),
+            Domain::Only(s) => s.is_empty(),
+            Domain::Except(_) => false,
+        }
+    }
+}
+
+/// Values a field may hold, plus whether a missing (`null`) field qualifies.
+#[derive(Debug, Clone, PartialEq)]
+struct Constraint { domain: Domain, null: bool }
+
+impl Constraint {
+    fn complement(&self) -> Constraint { Constraint { domain: self.domain.complement(), null: !self.null } }
+    fn intersect(&self, o: &Constraint) -> Constraint { Constraint { domain: self.domain.intersect(&o.domain), null: self.null && o.null } }
+    fn union(&self, o: &Constraint) -> Constraint { Constraint { domain: self.domain.union(&o.domain), null: self.null || o.null } }
+    fn is_empty(&self) -> bool { self.domain.is_empty() && !self.null }
+    fn is_full(&self) -> bool { self.complement().is_empty() }
+    fn is_subset(&self, o: &Constraint) -> bool { self.intersect(&o.complement()).is_empty() }
+}
+
+type Key = (String, Kind);
+
+fn constant(operand: &Operand) -> Option<Value> {
+    match operand {
+        Operand::Number(n) => Some(Value::Number(*n)),
+        Operand::Neg(inner) => match constant(inner)? {
+            Value::Number(n) => n.checked_neg().map(Value::Number),
+            Value::Duration(d) => d.checked_neg().map(Value::Duration),
+            _ => None,
+        },
+        Operand::Duration { value, unit } => value.checked_mul(unit_seconds(unit)?).map(Value::Duration),
+        Operand::Str(s) => Some(Value::Str(s.clone())),
+        _ => None,
+    }
+}
+
+fn flip(op: CompOp) -> Option<CompOp> {
+    use CompOp::*;
+    Some(match op { Eq => Eq, Ne => Ne, Gt => Lt, Lt => Gt, Ge => Le, Le => Ge, _ => return None })
+}
+
+/// The constraint a predicate puts on a single field, if it is that simple.
+fn constraint(expr: &Expr) -> Option<(Key, Constraint)> {
+    match expr {
+        Expr::Not(inner) => constraint(inner).map(|(k, c)| (k, c.complement())),
+        Expr::In { field, set } => Some((
+            (field.to_string(), Kind::Str),
+            Constraint { domain: Domain::Only(set.iter().cloned().collect()), null: false },
+        )),
+        Expr::Compare { left, op, right } => {
+            let (field, op, value) = match (left, right) {
+                (Operand::Field(f), c) => (f, *op, constant(c)?),
+                (c, Operand::Field(f)) => (f, flip(*op)?, constant(c)?),
+                _ => return None,
+            };
+            // `null != x` holds, every other comparison with null fails
+            let null = op == CompOp::Ne;
+            let (kind, domain) = match value {
+                Value::Number(n) => (Kind::Number, real_domain(op, n)?),
+                Value::Duration(d) => (Kind::Duration, real_domain(op, d)?),
+                Value::Str(s) => (Kind::Str, match op {
+                    CompOp::Eq => Domain::Only(BTreeSet::from([s])),
+                    CompOp::Ne => Domain::Except(BTreeSet::from([s])),
+                    _ => return None,
+                }),
+                _ => return None,
+            };
+            Some(((field.to_string(), kind), Constraint { domain, null }))
+        }
+        _ => None,
+    }
+}
+
+fn real_domain(op: CompOp, n: i64) -> Option<Domain> {
+    use CompOp::*;
+    let n = 2 * i128::from(n);
+    let range = |lo, hi| Domain::Reals(vec![(lo, hi)]);
+    Some(match op {
+        Eq => range(n, n),
+        Ne => range(n, n).complement(),
+        Gt => range(n + 1, i128::MAX),
+        Ge => range(n, i128::MAX),
+        Lt => range(i128::MIN, n - 1),
+        Le => range(i128::MIN, n),
+        _ => return None,
+    })
+}
+
+/// Truth value of a comparison between two literals, e.g. `1 > 2`.
+fn static_truth(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Not(inner) => static_truth(inner).map(|b| !b),
+        Expr::Compare { left, op, right } => {
+            let (l, r) = (counted(constant(left)?, right).ok()?, counted(constant(right)?, left).ok()?);
+            compare(&l, *op, &r).ok()
+        }
+        _ => None,
+    }
+}
+
+fn flatten<'a>(expr: &'a Expr, and: bool, out: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::And(a, b) if and => { flatten(a, and, out); flatten(b, and, out) }
+        Expr::Or(a, b) if !and => { flatten(a, and, out); flatten(b, and, out) }
+        other => out.push(other),
+    }
+}
+
+/// Combine the single-field constraints among `parts` per field, with `merge`.
+fn combined(parts: &[&Expr], merge: fn(&Constraint, &Constraint) -> Constraint) -> HashMap<Key, Constraint> {
+    let mut by_key: HashMap<Key, Constraint> = HashMap::new();
+    for part in parts {
+        if let Some((key, c)) = constraint(part) {
+            let merged = match by_key.get(&key) { Some(prev) => merge(prev, &c), None => c };
+            by_key.insert(key, merged);
+        }
+    }
+    by_key
+}
+
+/// Whether `parts` contains both some predicate and its negation.
+fn has_complementary(parts: &[&Expr]) -> bool {
+    parts.iter().any(|p| matches!(p, Expr::Not(inner) if parts.contains(&&**inner)))
+}
+
+/// Whether a normalized condition can never hold.
+fn always_false(expr: &Expr) -> bool {
+    match expr {
+        Expr::Or(a, b) => always_false(a) && always_false(b),
+        Expr::And(..) => {
+            let mut parts = Vec::new();
+            flatten(expr, true, &mut parts);
+            parts.iter().any(|p| always_false(p))
+                || has_complementary(&parts)
+                || combined(&parts, Constraint::intersect).values().any(Constraint::is_empty)
+        }
+        atom => static_truth(atom) == Some(false) || constraint(atom).is_some_and(|(_, c)| c.is_empty()),
+    }
+}
+
+/// Whether a normalized condition holds for every record.
+fn always_true(expr: &Expr) -> bool {
+    match expr {
+        Expr::And(a, b) => always_true(a) && always_true(b),
+        Expr::Or(..) => {
+            let mut parts = Vec::new();
+            flatten(expr, false, &mut parts);
+            parts.iter().any(|p| always_true(p))
+                || has_complementary(&parts)
+                || combined(&parts, Constraint::union).values().any(Constraint::is_full)
+        }
+        atom => static_truth(atom) == Some(true) || constraint(atom).is_some_and(|(_, c)| c.is_full()),
+    }
+}
+
+/// Whether every record satisfying normalized `a` also satisfies normalized `b`.
+/// Conservative: `false` means "could not prove it".
+pub fn implies(a: &Expr, b: &Expr) -> bool {
+    if a == b { return true; }
+    if let Expr::Or(x, y) = a { return implies(x, b) && implies(y, b); }
+    if let Expr::And(x, y) = b { return implies(a, x) && implies(a, y); }
+    if let Expr::Or(x, y) = b && (implies(a, x) || implies(a, y)) { return true; }
+    if let Expr::And(..) = a {
+        let mut parts = Vec::new();
+        flatten(a, true, &mut parts);
+        if parts.iter().any(|p| implies(p, b)) { return true; }
+        return match constraint(b) {
+            Some((key, cb)) => combined(&parts, Constraint::intersect).get(&key).is_some_and(|ca| ca.is_subset(&cb)),
+            None => false,
+        };
+    }
+    match (constraint(a), constraint(b)) {
+        (Some((ka, ca)), Some((kb, cb))) => ka == kb && ca.is_subset(&cb),
+        _ => false,
+    }
+}
+
+//
+// Lints
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintCode {
+    /// The condition is a contradiction, so the statement never fires.
+    NeverFires,
+    /// The condition is a tautology, so the statement fires for every record.
+    AlwaysTrue,
+    /// An earlier statement with the same action fires whenever this one does.
+    Subsumed,
+    /// A rule name or field mixes characters from several scripts, e.g. Latin and Cyrillic.
+    MixedScript,
+    /// A rule name or field looks like another one, or like an ASCII word, but is spelled differently.
+    Confusable,
+}
+
+impl LintCode {
+    pub const ALL: [LintCode; 5] =
+        [LintCode::NeverFires, LintCode::AlwaysTrue, LintCode::Subsumed, LintCode::MixedScript, LintCode::Confusable];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LintCode::NeverFires => "never-fires",
+            LintCode::AlwaysTrue => "always-true",
+            LintCode::Subsumed => "subsumed",
+            LintCode::MixedScript => "mixed-script",
+            LintCode::Confusable => "confusable",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LintCode> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+impl fmt::Display for LintCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.name()) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity { Allow, Warn, Deny }
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self { Severity::Allow => "allow", Severity::Warn => "warning", Severity::Deny => "error" })
+    }
+}
+
+/// Severity per lint; every lint is a warning unless configured otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    severities: HashMap<LintCode, Severity>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, code: LintCode, severity: Severity) { self.severities.insert(code, severity); }
+    pub fn severity(&self, code: LintCode) -> Severity { self.severities.get(&code).copied().unwrap_or(Severity::Warn) }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub code: LintCode,
+    pub severity: Severity,
+    pub rule: String,
+    /// `None` for lints about the rule itself, such as its name.
+    pub statement: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: rule '{}'", self.severity, self.code, self.rule)?;
+        if let Some(i) = self.statement { write!(f, ", statement {i}")?; }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Report statements that never fire, always fire or are subsumed by an earlier
+/// statement with the same action, then suspicious identifiers. Lints
+/// configured as `Allow` are dropped.
+pub fn analyze(program: &Program, config: &LintConfig) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut seen: Vec<(&str, &Schedule, usize, &Statement, Expr, bool)> = Vec::new();
+    for rule in &program.rules {
+        if rule.schedule.is_never() {
+            let severity = config.severity(LintCode::NeverFires);
+            if severity != Severity::Allow {
+                let message = format!("schedule `{}` is never in force", rule.schedule);
+                lints.push(Lint { code: LintCode::NeverFires, severity, rule: rule.name.clone(), statement: None, message });
+            }
+            continue;
+        }
+        for (i, st) in rule.statements.iter().enumerate() {
+            let mut report = |code: LintCode, message: String| {
+                let severity = config.severity(code);
+                if severity != Severity::Allow {
+                    lints.push(Lint { code, severity, rule: rule.name.clone(), statement: Some(i), message });
+                }
+            };
+            let cond = normalize(&st.condition);
+            let never = always_false(&cond);
+            if never {
+                report(LintCode::NeverFires, format!("condition `{}` can never be true", st.condition));
+            } else if always_true(&cond) {
+                report(LintCode::AlwaysTrue, format!("condition `{}` is always true", st.condition));
+            }
+            // `*`/`**` conditions pick their targets per location, so only exact repeats are redundant;
+            // a scheduled statement only covers statements in force at the same times
+            let patterns = !glob::pattern_fields(&cond).is_empty();
+            if !never && let Some((r, _, j, ..)) = seen.iter().find(|(_, schedule, _, prev, earlier, earlier_patterns)| {
+                (schedule.is_always() || **schedule == rule.schedule)
+                    && (prev.action, prev.mask) == (st.action, st.mask)
+                    && (*earlier == cond || (!patterns && !earlier_patterns && implies(&cond, earlier)))
+            }) {
+                report(LintCode::Subsumed, format!("already covered by rule '{r}', statement {j} with the same action"));
+            }
+            if !never { seen.push((&rule.name, &rule.schedule, i, st, cond, patterns)); }
+        }
+    }
+    identifier_lints(program, config, &mut lints);
+    lints
+}
+
+/// Field keys and quantifier variables a condition spells out.
+fn identifiers<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => { identifiers(a, out); identifiers(b, out) }
+        Expr::Not(e) | Expr::Group(e) => identifiers(e, out),
+        Expr::Compare { left, right, .. } => { operand_identifiers(left, out); operand_identifiers(right, out) }
+        Expr::In { field, .. } => field_identifiers(field, out),
+        Expr::Call(call) => call.args.iter().for_each(|a| operand_identifiers(a, out)),
+        Expr::Quantified { var, list, body, .. } => {
+            out.push(var);
+            operand_identifiers(list, out);
+            identifiers(body, out);
+        }
+        Expr::LooksLike(_) => {}
+    }
+}
+
+fn operand_identifiers<'a>(operand: &'a Operand, out: &mut Vec<&'a str>) {
+    match operand {
+        Operand::Field(f) => field_identifiers(f, out),
+        Operand::Call(call) => call.args.iter().for_each(|a| operand_identifiers(a, out)),
+        Operand::Neg(inner) => operand_identifiers(inner, out),
+        Operand::Arith { left, right, .. } => { operand_identifiers(left, out); operand_identifiers(right, out) }
+        _ => {}
+    }
+}
+
+fn field_identifiers<'a>(field: &'a Field, out: &mut Vec<&'a str>) {
+    out.extend(field.segments.iter().filter_map(|s| match s { Segment::Key(k) => Some(k.as_str()), _ => None }));
+}
+
+/// Report each rule name or field that mixes scripts, or whose confusable
+/// skeleton (UTS #39) matches a differently spelled identifier or plain ASCII,
+/// once at its first occurrence.
+fn identifier_lints(program: &Program, config: &LintConfig, lints: &mut Vec<Lint>) {
+    use unicode_security::{skeleton, MixedScript};
+
+    let mut seen: BTreeSet<&str> = BTreeSet::new();
+    let mut skeletons: HashMap<String, &str> = HashMap::new();
+    for rule in &program.rules {
+        let mut names = vec![(None, rule.name.as_str())];
+        for (i, st) in rule.statements.iter().enumerate() {
+            let mut found = Vec::new();
+            identifiers(&st.condition, &mut found);
+            names.extend(found.into_iter().map(|name| (Some(i), name)));
+        }
+        for (statement, name) in names {
+            if !seen.insert(name) { continue; }
+            let mut report = |code: LintCode, message: String| {
+                let severity = config.severity(code);
+                if severity != Severity::Allow {
+                    lints.push(Lint { code, severity, rule: rule.name.clone(), statement, message });
+                }
+            };
+            if !name.is_single_script() {
+                report(LintCode::MixedScript, format!("identifier `{name}` mixes scripts"));
+            }
+            // plain ASCII is the expected spelling, never the impostor
+            let skel: String = skeleton(name).collect();
+            if !name.is_ascii() {
+                if skel.is_ascii() {
+                    report(LintCode::Confusable, format!("identifier `{name}` looks like ASCII `{skel}`"));
+                } else if let Some(other) = skeletons.get(&skel) {
+                    report(LintCode::Confusable, format!("identifier `{name}` looks like `{other}`"));
+                }
+            }
+            skeletons.entry(skel).or_insert(name);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::ops::Range;
+
+use serde_json::{json, Value as Json};
+
+use crate::analyze::{analyze, LintConfig, Severity};
+use crate::ast::{Literal, Program, Rule};
+use crate::eval::{Decision, Evaluator};
+use crate::explain::{StatementTrace, Trace};
+use crate::parser::Parser;
+use crate::source::{LineIndex, Spans};
+use crate::token::{Lexer, Token};
+use crate::value::Value;
+
+//
+// ===== JSON API =====
+//
+// The library behind a single JSON request and response, for hosts that only
+// speak JSON, such as the WebAssembly build (see `wasm.rs`). A request names
+// an operation:
+//
+//   {"op": "lex", "source": "..."}
+//   {"op": "parse", "source": "..."}
+//   {"op": "check", "source": "..."}
+//   {"op": "evaluate", "source": "...", "records": [{...}], "now": 1700000000}
+//
+// The response is `{"ok": true, ...}` with the result, or `{"ok": false,
+// "diagnostics": [...]}`. Tokens, rules and statements in results carry
+// spans too. `check` also reports lints, so it can be ok and
+// still carry warnings. A diagnostic is
+//
+//   {"severity": "error" | "warning", "stage": "request" | "lex" | "parse" |
+//    "check" | "lint" | "evaluate", "message": "...", "code": "never-fires",
+//    "span": {"start": {"offset": 9, "line": 1, "col": 10}, "end": {...}}}
+//
+// where `code` is only present for lints and `span` is null when there is
+// nothing to point at. Offsets are bytes into `source`; lines and columns
+// are 1-based, with columns counted in characters.
+//
+// `check` and `evaluate` know the built-in functions; a host that registers
+// its own passes its evaluator to `handle_with`.
+//
+
+/// A source file lexed once, so diagnostics can point into it.
+struct Source<'a> {
+    text: &'a str,
+    index: LineIndex<'a>,
+    spans: Spans<'a>,
+}
+
+impl<'a> Source<'a> {
+    fn lex(text: &'a str) -> Result<Self, Json> {
+        let index = LineIndex::new(text);
+        let tokens = Lexer::new(text).collect::<Result<Vec<_>, _>>().map_err(|e| {
+            let start = e.offset();
+            let end = text[start..].chars().next().map_or(start, |c| start + c.len_utf8());
+            failure(vec![diagnostic(&index, "error", "lex", e.to_string(), Some(start..end))])
+        })?;
+        Ok(Source { text, index, spans: Spans::new(tokens) })
+    }
+
+    fn parse(&self) -> Result<Program, Json> {
+        let tokens = self.spans.tokens();
+        let mut parser = Parser::new(tokens.iter().map(|(t, _)| t.clone()).collect());
+        parser.parse_program().map_err(|e| {
+            // an error at end of input points just past the last token
+            let end = tokens.last().map_or(0, |(_, span)| span.end);
+            let span = parser.error_token(&e).map_or(end..end, |i| tokens[i].1.clone());
+            failure(vec![self.diagnostic("error", "parse", e.to_string(), Some(span))])
+        })
+    }
+
+    fn diagnostic(&self, severity: &str, stage: &str, message: String, span: Option<Range<usize>>) -> Json {
+        diagnostic(&self.index, severity, stage, message, span)
+    }
+}
+
+fn span_json(index: &LineIndex, span: &Range<usize>) -> Json {
+    let at = |offset: usize| {
+        let lc = index.line_col(offset);
+        json!({ "offset": offset, "line": lc.line, "col": lc.col })
+    };
+    json!({ "start": at(span.start), "end": at(span.end) })
+}
+
+fn diagnostic(index: &LineIndex, severity: &str, stage: &str, message: String, span: Option<Range<usize>>) -> Json {
+    json!({
+        "severity": severity,
+        "stage": stage,
+        "message": message,
+        "span": span.map(|s| span_json(index, &s)),
+    })
+}
+
+fn failure(diagnostics: Vec<Json>) -> Json {
+    json!({ "ok": false, "diagnostics": diagnostics })
+}
+
+fn request_error(message: impl Into<String>) -> Json {
+    failure(vec![json!({ "severity": "error", "stage": "request", "message": message.into(), "span": null })])
+}
+
+/// Handle one request; see the module comment for the protocol.
+pub fn call(request: &str) -> String {
+    let response = match serde_json::from_str::<Json>(request) {
+        Ok(request) => handle(&request),
+        Err(e) => request_error(format!("invalid JSON: {e}")),
+    };
+    response.to_string()
+}
+
+/// `call` on a parsed request.
+pub fn handle(request: &Json) -> Json {
+    // only the functions are used; `evaluate` takes `now` from the request
+    handle_with(request, &Evaluator::with_now(0))
+}
+
+/// `handle`, checking and evaluating with `evaluator`'s functions.
+pub fn handle_with(request: &Json, evaluator: &Evaluator) -> Json {
+    let Some(source) = request["source"].as_str() else { return request_error("missing \"source\" string") };
+    let result = match request["op"].as_str() {
+        Some("lex") => lex(source),
+        Some("parse") => parse(source),
+        Some("check") => check(source, evaluator),
+        Some("evaluate") => evaluate(source, &request["records"], &request["now"], evaluator),
+        Some(op) => Err(request_error(format!("unknown op \"{op}\""))),
+        None => Err(request_error("missing \"op\" string")),
+    };
+    result.unwrap_or_else(|failure| failure)
+}
+
+fn token_kind(token: &Token) -> &'static str {
+    match token {
+        Token::Doc(_) => "doc",
+        Token::Keyword(_) => "keyword",
+        Token::Ident(_) => "ident",
+        Token::Number(_) => "number",
+        Token::Str(_) => "string",
+        Token::Symbol(_) => "symbol",
+        Token::Operator(_) => "operator",
+    }
+}
+
+fn lex(text: &str) -> Result<Json, Json> {
+    let source = Source::lex(text)?;
+    let tokens: Vec<Json> = source.spans.tokens().iter().map(|(tok, span)| json!({
+        "kind": token_kind(tok),
+        "text": &source.text[span.clone()],
+        "span": span_json(&source.index, span),
+    })).collect();
+    Ok(json!({ "ok": true, "tokens": tokens }))
+}
+
+fn program_json(source: &Source, program: &Program) -> Json {
+    let rules: Vec<Json> = program.rules.iter().map(|rule| {
+        let mut json = rule_json(rule);
+        for (i, st) in json["statements"].as_array_mut().into_iter().flatten().enumerate() {
+            st["span"] = json!(source.spans.statement(&rule.name, i).map(|s| span_json(&source.index, &s)));
+        }
+        json["span"] = json!(source.spans.rule(&rule.name).map(|s| span_json(&source.index, &s)));
+        json
+    }).collect();
+    let tests: Vec<&str> = program.tests.iter().map(|t| t.name.as_str()).collect();
+    json!({ "rules": rules, "tests": tests, "formatted": program.to_string() })
+}
+
+fn parse(text: &str) -> Result<Json, Json> {
+    let source = Source::lex(text)?;
+    let program = source.parse()?;
+    let mut out = program_json(&source, &program);
+    out["ok"] = json!(true);
+    Ok(out)
+}
+
+/// Type errors and lints, as diagnostics.
+fn diagnostics(source: &Source, program: &Program, evaluator: &Evaluator) -> Vec<Json> {
+    let mut out = Vec::new();
+    if let Err(e) = evaluator.check(program) {
+        let span = source.spans.statement(&e.rule, e.statement);
+        out.push(source.diagnostic("error", "check", e.to_string(), span));
+    }
+    for lint in analyze(program, &LintConfig::default()) {
+        let severity = if lint.severity == Severity::Deny { "error" } else { "warning" };
+        let span = match lint.statement {
+            Some(i) => source.spans.statement(&lint.rule, i),
+            None => source.spans.rule(&lint.rule),
+        };
+        let mut d = source.diagnostic(severity, "lint", lint.to_string(), span);
+        d["code"] = json!(lint.code.name());
+        out.push(d);
+    }
+    out
+}
+
+fn check(text: &str, evaluator: &Evaluator) -> Result<Json, Json> {
+    let source = Source::lex(text)?;
+    let program = source.parse()?;
+    let diagnostics = diagnostics(&source, &program, evaluator);
+    let ok = !diagnostics.iter().any(|d| d["severity"] == "error");
+    Ok(json!({ "ok": ok, "diagnostics": diagnostics }))
+}
+
+fn evaluate(text: &str, records: &Json, now: &Json, evaluator: &Evaluator) -> Result<Json, Json> {
+    // wasm32 has no clock, so the caller supplies one
+    let Some(now) = now.as_i64() else { return Err(request_error("missing \"now\" in seconds since the Unix epoch")) };
+    let Some(records) = records.as_array() else { return Err(request_error("missing \"records\" array")) };
+    let source = Source::lex(text)?;
+    let program = source.parse()?;
+    if let Err(e) = evaluator.check(&program) {
+        let span = source.spans.statement(&e.rule, e.statement);
+        return Err(failure(vec![source.diagnostic("error", "check", e.to_string(), span)]));
+    }
+    let evaluator = evaluator.at(now);
+    let mut results = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let record = Value::from_json(record).map_err(|e| request_error(format!("record {i}: {e}")))?;
+        let decisions = evaluator.evaluate(&program, &record).map_err(|e| {
+            failure(vec![source.diagnostic("error", "evaluate", format!("record {i}: {e}"), None)])
+        })?;
+        let decisions: Vec<Json> = decisions.iter().map(|d| decision_json(d, &program)).collect();
+        results.push(json!({ "decisions": decisions }));
+    }
+    Ok(json!({ "ok": true, "results": results }))
+}
+
+/// A decision, with the metadata of its rule in `program` for routing.
+pub(crate) fn decision_json(d: &Decision, program: &Program) -> Json {
+    json!({
+        "rule": d.rule,
+        "statement": d.statement,
+        "action": d.action.to_string(),
+        "targets": d.targets.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "meta": d.rule(program).map_or_else(|| json!({}), meta_json),
+    })
+}
+
+/// A rule's metadata as an object. Durations are written as in the source.
+fn meta_json(rule: &Rule) -> Json {
+    fn literal_json(literal: &Literal) -> Json {
+        match literal {
+            Literal::Bool(b) => json!(b),
+            Literal::Number(n) => json!(n),
+            Literal::Str(s) => json!(s),
+            Literal::Duration { .. } => json!(literal.to_string()),
+            Literal::List(items) => items.iter().map(literal_json).collect(),
+        }
+    }
+    rule.metadata.iter().map(|(k, v)| (k.clone(), literal_json(v))).collect::<serde_json::Map<_, _>>().into()
+}
+
+pub(crate) fn statement_trace_json(t: &StatementTrace) -> Json {
+    json!({
+        "rule": t.rule,
+        "statement": t.statement,
+        "action": t.action.to_string(),
+        "fired": t.fired,
+        "in_force": t.in_force,
+        "traces": t.traces.iter().map(trace_json).collect::<Vec<_>>(),
+    })
+}
+
+fn trace_json(t: &Trace) -> Json {
+    let values: Vec<Json> = t.values.iter().map(|(name, v)| json!({ "name": name, "value": v.to_json() })).collect();
+    json!({
+        "expr": t.expr,
+        "result": t.result,
+        "values": values,
+        "children": t.children.iter().map(trace_json).collect::<Vec<_>>(),
+    })
+}
+
+/// A rule without source spans: name, doc comment, metadata, schedule and statements.
+pub(crate) fn rule_json(rule: &Rule) -> Json {
+    let statements: Vec<Json> = rule.statements.iter().map(|st| json!({
+        "condition": st.condition.to_string(),
+        "action": st.action.to_string(),
+        "mask": st.mask.map(|m| m.to_string()),
+    })).collect();
+    json!({
+        "name": rule.name,
+        "doc": rule.doc,
+        "meta": meta_json(rule),
+        "schedule": (!rule.schedule.is_always()).then(|| rule.schedule.to_string()),
+        "statements": statements,
+    })
+}
@@ -0,0 +1,697 @@
+use std::fmt;
+
+use crate::time::{format_timestamp, SECS_PER_DAY};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub rules: Vec<Rule>,
+    pub tests: Vec<Test>,
+}
+
+/// Changes to a base program for one tenant; see `overlay::resolve`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overlay {
+    pub items: Vec<OverlayItem>,
+    /// Tests of the effective program, run alongside the base's.
+    pub tests: Vec<Test>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverlayItem {
+    /// `rule name { ... }`: a rule the base doesn't have.
+    Add(Rule),
+    /// `override rule name { ... }`: replaces the base rule of the same name
+    /// in place. Without a doc comment or metadata, the base rule's are kept.
+    Override(Rule),
+    /// `disable rule name`
+    Disable(String),
+}
+
+impl OverlayItem {
+    /// The name of the rule added, overridden or disabled.
+    pub fn rule(&self) -> &str {
+        match self {
+            OverlayItem::Add(rule) | OverlayItem::Override(rule) => &rule.name,
+            OverlayItem::Disable(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// The `///` lines before the rule, joined with newlines.
+    pub doc: Option<String>,
+    pub name: String,
+    /// `[key: value, ...]` after the name, in order, with distinct keys;
+    /// e.g. `[owner: "data-team", severity: high, tags: [gdpr, retention]]`.
+    /// Bare words are strings.
+    pub metadata: Vec<(String, Literal)>,
+    /// When the rule is in force; always, unless written.
+    pub schedule: Schedule,
+    pub statements: Vec<Statement>,
+}
+
+impl Rule {
+    /// The metadata value under `key`.
+    pub fn meta(&self, key: &str) -> Option<&Literal> {
+        self.metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// The strings under `tags`, which may be a list or a single string.
+    pub fn tags(&self) -> Vec<&str> {
+        match self.meta("tags") {
+            Some(Literal::Str(tag)) => vec![tag],
+            Some(Literal::List(items)) => items.iter().filter_map(|item| match item {
+                Literal::Str(tag) => Some(tag.as_str()),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// When a rule is in force, written between its name and body:
+///
+/// ```text
+/// rule purge from "2026-01-01" until "2027-01-01" on saturday, sunday between "01:00" and "05:00" { ... }
+/// ```
+///
+/// Each part is optional. Times are UTC; see `Schedule::allows`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    /// The first second the rule is in force, since the Unix epoch.
+    pub from: Option<i64>,
+    /// The first second it no longer is, so `until "2027-01-01"` ends with 2026.
+    pub until: Option<i64>,
+    /// The days of the week it is in force, in order; empty for every day.
+    pub days: Vec<Weekday>,
+    /// The minutes after midnight at which its daily window opens and
+    /// closes. A window that closes before it opens spans midnight.
+    pub hours: Option<(u32, u32)>,
+}
+
+impl Schedule {
+    pub fn is_always(&self) -> bool {
+        *self == Schedule::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Weekday { Monday, Tuesday, Wednesday, Thursday, Friday, Saturday, Sunday }
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = {
+        use Weekday::*;
+        [Monday, Tuesday, Wednesday, Thursday, Friday, Saturday, Sunday]
+    };
+
+    pub fn name(self) -> &'static str {
+        use Weekday::*;
+        match self {
+            Monday=>"monday", Tuesday=>"tuesday", Wednesday=>"wednesday", Thursday=>"thursday",
+            Friday=>"friday", Saturday=>"saturday", Sunday=>"sunday",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub condition: Expr,
+    pub action: Action,
+    /// The strategy in `then mask(last 4)`; `None` leaves it to the masker.
+    pub mask: Option<MaskStrategy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action { Delete, Mask, Notify, Encrypt }
+
+/// How `mask` hides a value; see `mask::Masker` for what each does to strings,
+/// numbers and dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskStrategy {
+    /// `redact`: nothing is kept.
+    Redact,
+    /// `last N`: only the last N characters are kept.
+    KeepLast(u32),
+    /// `email`: the local part of an address is hidden, the domain kept.
+    Email,
+    /// `hash`: a salted hash, equal for equal values so joins still work.
+    Hash,
+    /// `month`: a date generalized to the first of its month.
+    Month,
+    /// `year`: a date generalized to January 1st of its year.
+    Year,
+}
+
+/// `test "name" { given { user.is_admin: true, field: ssn } expect no mask }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Test {
+    pub name: String,
+    /// The `now` to evaluate at, from `test "name" at "2026-01-03T02:00:00" { ... }`;
+    /// `None` uses the evaluator's.
+    pub at: Option<i64>,
+    /// Record fields to set, as plain key paths.
+    pub given: Vec<(Field, Literal)>,
+    pub expect: Vec<Expectation>,
+}
+
+/// A value in a test's `given` block. Bare identifiers are strings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Bool(bool),
+    Number(i64),
+    Str(String),
+    Duration { value: i64, unit: String },
+    List(Vec<Literal>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    /// `expect mask`: some statement with this action fires.
+    Fires(Action),
+    /// `expect no mask`
+    NotFires(Action),
+    /// `expect nothing`: no statement fires at all.
+    Nothing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Group(Box<Expr>),
+    Compare { left: Operand, op: CompOp, right: Operand },
+    In { field: Field, set: Vec<String> },
+    /// A function call used directly as a condition, e.g. `is_email(field)`.
+    Call(Call),
+    /// `any x in user.emails: <body>` / `all x in ...: <body>`
+    Quantified { quantifier: Quantifier, var: String, list: Operand, body: Box<Expr> },
+    /// `looks_like(ssn)`: the string value being scanned resembles sensitive
+    /// data; only meaningful in `Evaluator::scan`.
+    LooksLike(Detector),
+}
+
+/// A kind of sensitive data `looks_like` recognizes; see `scan.rs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Detector { Ssn, CreditCard, Email, Phone }
+
+impl Detector {
+    pub const ALL: [Detector; 4] = [Detector::Ssn, Detector::CreditCard, Detector::Email, Detector::Phone];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Detector::Ssn => "ssn",
+            Detector::CreditCard => "credit_card",
+            Detector::Email => "email",
+            Detector::Phone => "phone",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantifier { Any, All }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompOp { Eq, Ne, Gt, Lt, Ge, Le, StartsWith, EndsWith, Contains }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithOp { Add, Sub, Mul, Div, Rem }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Number(i64),
+    Str(String),
+    Bool(bool),
+    Duration { value: i64, unit: String },
+    Field(Field),
+    Call(Call),
+    /// The evaluation timestamp.
+    Now,
+    Neg(Box<Operand>),
+    Arith { left: Box<Operand>, op: ArithOp, right: Box<Operand> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field { pub segments: Vec<Segment> }
+
+/// One step of a field path: `.name`, `[index]`, `.*` or `.**`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+    /// `*`: any single key or index.
+    Wildcard,
+    /// `**`: zero or more keys or indexes.
+    Glob,
+}
+
+impl Field {
+    /// Whether the path contains `*` or `**` and must be expanded against a record.
+    pub fn is_pattern(&self) -> bool {
+        self.segments.iter().any(|s| matches!(s, Segment::Wildcard | Segment::Glob))
+    }
+
+    /// A path given on a command line or in configuration, written as it
+    /// prints, e.g. `record.contacts[0].phone` or `record.contacts.*.phone`.
+    pub fn from_dotted(path: &str) -> Field {
+        let mut segments = Vec::new();
+        for part in path.split('.') {
+            let (key, brackets) = part.split_once('[').map_or((part, None), |(k, rest)| (k, Some(rest)));
+            let indexes: Option<Vec<usize>> = match brackets {
+                None => Some(Vec::new()),
+                Some(rest) => rest.strip_suffix(']').and_then(|r| r.split("][").map(|i| i.parse().ok()).collect()),
+            };
+            // not an index after all, so part of the key
+            let Some(indexes) = indexes else { segments.push(Segment::Key(part.to_string())); continue };
+            segments.push(match key {
+                "*" => Segment::Wildcard,
+                "**" => Segment::Glob,
+                k => Segment::Key(k.to_string()),
+            });
+            segments.extend(indexes.into_iter().map(Segment::Index));
+        }
+        Field { segments }
+    }
+}
+
+impl Expr {
+    /// The record fields the condition reads, in order of first use. Paths
+    /// that start with a quantifier variable point into a list item rather
+    /// than the record, and are left out.
+    pub fn fields(&self) -> Vec<&Field> {
+        let mut out = Vec::new();
+        expr_fields(self, &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// The fields the condition is about: those of its first predicate that
+    /// reads any, e.g. `record.ssn` in `record.ssn != "" and record.age > 18`.
+    /// For a quantifier that is the list it ranges over.
+    pub fn subject(&self) -> Vec<&Field> {
+        match self {
+            Expr::Or(a, b) | Expr::And(a, b) => {
+                let subject = a.subject();
+                if subject.is_empty() { b.subject() } else { subject }
+            }
+            Expr::Not(e) | Expr::Group(e) => e.subject(),
+            Expr::Quantified { list, .. } => {
+                let mut out = Vec::new();
+                operand_fields(list, &mut Vec::new(), &mut out);
+                out
+            }
+            _ => self.fields(),
+        }
+    }
+
+    /// The `and` and `or` nodes of the condition in source order: those in
+    /// a node's left side, the node, then those in its right side.
+    pub fn connectives(&self) -> Vec<&Expr> {
+        let mut out = Vec::new();
+        expr_connectives(self, &mut out);
+        out
+    }
+
+    /// The detectors `looks_like` uses in the condition, in order; a
+    /// condition with any is only evaluated when scanning.
+    pub fn detectors(&self) -> Vec<Detector> {
+        let mut out = Vec::new();
+        expr_detectors(self, &mut out);
+        out
+    }
+}
+
+fn expr_detectors(expr: &Expr, out: &mut Vec<Detector>) {
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => { expr_detectors(a, out); expr_detectors(b, out) }
+        Expr::Not(e) | Expr::Group(e) => expr_detectors(e, out),
+        Expr::Quantified { body, .. } => expr_detectors(body, out),
+        Expr::LooksLike(detector) => if !out.contains(detector) { out.push(*detector) },
+        Expr::Compare { .. } | Expr::In { .. } | Expr::Call(_) => {}
+    }
+}
+
+fn expr_connectives<'a>(expr: &'a Expr, out: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => { expr_connectives(a, out); out.push(expr); expr_connectives(b, out) }
+        Expr::Not(e) | Expr::Group(e) => expr_connectives(e, out),
+        Expr::Quantified { body, .. } => expr_connectives(body, out),
+        Expr::Compare { .. } | Expr::In { .. } | Expr::Call(_) | Expr::LooksLike(_) => {}
+    }
+}
+
+fn expr_fields<'a>(expr: &'a Expr, bound: &mut Vec<&'a str>, out: &mut Vec<&'a Field>) {
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => { expr_fields(a, bound, out); expr_fields(b, bound, out) }
+        Expr::Not(e) | Expr::Group(e) => expr_fields(e, bound, out),
+        Expr::Compare { left, right, .. } => { operand_fields(left, bound, out); operand_fields(right, bound, out) }
+        Expr::In { field, .. } => add_field(field, bound, out),
+        Expr::LooksLike(_) => {}
+        Expr::Call(call) => call.args.iter().for_each(|a| operand_fields(a, bound, out)),
+        Expr::Quantified { var, list, body, .. } => {
+            operand_fields(list, bound, out);
+            bound.push(var);
+            expr_fields(body, bound, out);
+            bound.pop();
+        }
+    }
+}
+
+fn operand_fields<'a>(operand: &'a Operand, bound: &mut Vec<&'a str>, out: &mut Vec<&'a Field>) {
+    match operand {
+        Operand::Field(f) => add_field(f, bound, out),
+        Operand::Call(call) => call.args.iter().for_each(|a| operand_fields(a, bound, out)),
+        Operand::Neg(inner) => operand_fields(inner, bound, out),
+        Operand::Arith { left, right, .. } => { operand_fields(left, bound, out); operand_fields(right, bound, out) }
+        _ => {}
+    }
+}
+
+fn add_field<'a>(field: &'a Field, bound: &[&str], out: &mut Vec<&'a Field>) {
+    if let Some(Segment::Key(first)) = field.segments.first() && bound.contains(&first.as_str()) { return; }
+    if !out.contains(&field) { out.push(field); }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, seg) in self.segments.iter().enumerate() {
+            if i > 0 && !matches!(seg, Segment::Index(_)) { write!(f, ".")?; }
+            match seg {
+                Segment::Key(k) => write!(f, "{k}")?,
+                Segment::Index(n) => write!(f, "[{n}]")?,
+                Segment::Wildcard => write!(f, "*")?,
+                Segment::Glob => write!(f, "**")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub name: String,
+    pub args: Vec<Operand>,
+}
+
+impl fmt::Display for CompOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CompOp::*;
+        write!(f, "{}", match self {
+            Eq=>"==", Ne=>"!=", Gt=>">", Lt=>"<", Ge=>">=", Le=>"<=",
+            StartsWith=>"starts_with", EndsWith=>"ends_with", Contains=>"contains",
+        })
+    }
+}
+
+impl fmt::Display for Quantifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self { Quantifier::Any => "any", Quantifier::All => "all" })
+    }
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ArithOp::*;
+        write!(f, "{}", match self { Add=>"+", Sub=>"-", Mul=>"*", Div=>"/", Rem=>"%" })
+    }
+}
+
+/// Number of seconds in one `unit` of a duration literal, e.g. `days`.
+pub fn unit_seconds(unit: &str) -> Option<i64> {
+    match unit {
+        "second" | "seconds" => Some(1),
+        "minute" | "minutes" => Some(60),
+        "hour" | "hours" => Some(60 * 60),
+        "day" | "days" => Some(24 * 60 * 60),
+        "week" | "weeks" => Some(7 * 24 * 60 * 60),
+        _ => None,
+    }
+}
+
+//
+// ===== PRINTER =====
+//
+// `Display` renders the AST back to DSL source. Output of a parsed program
+// parses back to the same AST: `Group`s are printed as written and operands
+// get the minimal parentheses their precedence needs.
+//
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if i > 0 { writeln!(f)?; }
+            write!(f, "{rule}")?;
+        }
+        for (i, test) in self.tests.iter().enumerate() {
+            if i > 0 || !self.rules.is_empty() { writeln!(f)?; }
+            write!(f, "{test}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Overlay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 { writeln!(f)?; }
+            match item {
+                OverlayItem::Add(rule) => write!(f, "{rule}")?,
+                OverlayItem::Override(rule) => {
+                    write_doc(f, &rule.doc)?;
+                    write!(f, "override ")?;
+                    rule.write_body(f)?;
+                }
+                OverlayItem::Disable(name) => writeln!(f, "disable rule {name};")?,
+            }
+        }
+        for (i, test) in self.tests.iter().enumerate() {
+            if i > 0 || !self.items.is_empty() { writeln!(f)?; }
+            write!(f, "{test}")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_doc(f: &mut fmt::Formatter<'_>, doc: &Option<String>) -> fmt::Result {
+    for line in doc.iter().flat_map(|d| d.split('\n')) {
+        if line.is_empty() { writeln!(f, "///")? } else { writeln!(f, "/// {line}")? }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_doc(f, &self.doc)?;
+        self.write_body(f)
+    }
+}
+
+impl Rule {
+    /// The rule from its `rule` keyword on, without the doc comment.
+    fn write_body(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rule {} ", self.name)?;
+        if !self.metadata.is_empty() {
+            let entries: Vec<String> = self.metadata.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+            write!(f, "[{}] ", entries.join(", "))?;
+        }
+        if !self.schedule.is_always() { write!(f, "{} ", self.schedule)?; }
+        writeln!(f, "{{")?;
+        for st in &self.statements {
+            writeln!(f, "    {st};")?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// A timestamp as a string literal, with just the date when it is midnight.
+fn write_date(f: &mut fmt::Formatter<'_>, ts: i64) -> fmt::Result {
+    let full = format_timestamp(ts);
+    let text = if ts.rem_euclid(SECS_PER_DAY) == 0 { &full[..full.len() - "T00:00:00Z".len()] } else { &full };
+    write_str(f, text)
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(from) = self.from { write!(f, "from ")?; write_date(f, from)?; sep = " "; }
+        if let Some(until) = self.until { write!(f, "{sep}until ")?; write_date(f, until)?; sep = " "; }
+        if !self.days.is_empty() {
+            let days: Vec<&str> = self.days.iter().map(|d| d.name()).collect();
+            write!(f, "{sep}on {}", days.join(", "))?;
+            sep = " ";
+        }
+        if let Some((open, close)) = self.hours {
+            write!(f, "{sep}between \"{:02}:{:02}\" and \"{:02}:{:02}\"", open / 60, open % 60, close / 60, close % 60)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "if {} then {}", self.condition, self.action)?;
+        if let Some(strategy) = self.mask { write!(f, "({strategy})")?; }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MaskStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaskStrategy::Redact => write!(f, "redact"),
+            MaskStrategy::KeepLast(n) => write!(f, "last {n}"),
+            MaskStrategy::Email => write!(f, "email"),
+            MaskStrategy::Hash => write!(f, "hash"),
+            MaskStrategy::Month => write!(f, "month"),
+            MaskStrategy::Year => write!(f, "year"),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Action::*;
+        write!(f, "{}", match self { Delete=>"delete", Mask=>"mask", Notify=>"notify", Encrypt=>"encrypt" })
+    }
+}
+
+impl fmt::Display for Test {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "test ")?;
+        write_str(f, &self.name)?;
+        if let Some(at) = self.at { write!(f, " at ")?; write_date(f, at)?; }
+        writeln!(f, " {{")?;
+        write!(f, "    given {{")?;
+        for (i, (field, value)) in self.given.iter().enumerate() {
+            write!(f, "{}{field}: {value}", if i > 0 { ", " } else { " " })?;
+        }
+        writeln!(f, "{}}}", if self.given.is_empty() { "" } else { " " })?;
+        write!(f, "    expect")?;
+        for (i, e) in self.expect.iter().enumerate() {
+            write!(f, "{}{e}", if i > 0 { " and " } else { " " })?;
+        }
+        writeln!(f)?;
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Bool(b) => write!(f, "{b}"),
+            Literal::Number(n) => write!(f, "{n}"),
+            Literal::Str(s) => write_str(f, s),
+            Literal::Duration { value, unit } => write!(f, "{value} {unit}"),
+            Literal::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Fires(action) => write!(f, "{action}"),
+            Expectation::NotFires(action) => write!(f, "no {action}"),
+            Expectation::Nothing => write!(f, "nothing"),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Or(a, b) => write!(f, "{a} or {b}"),
+            Expr::And(a, b) => write!(f, "{a} and {b}"),
+            Expr::Not(e) => write!(f, "not {e}"),
+            Expr::Group(e) => write!(f, "({e})"),
+            Expr::Compare { left, op, right } => write!(f, "{left} {op} {right}"),
+            Expr::In { field, set } => write!(f, "{field} in [{}]", set.join(", ")),
+            Expr::Call(call) => write!(f, "{call}"),
+            Expr::Quantified { quantifier, var, list, body } => write!(f, "{quantifier} {var} in {list}: {body}"),
+            Expr::LooksLike(detector) => write!(f, "looks_like({})", detector.name()),
+        }
+    }
+}
+
+impl Operand {
+    /// Binding strength, used to decide where the printer needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Operand::Arith { op: ArithOp::Add | ArithOp::Sub, .. } => 1,
+            Operand::Arith { .. } => 2,
+            Operand::Neg(_) => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// A string literal with `"` and `\\` escaped.
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        if c == '"' || c == '\\' { write!(f, "\\")?; }
+        write!(f, "{c}")?;
+    }
+    write!(f, "\"")
+}
+
+fn write_operand(f: &mut fmt::Formatter<'_>, operand: &Operand, parens: bool) -> fmt::Result {
+    if parens { write!(f, "({operand})") } else { write!(f, "{operand}") }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Number(n) => write!(f, "{n}"),
+            Operand::Str(s) => write_str(f, s),
+            Operand::Bool(b) => write!(f, "{b}"),
+            Operand::Duration { value, unit } => write!(f, "{value} {unit}"),
+            Operand::Field(field) => write!(f, "{field}"),
+            Operand::Call(call) => write!(f, "{call}"),
+            Operand::Now => write!(f, "now"),
+            Operand::Neg(inner) => {
+                write!(f, "-")?;
+                write_operand(f, inner, inner.precedence() < self.precedence())
+            }
+            Operand::Arith { left, op, right } => {
+                // operators are left-associative, so an equal-precedence right side needs parens
+                write_operand(f, left, left.precedence() < self.precedence())?;
+                write!(f, " {op} ")?;
+                write_operand(f, right, right.precedence() <= self.precedence())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 { write!(f, ", ")?; }
+            write!(f, "{arg}")?;
+        }
+        write!(f, ")")
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::ast::{Action, Field, Program};
+use crate::eval::{Decision, EvalError, Evaluator};
+use crate::time::format_timestamp;
+use crate::value::Value;
+
+//
+// ===== AUDIT LOG =====
+//
+// One event per fired statement, so it can be shown later which rule deleted
+// or masked which record under which version of the policy. Field values are
+// logged as keyed HMAC-SHA256 hashes unless allowlisted: someone holding the
+// key can check whether a field had a given value, but the log itself is not
+// another copy of the data the policy protects.
+//
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error("audit sink: {0}")]
+    Sink(#[from] io::Error),
+}
+
+/// A field value as it appears in the log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Logged {
+    /// Allowlisted with `Auditor::clear`.
+    Clear(Value),
+    /// Hex HMAC-SHA256 of the value's JSON under the auditor's key.
+    Hash(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    /// Evaluation time, in seconds since the Unix epoch.
+    pub timestamp: i64,
+    /// `policy_hash` of the program evaluated.
+    pub policy: String,
+    pub rule: String,
+    pub statement: usize,
+    pub action: Action,
+    /// The record's id, if the auditor has an id field and the record has it.
+    pub record: Option<String>,
+    /// The fields the statement's condition read, as concrete paths.
+    pub fields: Vec<(String, Logged)>,
+}
+
+impl AuditEvent {
+    /// The event as one JSON object; hashed values are `{"hmac": ...}` and
+    /// clear ones `{"value": ...}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let fields: serde_json::Map<String, serde_json::Value> = self.fields.iter().map(|(path, logged)| {
+            let v = match logged {
+                Logged::Clear(v) => json!({ "value": v.to_json() }),
+                Logged::Hash(h) => json!({ "hmac": h }),
+            };
+            (path.clone(), v)
+        }).collect();
+        json!({
+            "time": format_timestamp(self.timestamp),
+            "policy": self.policy,
+            "rule": self.rule,
+            "statement": self.statement,
+            "action": self.action.to_string(),
+            "record": self.record,
+            "fields": fields,
+        })
+    }
+}
+
+/// Where audit events go. A write error fails the evaluation being audited.
+pub trait AuditSink: Send + Sync {
+    fn write(&self, event: &AuditEvent) -> io::Result<()>;
+}
+
+impl<S: AuditSink + ?Sized> AuditSink for Arc<S> {
+    fn write(&self, event: &AuditEvent) -> io::Result<()> { (**self).write(event) }
+}
+
+/// Appends one JSON object per line to a file, flushing after each event.
+pub struct JsonLinesSink {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl JsonLinesSink {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { out: Mutex::new(BufWriter::new(file)) })
+    }
+}
+
+impl AuditSink for JsonLinesSink {
+    fn write(&self, event: &AuditEvent) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{}", event.to_json())?;
+        out.flush()
+    }
+}
+
+/// Keeps events in memory, for tests and for callers that ship them elsewhere.
+#[derive(Default)]
+pub struct MemorySink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl AuditSink for MemorySink {
+    fn write(&self, event: &AuditEvent) -> io::Result<()> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes { let _ = write!(out, "{b:02x}"); }
+    out
+}
+
+/// Identifies a version of a policy: the SHA-256 of its printed form, so
+/// reformatting and `//` comments don't change it.
+pub fn policy_hash(program: &Program) -> String {
+    format!("sha256:{}", hex(&Sha256::digest(program.to_string())))
+}
+
+/// Evaluates records and logs an event for every statement that fires.
+pub struct Auditor {
+    sink: Box<dyn AuditSink>,
+    key: Vec<u8>,
+    id_field: Option<Field>,
+    clear: Vec<String>,
+}
+
+impl Auditor {
+    /// Log to `sink`, hashing field values with `key`. Keep the key secret:
+    /// short values such as dates or country codes are easy to guess from an
+    /// unkeyed hash.
+    pub fn new(sink: impl AuditSink + 'static, key: &[u8]) -> Self {
+        Self { sink: Box::new(sink), key: key.to_vec(), id_field: None, clear: Vec::new() }
+    }
+
+    /// Identify records by the dotted path `path`, e.g. `record.id`. The id is
+    /// logged in clear, so it should be an opaque key rather than personal data.
+    pub fn id_field(mut self, path: &str) -> Self {
+        self.id_field = Some(Field::from_dotted(path));
+        self
+    }
+
+    /// Log the field at `path` (as printed, e.g. `record.country`) in clear.
+    pub fn clear(mut self, path: &str) -> Self {
+        self.clear.push(path.to_string());
+        self
+    }
+
+    /// The keyed hash logged for `value`.
+    pub fn hash(&self, value: &Value) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(value.to_json().to_string().as_bytes());
+        hex(&mac.finalize().into_bytes())
+    }
+
+    /// Evaluate `record` and log each decision. Decisions are returned only
+    /// once all of them are logged; don't act on a record whose audit failed.
+    pub fn evaluate(&self, evaluator: &Evaluator, program: &Program, record: &Value) -> Result<Vec<Decision>, AuditError> {
+        let decisions = evaluator.evaluate(program, record)?;
+        self.log(program, evaluator.now(), record, &decisions)?;
+        Ok(decisions)
+    }
+
+    /// Log `decisions`, made by evaluating `record` against `program` at `now`.
+    pub fn log(&self, program: &Program, now: i64, record: &Value, decisions: &[Decision]) -> io::Result<()> {
+        if decisions.is_empty() { return Ok(()); }
+        let policy = policy_hash(program);
+        let id = self.id_field.as_ref().map(|f| record.get(f)).and_then(|v| match v {
+            Value::Null => None,
+            Value::Str(s) => Some(s.clone()),
+            v => Some(v.to_string()),
+        });
+        for decision in decisions {
+            let fields = decision.fields(program).into_iter()
+                .map(|f| (f.to_string(), self.logged(&f.to_string(), record.get(f))))
+                .collect();
+            self.sink.write(&AuditEvent {
+                timestamp: now,
+                policy: policy.clone(),
+                rule: decision.rule.clone(),
+                statement: decision.statement,
+                action: decision.action,
+                record: id.clone(),
+                fields,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn logged(&self, path: &str, value: &Value) -> Logged {
+        if self.clear.iter().any(|c| c == path) { Logged::Clear(value.clone()) } else { Logged::Hash(self.hash(value)) }
+    }
+}
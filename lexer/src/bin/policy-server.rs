@@ -0,0 +1,43 @@
+use std::env;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use lexer::{Evaluator, PolicyEngine, Server};
+
+fn main() -> ExitCode {
+    match run(&env::args().skip(1).collect::<Vec<_>>()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = "Usage: policy-server <policy file or directory> [--addr <host:port>] [--poll <seconds>]\n\n\
+        Serves POST /evaluate, POST /validate and GET /rules. The policy is reloaded\n\
+        when it changes, checked every --poll seconds (default 2; 0 never reloads).";
+    let (mut path, mut addr, mut poll) = (None, "127.0.0.1:7878".to_string(), 2);
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--addr" => addr = it.next().ok_or("--addr needs a host:port")?.clone(),
+            "--poll" => poll = it.next().ok_or("--poll needs a number of seconds")?.parse().map_err(|_| "--poll needs a number of seconds")?,
+            "-h" | "--help" => { println!("{usage}"); return Ok(()) }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(usage.into()),
+        }
+    }
+    let path = path.ok_or(usage)?;
+
+    let engine = PolicyEngine::load(path)?;
+    let _watcher = (poll > 0).then(|| engine.watch(Duration::from_secs(poll), |outcome| match outcome {
+        Ok(snapshot) => eprintln!("reloaded the policy, now version {}", snapshot.version),
+        Err(e) => eprintln!("reload failed, keeping the current version: {e}"),
+    }));
+    let server = Server::bind(&addr, engine, Evaluator::new()).map_err(|e| format!("{addr}: {e}"))?;
+    eprintln!("serving {} on http://{}", path, server.local_addr()?);
+    server.run();
+    Ok(())
+}
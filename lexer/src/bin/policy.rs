@@ -0,0 +1,463 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use lexer::{
+    analyze, check_program, diff_programs, diff_records, docs, records_from_jsonl, run_tests, to_sql, Cipher, Coverage, Dialect,
+    Evaluator, Field, Keyring, Lexer, LineIndex, LintCode, LintConfig, Literal, Overlay, ParseError, Parser, Program, ScanReport, Schema, Severity, Tenants,
+    Value,
+};
+use lexer::time::parse_timestamp;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        display_help();
+        return ExitCode::FAILURE;
+    }
+
+    let result = match args[1].as_str() {
+        "lint" => lint(&args[2..]),
+        "list" => list(&args[2..]),
+        "diff" => diff(&args[2..]),
+        "test" => test(&args[2..]),
+        "docs" => docs(&args[2..]),
+        "coverage" => coverage(&args[2..]),
+        "scan" => scan(&args[2..]),
+        "sql" => sql(&args[2..]),
+        "tenants" => tenants(&args[2..]),
+        "encrypt" => encrypt(&args[2..]),
+        "decrypt" => decrypt(&args[2..]),
+        "help" => { display_help(); Ok(true) }
+        _ => { display_help(); Ok(false) }
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn display_help() {
+    println!("Usage: policy <command> [args]");
+    println!();
+    println!("Commands:");
+    println!("  lint <file> [--allow|--warn|--deny <lint>]...");
+    println!("      report statements that never fire, always fire or are subsumed");
+    println!("      lints: {}", LintCode::ALL.map(LintCode::name).join(", "));
+    println!("  list <file> [--tag <tag>]... [--meta <key>=<value>]...");
+    println!("      list the rules and their metadata, keeping those with every given tag and value");
+    println!("  diff <old> <new> [--records <file.jsonl>] [--now <time>]");
+    println!("      report added, removed, renamed and changed rules; with --records,");
+    println!("      also list the sample records the new version treats differently");
+    println!("  test <file>...");
+    println!("      run the `test` blocks in each file and explain any failures");
+    println!("  docs <file> [--html]");
+    println!("      print Markdown (or HTML) documentation of the rules for reviewers");
+    println!("  coverage <file> <records.jsonl> [--html] [--now <time>]");
+    println!("      report the rules and statements that never fired over the records, and the");
+    println!("      sides of `and`/`or` that never decided a condition; --html annotates the source");
+    println!("  scan <file> <records.jsonl> [--json] [--now <time>]");
+    println!("      report the fields whose string values the `looks_like` statements flag, as");
+    println!("      candidates for the schema; --json prints one object per field and rule");
+    println!("  sql <file> --table <name> [--row <prefix>] [--column <field>=<column>]...");
+    println!("      [--column-var <name>] [--protect <column>]... [--postgres]");
+    println!("      print DELETE/UPDATE/SELECT statements enforcing the rules in the database");
+    println!("  tenants <base> <overlay>... [--effective <tenant>]");
+    println!("      report where each tenant's overlay makes its policy differ from the base, or");
+    println!("      print one tenant's effective policy; tenants are named by overlay file name");
+    println!("  encrypt <file> <records.jsonl> --keyring <file> [--key <id>] [--tokenize <path>]...");
+    println!("      print the records with the fields matched by `encrypt` statements encrypted,");
+    println!("      or tokenized in the same format for fields matching a --tokenize path, with");
+    println!("      the key a `tokenize <path> <key id>` line in the keyring binds them to");
+    println!("  decrypt <records.jsonl> --keyring <file> [--key <id>] [--tokenize <path>]...");
+    println!("      print the records with every encrypted or tokenized value restored");
+    println!("  help");
+    println!();
+    println!("--now evaluates as of a time like 2026-01-03T02:00:00 (UTC) instead of the current time.");
+}
+
+/// Lex a file and parse it with `parse`, reporting errors at `path:line:col`.
+fn parse_file<T>(
+    path: &str,
+    parse: impl FnOnce(&mut Parser) -> Result<T, ParseError>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let src = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let index = LineIndex::new(&src);
+    let tokens = Lexer::new(&src).collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{path}:{}: {e}", index.line_col(e.offset())))?;
+    let mut parser = Parser::new(tokens.iter().map(|(t, _)| t.clone()).collect());
+    Ok(parse(&mut parser).map_err(|e| {
+        // an error at end of input points just past the last token
+        let end = tokens.last().map_or(0, |(_, span)| span.end);
+        let offset = parser.error_token(&e).map_or(end, |i| tokens[i].1.start);
+        format!("{path}:{}: {e}", index.line_col(offset))
+    })?)
+}
+
+/// Lex, parse and type-check a policy file.
+fn load_program(path: &str) -> Result<Program, Box<dyn std::error::Error>> {
+    let program = parse_file(path, |p| p.parse_program())?;
+    check_program(&program)?;
+    Ok(program)
+}
+
+/// An evaluator at `--now`, if given, or the current time.
+fn evaluator(now: Option<&String>) -> Result<Evaluator, Box<dyn std::error::Error>> {
+    match now {
+        Some(now) => Ok(Evaluator::with_now(parse_timestamp(now).ok_or_else(|| format!("--now: invalid time '{now}'"))?)),
+        None => Ok(Evaluator::new()),
+    }
+}
+
+/// Lex and parse a tenant overlay file.
+fn load_overlay(path: &str) -> Result<Overlay, Box<dyn std::error::Error>> {
+    parse_file(path, |p| p.parse_overlay())
+}
+
+fn lint(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut config = LintConfig::default();
+    let mut path = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let severity = match arg.as_str() {
+            "--allow" => Severity::Allow,
+            "--warn" => Severity::Warn,
+            "--deny" => Severity::Deny,
+            _ if path.is_none() => { path = Some(arg.as_str()); continue; }
+            _ => return Err(format!("unexpected argument '{arg}'").into()),
+        };
+        let name = it.next().ok_or_else(|| format!("{arg} needs a lint name"))?;
+        let code = LintCode::from_name(name).ok_or_else(|| format!("unknown lint '{name}'"))?;
+        config.set(code, severity);
+    }
+    let path = path.ok_or("Usage: policy lint <file> [--allow|--warn|--deny <lint>]...")?;
+
+    let program = load_program(path)?;
+    let lints = analyze(&program, &config);
+    for l in &lints {
+        println!("{path}: {l}");
+    }
+    Ok(!lints.iter().any(|l| l.severity == Severity::Deny))
+}
+
+fn list(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy list <file> [--tag <tag>]... [--meta <key>=<value>]...";
+    let (mut path, mut wanted) = (None, Vec::new());
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--tag" => wanted.push(("tags", it.next().ok_or("--tag needs a tag")?.as_str())),
+            "--meta" => wanted.push(it.next().and_then(|m| m.split_once('=')).ok_or("--meta takes <key>=<value>")?),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(usage.into()),
+        }
+    }
+    let program = load_program(path.ok_or(usage)?)?;
+
+    // a list matches if any item does, so `--tag` finds one tag among several
+    fn matches(literal: &Literal, value: &str) -> bool {
+        match literal {
+            Literal::Str(s) => s == value,
+            Literal::List(items) => items.iter().any(|item| matches(item, value)),
+            literal => literal.to_string() == value,
+        }
+    }
+    for rule in &program.rules {
+        if !wanted.iter().all(|(key, value)| rule.meta(key).is_some_and(|v| matches(v, value))) { continue; }
+        let metadata: Vec<String> = rule.metadata.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+        if metadata.is_empty() {
+            println!("{}", rule.name);
+        } else {
+            println!("{} [{}]", rule.name, metadata.join(", "));
+        }
+    }
+    Ok(true)
+}
+
+fn diff(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy diff <old> <new> [--records <file.jsonl>] [--now <time>]";
+    let mut paths = Vec::new();
+    let (mut records, mut now) = (None, None);
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--records" => records = Some(it.next().ok_or("--records needs a file")?),
+            "--now" => now = Some(it.next().ok_or("--now needs a time")?),
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    let [old_path, new_path] = paths[..] else { return Err(usage.into()) };
+    let evaluator = evaluator(now)?;
+
+    let old = load_program(old_path)?;
+    let new = load_program(new_path)?;
+    let changes = diff_programs(&old, &new);
+    if changes.is_empty() {
+        println!("no rule changes");
+    }
+    for c in &changes {
+        println!("{c}");
+    }
+
+    if let Some(path) = records {
+        let records = load_records(path)?;
+        let affected = diff_records(&old, &new, &records, &evaluator)?;
+        println!();
+        println!("{} of {} records treated differently", affected.len(), records.len());
+        for r in &affected {
+            println!("  {r}");
+        }
+    }
+    Ok(true)
+}
+
+fn test(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        return Err("Usage: policy test <file>...".into());
+    }
+    let evaluator = Evaluator::new();
+    let (mut passed, mut failed) = (0, 0);
+    for path in args {
+        let program = load_program(path)?;
+        for result in run_tests(&program, &evaluator).map_err(|e| format!("{path}: {e}"))? {
+            if result.passed() {
+                passed += 1;
+                println!("PASS {path}: {}", result.name);
+                continue;
+            }
+            failed += 1;
+            println!("FAIL {path}: {}", result.name);
+            for failure in &result.failures {
+                println!("  {failure}");
+            }
+            for t in &result.trace {
+                for line in t.to_string().lines() {
+                    println!("    {line}");
+                }
+            }
+        }
+    }
+    println!();
+    println!("{passed} passed, {failed} failed");
+    Ok(failed == 0)
+}
+
+fn docs(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy docs <file> [--html]";
+    let html = args.iter().any(|a| a == "--html");
+    let paths: Vec<&String> = args.iter().filter(|a| *a != "--html").collect();
+    let [path] = paths[..] else { return Err(usage.into()) };
+
+    let program = load_program(path)?;
+    let title = std::path::Path::new(path).file_stem().map_or(path.clone(), |s| s.to_string_lossy().into_owned());
+    if html {
+        print!("{}", docs::html(&title, &program));
+    } else {
+        print!("{}", docs::markdown(&title, &program));
+    }
+    Ok(true)
+}
+
+fn coverage(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy coverage <file> <records.jsonl> [--html] [--now <time>]";
+    let (mut paths, mut html, mut now) = (Vec::new(), false, None);
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--html" => html = true,
+            "--now" => now = Some(it.next().ok_or("--now needs a time")?),
+            _ => paths.push(arg),
+        }
+    }
+    let [path, records] = paths[..] else { return Err(usage.into()) };
+
+    let program = load_program(path)?;
+    let evaluator = evaluator(now)?;
+    let mut coverage = Coverage::new(&program);
+    for (i, record) in load_records(records)?.iter().enumerate() {
+        evaluator.evaluate_covered(&program, record, &mut coverage).map_err(|e| format!("{records}: record {}: {e}", i + 1))?;
+    }
+    if html {
+        let src = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        print!("{}", coverage.html(path, &src)?);
+    } else {
+        print!("{coverage}");
+    }
+    Ok(true)
+}
+
+fn scan(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy scan <file> <records.jsonl> [--json] [--now <time>]";
+    let (mut paths, mut json, mut now) = (Vec::new(), false, None);
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--now" => now = Some(it.next().ok_or("--now needs a time")?),
+            _ => paths.push(arg),
+        }
+    }
+    let [path, records] = paths[..] else { return Err(usage.into()) };
+
+    let program = load_program(path)?;
+    let evaluator = evaluator(now)?;
+    let mut report = ScanReport::new();
+    for (i, record) in load_records(records)?.iter().enumerate() {
+        let findings = evaluator.scan(&program, record).map_err(|e| format!("{records}: record {}: {e}", i + 1))?;
+        report.add(record, &findings);
+    }
+    if !json {
+        print!("{report}");
+        return Ok(true);
+    }
+    for candidate in report.candidates() {
+        let tags = program.rules.iter().find(|r| r.name == candidate.rule).map(|r| r.tags()).unwrap_or_default();
+        println!("{}", serde_json::json!({
+            "field": candidate.field.to_string(),
+            "rule": candidate.rule,
+            "tags": tags,
+            "matches": candidate.matches,
+            "values": candidate.values,
+        }));
+    }
+    Ok(true)
+}
+
+fn sql(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy sql <file> --table <name> [--row <prefix>] [--column <field>=<column>]... \
+                 [--column-var <name>] [--protect <column>]... [--postgres]";
+    let (mut path, mut table, mut dialect) = (None, None, Dialect::Sqlite);
+    let mut options = Vec::new();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--postgres" => dialect = Dialect::Postgres,
+            "--table" => table = Some(it.next().ok_or("--table needs a name")?),
+            "--row" | "--column" | "--column-var" | "--protect" => {
+                options.push((arg.as_str(), it.next().ok_or_else(|| format!("{arg} needs a value"))?));
+            }
+            _ if path.is_none() => path = Some(arg.as_str()),
+            _ => return Err(format!("unexpected argument '{arg}'").into()),
+        }
+    }
+    let (Some(path), Some(table)) = (path, table) else { return Err(usage.into()) };
+    let mut schema = Schema::new(table.as_str());
+    for (option, value) in options {
+        schema = match option {
+            "--row" => schema.row(value.as_str()),
+            "--column-var" => schema.column_var(value.as_str()),
+            "--protect" => schema.protect(value.as_str()),
+            _ => {
+                let (field, column) = value.split_once('=').ok_or("--column takes <field>=<column>")?;
+                schema.column(field, column)
+            }
+        };
+    }
+
+    let program = load_program(path)?;
+    let (statements, errors) = to_sql(&program, &schema, dialect);
+    for st in &statements {
+        println!("{st}");
+    }
+    for e in &errors {
+        eprintln!("{path}: {e}");
+    }
+    Ok(errors.is_empty())
+}
+
+/// The `--keyring`, `--key` and `--tokenize` options shared by `encrypt` and
+/// `decrypt`, and the remaining arguments.
+fn cipher_args(args: &[String]) -> Result<(Cipher, Vec<&str>), Box<dyn std::error::Error>> {
+    let (mut keyring, mut key, mut tokenize, mut rest) = (None, None, Vec::new(), Vec::new());
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--keyring" => keyring = Some(it.next().ok_or("--keyring needs a file")?),
+            "--key" => key = Some(it.next().ok_or("--key needs a key id")?),
+            "--tokenize" => tokenize.push(Field::from_dotted(it.next().ok_or("--tokenize needs a path")?)),
+            _ => rest.push(arg.as_str()),
+        }
+    }
+    let keyring = keyring.ok_or("--keyring is required")?;
+    let mut cipher = Cipher::new(Keyring::load(keyring)?);
+    if let Some(id) = key {
+        cipher = cipher.with_key(id)?;
+    }
+    Ok((tokenize.into_iter().fold(cipher, Cipher::tokenize), rest))
+}
+
+fn load_records(path: &str) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let src = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    Ok(records_from_jsonl(&src).map_err(|e| format!("{path}: {e}"))?)
+}
+
+fn tenants(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy tenants <base> <overlay>... [--effective <tenant>]";
+    let mut paths = Vec::new();
+    let mut effective = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--effective" => effective = Some(it.next().ok_or("--effective needs a tenant")?),
+            _ => paths.push(arg.as_str()),
+        }
+    }
+    let [base, overlays @ ..] = &paths[..] else { return Err(usage.into()) };
+    if overlays.is_empty() {
+        return Err(usage.into());
+    }
+
+    let mut tenants = Tenants::new(load_program(base)?);
+    for path in overlays {
+        let name = std::path::Path::new(path).file_stem().map_or(path.to_string(), |s| s.to_string_lossy().into_owned());
+        tenants = tenants.tenant(name, load_overlay(path)?);
+    }
+    // every effective policy must type-check, whichever is printed
+    let names: Vec<String> = tenants.names().map(String::from).collect();
+    for name in &names {
+        check_program(&tenants.effective(name)?).map_err(|e| format!("tenant '{name}': {e}"))?;
+    }
+
+    if let Some(name) = effective {
+        print!("{}", tenants.effective(name)?);
+        return Ok(true);
+    }
+    for (i, report) in tenants.report()?.iter().enumerate() {
+        if i > 0 { println!(); }
+        print!("{report}");
+    }
+    Ok(true)
+}
+
+fn encrypt(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy encrypt <file> <records.jsonl> --keyring <file> [--key <id>] [--tokenize <path>]...";
+    let (cipher, rest) = cipher_args(args)?;
+    let [path, records] = rest[..] else { return Err(usage.into()) };
+
+    let program = load_program(path)?;
+    let evaluator = Evaluator::new();
+    for (i, mut record) in load_records(records)?.into_iter().enumerate() {
+        let decisions = evaluator.evaluate(&program, &record)?;
+        cipher.apply(&program, &decisions, &mut record).map_err(|e| format!("{records}: record {}: {e}", i + 1))?;
+        println!("{}", record.to_json());
+    }
+    Ok(true)
+}
+
+fn decrypt(args: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    let usage = "Usage: policy decrypt <records.jsonl> --keyring <file> [--key <id>] [--tokenize <path>]...";
+    let (cipher, rest) = cipher_args(args)?;
+    let [records] = rest[..] else { return Err(usage.into()) };
+
+    for (i, mut record) in load_records(records)?.into_iter().enumerate() {
+        cipher.decrypt_record(&mut record).map_err(|e| format!("{records}: record {}: {e}", i + 1))?;
+        println!("{}", record.to_json());
+    }
+    Ok(true)
+}
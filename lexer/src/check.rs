@@ -0,0 +1,159 @@
+use std::fmt;
+use thiserror::Error;
+
+use crate::ast::*;
+use crate::functions::Functions;
+use crate::value::Value;
+
+//
+// ===== TYPE CHECKER =====
+//
+
+/// Static type of an operand. Fields are `Any` until evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type { Number, String, Bool, Duration, Timestamp, Any }
+
+impl Type {
+    /// Runtime type of a value; `null`, lists and maps have no static type.
+    pub fn of(value: &Value) -> Type {
+        match value {
+            Value::Number(_) | Value::Float(_) => Type::Number,
+            Value::Str(_) => Type::String,
+            Value::Bool(_) => Type::Bool,
+            Value::Duration(_) => Type::Duration,
+            Value::Timestamp(_) => Type::Timestamp,
+            Value::Null | Value::List(_) | Value::Map(_) => Type::Any,
+        }
+    }
+
+    /// Whether a parameter of this type accepts an argument of type `arg`.
+    pub fn accepts(self, arg: Type) -> bool {
+        self == arg || self == Type::Any || arg == Type::Any
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Type::*;
+        write!(f, "{}", match self { Number=>"number", String=>"string", Bool=>"bool", Duration=>"duration", Timestamp=>"timestamp", Any=>"any" })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TypeError {
+    #[error("unknown duration unit '{0}'")]
+    UnknownUnit(String),
+    #[error("cannot apply '{op}' to {left} and {right}")]
+    Arith { op: ArithOp, left: Type, right: Type },
+    #[error("cannot negate {0}")]
+    Neg(Type),
+    #[error("cannot compare {left} {op} {right}")]
+    Compare { left: Type, op: CompOp, right: Type },
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("function '{name}' takes {expected} argument(s), found {found}")]
+    Arity { name: String, expected: usize, found: usize },
+    #[error("argument {index} of '{name}' must be {expected}, found {found}")]
+    Argument { name: String, index: usize, expected: Type, found: Type },
+    #[error("condition must be bool, found {0}")]
+    NotBool(Type),
+    #[error("quantifier needs a list, found {0}")]
+    NotList(Type),
+}
+
+#[derive(Debug, Error)]
+#[error("rule '{rule}', statement {statement}: {error}")]
+pub struct CheckError {
+    pub rule: String,
+    pub statement: usize,
+    #[source]
+    pub error: TypeError,
+}
+
+/// Result type of `left op right`, shared with the evaluator so both agree.
+pub fn arith_type(left: Type, op: ArithOp, right: Type) -> Option<Type> {
+    use ArithOp::*;
+    use Type::*;
+    match (left, op, right) {
+        (Any, _, _) | (_, _, Any) => Some(Any),
+        (Number, _, Number) => Some(Number),
+        (Duration, Add | Sub | Rem, Duration) => Some(Duration),
+        (Duration, Div, Duration) => Some(Number),
+        (Duration, Mul | Div, Number) | (Number, Mul, Duration) => Some(Duration),
+        (Timestamp, Add | Sub, Duration) | (Duration, Add, Timestamp) => Some(Timestamp),
+        (Timestamp, Sub, Timestamp) => Some(Duration),
+        _ => None,
+    }
+}
+
+pub fn type_of(operand: &Operand, fns: &Functions) -> Result<Type, TypeError> {
+    match operand {
+        Operand::Number(_) => Ok(Type::Number),
+        Operand::Str(_) => Ok(Type::String),
+        Operand::Bool(_) => Ok(Type::Bool),
+        Operand::Duration { unit, .. } => match unit_seconds(unit) {
+            Some(_) => Ok(Type::Duration),
+            None => Err(TypeError::UnknownUnit(unit.clone())),
+        },
+        Operand::Field(_) => Ok(Type::Any),
+        Operand::Call(call) => type_of_call(call, fns),
+        Operand::Now => Ok(Type::Timestamp),
+        Operand::Neg(inner) => match type_of(inner, fns)? {
+            t @ (Type::Number | Type::Duration | Type::Any) => Ok(t),
+            t => Err(TypeError::Neg(t)),
+        },
+        Operand::Arith { left, op, right } => {
+            let (l, r) = (type_of(left, fns)?, type_of(right, fns)?);
+            arith_type(l, *op, r).ok_or(TypeError::Arith { op: *op, left: l, right: r })
+        }
+    }
+}
+
+fn type_of_call(call: &Call, fns: &Functions) -> Result<Type, TypeError> {
+    let args = call.args.iter().map(|a| type_of(a, fns)).collect::<Result<Vec<_>, _>>()?;
+    fns.check_call(&call.name, &args)
+}
+
+pub fn check_expr(expr: &Expr, fns: &Functions) -> Result<(), TypeError> {
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => { check_expr(a, fns)?; check_expr(b, fns) }
+        Expr::Not(e) | Expr::Group(e) => check_expr(e, fns),
+        Expr::Compare { left, op, right } => {
+            let (l, r) = (type_of(left, fns)?, type_of(right, fns)?);
+            let ok = match op {
+                CompOp::StartsWith | CompOp::EndsWith => Type::String.accepts(l) && Type::String.accepts(r),
+                // `contains` also tests list membership, and lists are `Any`
+                CompOp::Contains => l == Type::Any || (l == Type::String && Type::String.accepts(r)),
+                CompOp::Eq | CompOp::Ne => l.accepts(r),
+                _ => l.accepts(r) && l != Type::Bool && r != Type::Bool,
+            };
+            if ok { Ok(()) } else { Err(TypeError::Compare { left: l, op: *op, right: r }) }
+        }
+        Expr::In { .. } | Expr::LooksLike(_) => Ok(()),
+        Expr::Quantified { list, body, .. } => match type_of(list, fns)? {
+            Type::Any => check_expr(body, fns),
+            t => Err(TypeError::NotList(t)),
+        },
+        Expr::Call(call) => match type_of_call(call, fns)? {
+            Type::Bool | Type::Any => Ok(()),
+            t => Err(TypeError::NotBool(t)),
+        },
+    }
+}
+
+/// Check `program` against the built-in functions; see `Evaluator::check`
+/// for a program evaluated with host functions.
+pub fn check_program(program: &Program) -> Result<(), CheckError> {
+    check_program_with(program, &Functions::builtins())
+}
+
+/// Check `program` against a custom function registry, e.g. `Evaluator::functions`.
+pub fn check_program_with(program: &Program, fns: &Functions) -> Result<(), CheckError> {
+    for rule in &program.rules {
+        for (i, st) in rule.statements.iter().enumerate() {
+            check_expr(&st.condition, fns)
+                .map_err(|error| CheckError { rule: rule.name.clone(), statement: i, error })?;
+        }
+    }
+    Ok(())
+}
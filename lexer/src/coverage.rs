@@ -0,0 +1,281 @@
+use std::fmt::{self, Write as _};
+use std::ops::Range;
+
+use crate::ast::*;
+use crate::docs::html_escape;
+use crate::eval::{statement_with, Decision, EvalError, Evaluator, Scope};
+use crate::source::Spans;
+use crate::token::{LexError, Lexer};
+use crate::value::Value;
+
+//
+// ===== COVERAGE =====
+//
+// Which rules and statements fire over a sample dataset, and which sides of
+// each `and` and `or` ever decide the outcome, so dead rules and conditions
+// can be pruned. A side of `a or b` is decisive for a record when it is what
+// makes the `or` true: `a` when `a` holds, `b` when `a` doesn't and `b` does.
+// A side of `a and b` is decisive when it makes the `and` false. A side that
+// is never decisive could be dropped without changing any decision over the
+// dataset.
+//
+// Counts are of records: a statement whose condition is tried once per `*`
+// location, or a side inside a quantifier body, counts once per record
+// however many times it held or decided.
+//
+
+/// The sides of one `and` or `or`.
+#[derive(Debug, Clone)]
+pub struct BranchCoverage {
+    /// `true` for `or`.
+    pub is_or: bool,
+    /// The left and right sides, as conditions.
+    pub sides: [String; 2],
+    /// The number of records for which each side was decisive.
+    pub decisive: [usize; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct StatementCoverage {
+    pub action: Action,
+    /// The number of records for which the statement fired.
+    pub fired: usize,
+    /// One per `and` and `or` of the condition; see `Expr::connectives`.
+    pub branches: Vec<BranchCoverage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleCoverage {
+    pub name: String,
+    pub statements: Vec<StatementCoverage>,
+}
+
+impl RuleCoverage {
+    pub fn fired(&self) -> bool {
+        self.statements.iter().any(|st| st.fired > 0)
+    }
+}
+
+/// How a program fared over the records evaluated with
+/// `Evaluator::evaluate_covered`, rule by rule in program order.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    pub records: usize,
+    pub rules: Vec<RuleCoverage>,
+}
+
+impl Coverage {
+    /// Coverage of `program` over no records yet.
+    pub fn new(program: &Program) -> Self {
+        let rules = program.rules.iter().map(|rule| RuleCoverage {
+            name: rule.name.clone(),
+            statements: rule.statements.iter().map(|st| StatementCoverage {
+                action: st.action,
+                fired: 0,
+                branches: st.condition.connectives().into_iter().map(|node| {
+                    let (Expr::Or(a, b) | Expr::And(a, b)) = node else { unreachable!("connectives are `and` and `or`") };
+                    BranchCoverage {
+                        is_or: matches!(node, Expr::Or(..)),
+                        sides: [a.to_string(), b.to_string()],
+                        decisive: [0, 0],
+                    }
+                }).collect(),
+            }).collect(),
+        }).collect();
+        Coverage { records: 0, rules }
+    }
+
+    /// Statements that fired for some record, and all statements.
+    fn statement_counts(&self) -> (usize, usize) {
+        let statements = self.rules.iter().flat_map(|r| &r.statements);
+        (statements.clone().filter(|st| st.fired > 0).count(), statements.count())
+    }
+
+    /// Sides of `and`s and `or`s that were never decisive, and all sides.
+    fn side_counts(&self) -> (usize, usize) {
+        let sides = self.rules.iter().flat_map(|r| &r.statements).flat_map(|st| &st.branches).flat_map(|b| b.decisive);
+        (sides.clone().filter(|&n| n == 0).count(), sides.count())
+    }
+
+    fn summary(&self) -> String {
+        let (fired, statements) = self.statement_counts();
+        let (dead, sides) = self.side_counts();
+        let records = plural(self.records, "record");
+        format!("{records}: {fired} of {statements} statements fired, {dead} of {sides} sides of `and`/`or` never decisive")
+    }
+
+    /// A standalone HTML page titled `title` showing `source`, the program's
+    /// source, with statements marked by whether they fired and `and`/`or`
+    /// keywords marked where a side was never decisive.
+    pub fn html(&self, title: &str, source: &str) -> Result<String, LexError> {
+        let spans = Spans::new(Lexer::new(source).collect::<Result<Vec<_>, _>>()?);
+        let mut marks: Vec<(Range<usize>, String)> = Vec::new();
+        for rule in &self.rules {
+            if !rule.fired() && let Some(span) = spans.rule(&rule.name) {
+                marks.push((span, r#"<span class="dead-rule" title="no statement fired">"#.to_string()));
+            }
+            for (i, st) in rule.statements.iter().enumerate() {
+                if let Some(span) = spans.statement(&rule.name, i) {
+                    let (class, title) = match st.fired {
+                        0 => ("dead", "never fired".to_string()),
+                        n => ("fired", format!("fired for {} of {}", n, plural(self.records, "record"))),
+                    };
+                    marks.push((span, format!(r#"<span class="{class}" title="{title}">"#)));
+                }
+                for (branch, span) in st.branches.iter().zip(spans.connectives(&rule.name, i)) {
+                    let dead = branch.dead_sides();
+                    if !dead.is_empty() {
+                        let title = html_escape(&dead.join("; "));
+                        marks.push((span, format!(r#"<span class="dead-side" title="{title}">"#)));
+                    }
+                }
+            }
+        }
+        let title = html_escape(title);
+        let mut out = String::new();
+        let _ = writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>");
+        let _ = writeln!(out, "<style>\n.fired {{ background: #dfd; }}\n.dead {{ background: #fdd; }}");
+        let _ = writeln!(out, ".dead-rule {{ opacity: 0.6; }}\n.dead-side {{ text-decoration: red wavy underline; }}\n</style>");
+        let _ = writeln!(out, "</head>\n<body>\n<h1>{title}</h1>\n<p>{}</p>\n<pre>", html_escape(&self.summary()));
+        out.push_str(&annotate(source, &marks));
+        let _ = writeln!(out, "</pre>\n</body>\n</html>");
+        Ok(out)
+    }
+}
+
+impl BranchCoverage {
+    /// A description of each side that was never decisive.
+    fn dead_sides(&self) -> Vec<String> {
+        let connective = if self.is_or { "or" } else { "and" };
+        ["left", "right"].iter().zip(&self.sides).zip(self.decisive)
+            .filter(|(_, n)| *n == 0)
+            .map(|((side, expr), _)| format!("never decisive: `{expr}`, {side} of `{connective}`"))
+            .collect()
+    }
+}
+
+/// `source` as HTML with each of `marks` opening a `<span>` tag over its
+/// span. Rules contain statements, which contain keywords, so marks nest.
+fn annotate(source: &str, marks: &[(Range<usize>, String)]) -> String {
+    let mut marks: Vec<&(Range<usize>, String)> = marks.iter().collect();
+    marks.sort_by_key(|(span, _)| (span.start, std::cmp::Reverse(span.end)));
+    let mut out = String::new();
+    let (mut pos, mut open): (usize, Vec<usize>) = (0, Vec::new());
+    for (span, tag) in marks.into_iter().map(|(span, tag)| (span.clone(), Some(tag))).chain([(source.len()..source.len(), None)]) {
+        while let Some(&end) = open.last() && end <= span.start {
+            out.push_str(&html_escape(&source[pos..end]));
+            out.push_str("</span>");
+            pos = end;
+            open.pop();
+        }
+        out.push_str(&html_escape(&source[pos..span.start]));
+        pos = span.start;
+        if let Some(tag) = tag {
+            out.push_str(tag);
+            open.push(span.end);
+        }
+    }
+    out
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 { format!("1 {noun}") } else { format!("{n} {noun}s") }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        for rule in &self.rules {
+            writeln!(f)?;
+            write!(f, "rule {}", rule.name)?;
+            writeln!(f, "{}", if rule.fired() { "" } else { ": never fired" })?;
+            for (i, st) in rule.statements.iter().enumerate() {
+                match st.fired {
+                    0 => writeln!(f, "  [{i}] {}: never fired", st.action)?,
+                    n => writeln!(f, "  [{i}] {}: fired for {}", st.action, plural(n, "record"))?,
+                }
+                for line in st.branches.iter().flat_map(BranchCoverage::dead_sides) {
+                    writeln!(f, "    {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which sides of a condition's `and`s and `or`s were decisive, indexed as
+/// `Expr::connectives`.
+type Decisive = Vec<[bool; 2]>;
+
+impl Evaluator {
+    /// `evaluate`, also counting in `coverage` the statements that fired and
+    /// the sides of `and`s and `or`s that decided. `coverage` must have been
+    /// made for `program`. On error, `coverage` is left as it was.
+    pub fn evaluate_covered(&self, program: &Program, record: &Value, coverage: &mut Coverage) -> Result<Vec<Decision>, EvalError> {
+        let mut decisions = Vec::new();
+        // whether each statement fired, and its decisive sides
+        let mut outcomes: Vec<(bool, Decisive)> = Vec::new();
+        for rule in &program.rules {
+            let in_force = rule.schedule.allows(self.now());
+            for (i, st) in rule.statements.iter().enumerate() {
+                if !in_force {
+                    outcomes.push((false, Vec::new()));
+                    continue;
+                }
+                let mut decisive = vec![[false; 2]; st.condition.connectives().len()];
+                let fired = statement_with(st, record, |cond| self.cover(cond, record, None, 0, &mut decisive))?;
+                outcomes.push((fired.is_some(), decisive));
+                if let Some(targets) = fired {
+                    decisions.push(Decision { rule: rule.name.clone(), statement: i, action: st.action, targets });
+                }
+            }
+        }
+
+        coverage.records += 1;
+        let statements = coverage.rules.iter_mut().flat_map(|r| &mut r.statements);
+        for (st, (fired, decisive)) in statements.zip(outcomes) {
+            st.fired += fired as usize;
+            for (branch, sides) in st.branches.iter_mut().zip(decisive) {
+                for (count, was) in branch.decisive.iter_mut().zip(sides) {
+                    *count += was as usize;
+                }
+            }
+        }
+        Ok(decisions)
+    }
+
+    /// `expr_in`, marking in `decisive` the sides that decided. `base` is the
+    /// index of the first `and`/`or` of `expr` among the condition's.
+    fn cover(&self, expr: &Expr, record: &Value, scope: Option<&Scope>, base: usize, decisive: &mut Decisive) -> Result<bool, EvalError> {
+        match expr {
+            Expr::Or(a, b) | Expr::And(a, b) => {
+                let is_or = matches!(expr, Expr::Or(..));
+                let at = base + a.connectives().len();
+                if self.cover(a, record, scope, base, decisive)? == is_or {
+                    decisive[at][0] = true;
+                    return Ok(is_or);
+                }
+                let right = self.cover(b, record, scope, at + 1, decisive)?;
+                if right == is_or {
+                    decisive[at][1] = true;
+                }
+                Ok(right)
+            }
+            Expr::Not(e) => Ok(!self.cover(e, record, scope, base, decisive)?),
+            Expr::Group(e) => self.cover(e, record, scope, base, decisive),
+            Expr::Quantified { quantifier, var, list, body } => {
+                for item in &self.list_in(list, record, scope)? {
+                    let inner = Scope { var, value: item, parent: scope };
+                    let held = self.cover(body, record, Some(&inner), base, decisive)?;
+                    match quantifier {
+                        Quantifier::Any if held => return Ok(true),
+                        Quantifier::All if !held => return Ok(false),
+                        _ => {}
+                    }
+                }
+                Ok(*quantifier == Quantifier::All)
+            }
+            Expr::Compare { .. } | Expr::In { .. } | Expr::Call(_) | Expr::LooksLike(_) => self.expr_in(expr, record, scope),
+        }
+    }
+}
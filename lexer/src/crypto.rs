@@ -0,0 +1,343 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use aes::Aes256;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use fpe::ff1::{FlexibleNumeralString, FF1};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::ast::{Action, Field, Program, Segment};
+use crate::eval::Decision;
+use crate::value::Value;
+
+//
+// ===== ENCRYPTION =====
+//
+// What the `encrypt` action does to a record: each matched field is replaced
+// by an authenticated ciphertext, or by a format-preserving token, that
+// `Cipher::decrypt_record` (and `policy decrypt`) turn back into the value.
+//
+// Keyring file: one key per line, `<id> <64 hex digits>` for a 256-bit key,
+// e.g. from `openssl rand -hex 32`. Ids are ASCII letters, digits, `-` and
+// `_`; blank lines and lines starting with `#` are ignored. The last key is
+// the current one. Rotate by appending a key and keep the old ones listed for
+// decrypting. A line `tokenize <path> <key id>` binds the tokenized fields
+// matching the path, which may use `*` and `**`, to a key; the first binding
+// that matches is used. On Unix the file must not be readable by group or
+// others.
+//
+// Each key is expanded into two subkeys, HMAC-SHA256(key, "policy-encrypt-v1")
+// for AES-256-GCM and HMAC-SHA256(key, "policy-tokenize-v1") for FF1, so no
+// key is used by both ciphers.
+//
+// Envelope: an encrypted value becomes the string
+//
+//     enc:v1:<key id>:<nonce>:<ciphertext>
+//
+// with a random 12-byte nonce and the ciphertext followed by its 16-byte tag,
+// both in unpadded base64url. The plaintext is the value's JSON, so strings,
+// numbers, lists and maps come back as they were, while a timestamp comes
+// back as its date string and a duration as its seconds, as in any JSON
+// record. The associated data is the field's path, so a ciphertext copied
+// into another field fails to decrypt.
+//
+// Tokens: tokenization encrypts the ASCII digits of a string with FF1
+// (NIST SP 800-38G, radix 10) and leaves every other character in place, so
+// `4111-1111-1111-1111` stays sixteen digits with dashes. The tweak is the
+// field's path. A token is deterministic and carries no key id: the same
+// value in the same field always gives the same token, and only the key that
+// made it can reverse it. So tokens are made and reversed with the key their
+// field is bound to, whatever the current key, and a tokenized field without
+// a binding is an error; rotating keys leaves bindings, and so tokens, as they
+// are. FF1 needs at least six digits.
+//
+
+const ENVELOPE: &str = "enc:v1:";
+const MIN_TOKEN_DIGITS: usize = 6;
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("{path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("keyring line {line}: {message}")]
+    Keyring { line: usize, message: String },
+    #[error("{0}: keyring must not be readable by group or others")]
+    Permissions(PathBuf),
+    #[error("keyring has no keys")]
+    Empty,
+    #[error("unknown key '{0}'")]
+    UnknownKey(String),
+    #[error("{field}: malformed ciphertext envelope")]
+    Malformed { field: String },
+    #[error("{field}: decryption failed: wrong key, wrong field or tampered value")]
+    Tampered { field: String },
+    #[error("{field}: only strings can be tokenized, found {found}")]
+    NotAString { field: String, found: &'static str },
+    #[error("{field}: tokenization needs at least {MIN_TOKEN_DIGITS} digits")]
+    TooFewDigits { field: String },
+    #[error("{field}: no `tokenize` line in the keyring binds this field to a key")]
+    Unbound { field: String },
+}
+
+struct Key {
+    id: String,
+    aead: Aes256Gcm,
+    ff1: FF1<Aes256>,
+}
+
+fn subkey(key: &[u8], label: &str) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(label.as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+impl Key {
+    fn new(id: &str, key: &[u8; 32]) -> Self {
+        Key {
+            id: id.to_string(),
+            aead: Aes256Gcm::new(&subkey(key, "policy-encrypt-v1").into()),
+            ff1: FF1::new(&subkey(key, "policy-tokenize-v1"), 10).expect("10 is a valid radix"),
+        }
+    }
+}
+
+/// The keys named in a keyring file; see the module comment for the format.
+pub struct Keyring {
+    keys: Vec<Key>,
+    /// `tokenize` lines: the paths they match and the index of their key.
+    tokens: Vec<(Field, usize)>,
+}
+
+fn parse_hex(s: &str) -> Option<[u8; 32]> {
+    if s.len() != 64 || !s.is_ascii() { return None; }
+    let mut out = [0; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+impl Keyring {
+    pub fn parse(text: &str) -> Result<Self, CryptoError> {
+        let mut keys: Vec<Key> = Vec::new();
+        let mut bindings = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let err = |message: &str| CryptoError::Keyring { line: i + 1, message: message.to_string() };
+            if let ["tokenize", path, id] = line.split_whitespace().collect::<Vec<_>>()[..] {
+                bindings.push((i + 1, Field::from_dotted(path), id));
+                continue;
+            }
+            let (id, hex) = line.split_once(char::is_whitespace).ok_or_else(|| err("expected `<id> <key>`"))?;
+            if !id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+                return Err(err("key ids may only contain ASCII letters, digits, `-` and `_`"));
+            }
+            if keys.iter().any(|k| k.id == id) { return Err(err("duplicate key id")); }
+            let key = parse_hex(hex.trim()).ok_or_else(|| err("keys are 64 hex digits"))?;
+            keys.push(Key::new(id, &key));
+        }
+        if keys.is_empty() { return Err(CryptoError::Empty); }
+        let tokens = bindings.into_iter().map(|(line, path, id)| match keys.iter().position(|k| k.id == id) {
+            Some(key) => Ok((path, key)),
+            None => Err(CryptoError::Keyring { line, message: format!("unknown key '{id}'") }),
+        }).collect::<Result<_, _>>()?;
+        Ok(Keyring { keys, tokens })
+    }
+
+    /// Read a keyring file, refusing one that others can read.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CryptoError> {
+        let path = path.as_ref();
+        let io = |source| CryptoError::Io { path: path.to_path_buf(), source };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if fs::metadata(path).map_err(io)?.permissions().mode() & 0o077 != 0 {
+                return Err(CryptoError::Permissions(path.to_path_buf()));
+            }
+        }
+        Self::parse(&fs::read_to_string(path).map_err(io)?)
+    }
+
+    /// The id of the key new values are encrypted under.
+    pub fn current(&self) -> &str {
+        &self.keys[self.keys.len() - 1].id
+    }
+
+    fn key(&self, id: &str) -> Result<&Key, CryptoError> {
+        self.keys.iter().find(|k| k.id == id).ok_or_else(|| CryptoError::UnknownKey(id.to_string()))
+    }
+
+    /// The id of the key tokens at `field` are made with, if it is bound.
+    pub fn token_key(&self, field: &Field) -> Option<&str> {
+        self.token_binding(field).map(|key| key.id.as_str())
+    }
+
+    fn token_binding(&self, field: &Field) -> Option<&Key> {
+        self.tokens.iter().find(|(p, _)| matches(&p.segments, &field.segments)).map(|&(_, key)| &self.keys[key])
+    }
+}
+
+/// Whether the concrete path `path` matches `pattern`, which may use `*` and `**`.
+fn matches(pattern: &[Segment], path: &[Segment]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (None, None) => true,
+        (Some((Segment::Glob, rest)), _) => matches(rest, path) || (!path.is_empty() && matches(pattern, &path[1..])),
+        (Some((Segment::Wildcard, rest)), Some((_, tail))) => matches(rest, tail),
+        (Some((p, rest)), Some((s, tail))) => p == s && matches(rest, tail),
+        _ => false,
+    }
+}
+
+/// Encrypts and decrypts field values with a keyring.
+pub struct Cipher {
+    keyring: Keyring,
+    key: usize,
+    tokenize: Vec<Field>,
+}
+
+impl Cipher {
+    /// Encrypt with the keyring's current key.
+    pub fn new(keyring: Keyring) -> Self {
+        let key = keyring.keys.len() - 1;
+        Cipher { keyring, key, tokenize: Vec::new() }
+    }
+
+    /// Encrypt with the key `id` instead of the current one. Tokens always
+    /// use the key their field is bound to.
+    pub fn with_key(mut self, id: &str) -> Result<Self, CryptoError> {
+        self.key = self.keyring.keys.iter().position(|k| k.id == id).ok_or_else(|| CryptoError::UnknownKey(id.to_string()))?;
+        Ok(self)
+    }
+
+    /// Tokenize fields matching `pattern` rather than encrypting them.
+    pub fn tokenize(mut self, pattern: Field) -> Self {
+        self.tokenize.push(pattern);
+        self
+    }
+
+    fn tokenized(&self, field: &Field) -> bool {
+        self.tokenize.iter().any(|p| matches(&p.segments, &field.segments))
+    }
+
+    fn token_key(&self, field: &Field) -> Result<&Key, CryptoError> {
+        self.keyring.token_binding(field).ok_or_else(|| CryptoError::Unbound { field: field.to_string() })
+    }
+
+    /// The ciphertext or token stored in place of `value` at `field`.
+    pub fn encrypt(&self, field: &Field, value: &Value) -> Result<Value, CryptoError> {
+        let key = &self.keyring.keys[self.key];
+        let path = field.to_string();
+        if self.tokenized(field) {
+            let Value::Str(s) = value else {
+                return Err(CryptoError::NotAString { field: path, found: value.type_name() });
+            };
+            return Ok(Value::Str(tokenize(self.token_key(field)?, &path, s, true)?));
+        }
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let plaintext = value.to_json().to_string();
+        let ciphertext = key.aead.encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: path.as_bytes() })
+            .expect("AES-GCM encryption of an in-memory buffer");
+        Ok(Value::Str(format!("{ENVELOPE}{}:{}:{}", key.id, URL_SAFE_NO_PAD.encode(nonce), URL_SAFE_NO_PAD.encode(ciphertext))))
+    }
+
+    /// The value behind a ciphertext or token at `field`. Other values are
+    /// returned unchanged.
+    pub fn decrypt(&self, field: &Field, value: &Value) -> Result<Value, CryptoError> {
+        let path = field.to_string();
+        match value {
+            Value::Str(s) if s.starts_with(ENVELOPE) => self.open(&path, &s[ENVELOPE.len()..]),
+            Value::Str(s) if self.tokenized(field) => Ok(Value::Str(tokenize(self.token_key(field)?, &path, s, false)?)),
+            _ => Ok(value.clone()),
+        }
+    }
+
+    fn open(&self, path: &str, envelope: &str) -> Result<Value, CryptoError> {
+        let malformed = || CryptoError::Malformed { field: path.to_string() };
+        let mut parts = envelope.split(':');
+        let (Some(id), Some(nonce), Some(ciphertext), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(malformed());
+        };
+        let key = self.keyring.key(id)?;
+        let nonce = URL_SAFE_NO_PAD.decode(nonce).ok().filter(|n| n.len() == 12).ok_or_else(malformed)?;
+        let ciphertext = URL_SAFE_NO_PAD.decode(ciphertext).map_err(|_| malformed())?;
+        let plaintext = key.aead.decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: path.as_bytes() })
+            .map_err(|_| CryptoError::Tampered { field: path.to_string() })?;
+        // authenticated, so it is the JSON we wrote
+        let json = serde_json::from_slice(&plaintext).map_err(|_| malformed())?;
+        Value::from_json(&json).map_err(|_| malformed())
+    }
+
+    /// Encrypt the fields matched by the `encrypt` decisions among
+    /// `decisions`, made by evaluating `record` against `program`; see
+    /// `Decision::targeted`. Missing fields are skipped. Returns the fields
+    /// encrypted.
+    pub fn apply(&self, program: &Program, decisions: &[Decision], record: &mut Value) -> Result<Vec<Field>, CryptoError> {
+        let mut done: Vec<Field> = Vec::new();
+        for decision in decisions.iter().filter(|d| d.action == Action::Encrypt) {
+            for field in decision.targeted(program) {
+                if done.contains(field) { continue; }
+                let Some(slot) = record.get_mut(field) else { continue };
+                if *slot == Value::Null { continue; }
+                *slot = self.encrypt(field, slot)?;
+                done.push(field.clone());
+            }
+        }
+        Ok(done)
+    }
+
+    /// Decrypt every ciphertext in `record`, and every token in a tokenized
+    /// field. Tokens carry no marker, so every string in a tokenized field is
+    /// taken for one. Returns the number of values decrypted.
+    pub fn decrypt_record(&self, record: &mut Value) -> Result<usize, CryptoError> {
+        let mut count = 0;
+        self.decrypt_in(record, &mut Vec::new(), &mut count)?;
+        Ok(count)
+    }
+
+    fn decrypt_in(&self, value: &mut Value, path: &mut Vec<Segment>, count: &mut usize) -> Result<(), CryptoError> {
+        match value {
+            Value::Map(m) => for (k, v) in m.iter_mut() {
+                path.push(Segment::Key(k.clone()));
+                self.decrypt_in(v, path, count)?;
+                path.pop();
+            },
+            Value::List(items) => for (i, v) in items.iter_mut().enumerate() {
+                path.push(Segment::Index(i));
+                self.decrypt_in(v, path, count)?;
+                path.pop();
+            },
+            Value::Str(s) if s.starts_with(ENVELOPE) || self.tokenize.iter().any(|p| matches(&p.segments, path)) => {
+                let field = Field { segments: path.clone() };
+                *value = self.decrypt(&field, value)?;
+                *count += 1;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Encrypt (or decrypt) the ASCII digits of `s` with FF1, keeping every
+/// other character where it is.
+fn tokenize(key: &Key, path: &str, s: &str, encrypt: bool) -> Result<String, CryptoError> {
+    let digits: Vec<u16> = s.bytes().filter(u8::is_ascii_digit).map(|b| u16::from(b - b'0')).collect();
+    if digits.len() < MIN_TOKEN_DIGITS {
+        return Err(CryptoError::TooFewDigits { field: path.to_string() });
+    }
+    let input = FlexibleNumeralString::from(digits);
+    let output = if encrypt { key.ff1.encrypt(path.as_bytes(), &input) } else { key.ff1.decrypt(path.as_bytes(), &input) };
+    let output: Vec<u16> = output.expect("digits are radix 10 and long enough").into();
+    let mut digits = output.into_iter();
+    Ok(s.chars().map(|c| if c.is_ascii_digit() {
+        char::from(b'0' + digits.next().expect("one output digit per input digit") as u8)
+    } else {
+        c
+    }).collect())
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::analyze::{implies, normalize};
+use crate::ast::*;
+use crate::eval::{Decision, EvalError, Evaluator};
+use crate::value::Value;
+
+//
+// ===== POLICY DIFF =====
+//
+
+/// A semantic difference between two versions of a program. Conditions are
+/// compared after `normalize`, so regrouping or De Morgan rewrites are not changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    RuleAdded(String),
+    RuleRemoved(String),
+    /// The same statements under a new name.
+    RuleRenamed { from: String, to: String },
+    /// `rule` is the rule's name in the new program.
+    ScheduleChanged { rule: String, old: Schedule, new: Schedule },
+    StatementAdded { rule: String, index: usize, statement: Statement },
+    StatementRemoved { rule: String, index: usize, statement: Statement },
+    /// `index` is the statement's position in the new program.
+    ConditionChanged { rule: String, index: usize, old: Expr, new: Expr, effect: Effect },
+    ActionChanged { rule: String, index: usize, old: Action, new: Action },
+    /// Both versions mask, with different strategies; `None` is the masker's default.
+    MaskChanged { rule: String, index: usize, old: Option<MaskStrategy>, new: Option<MaskStrategy> },
+}
+
+/// How a changed condition relates to the old one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Every record matching the new condition matched the old one.
+    Narrowed,
+    /// Every record matching the old condition matches the new one.
+    Broadened,
+    Changed,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::RuleAdded(name) => write!(f, "+ rule {name}"),
+            Change::RuleRemoved(name) => write!(f, "- rule {name}"),
+            Change::RuleRenamed { from, to } => write!(f, "~ rule {from} renamed to {to}"),
+            Change::ScheduleChanged { rule, old, new } => {
+                let show = |s: &Schedule| if s.is_always() { "always".to_string() } else { format!("`{s}`") };
+                write!(f, "~ rule {rule}: schedule {} -> {}", show(old), show(new))
+            }
+            Change::StatementAdded { rule, index, statement } => write!(f, "+ {rule}[{index}]: {statement}"),
+            Change::StatementRemoved { rule, index, statement } => write!(f, "- {rule}[{index}]: {statement}"),
+            Change::ConditionChanged { rule, index, old, new, effect } => {
+                let how = match effect { Effect::Narrowed => "narrowed", Effect::Broadened => "broadened", Effect::Changed => "changed" };
+                write!(f, "~ {rule}[{index}]: condition {how}: `{old}` -> `{new}`")
+            }
+            Change::ActionChanged { rule, index, old, new } => write!(f, "~ {rule}[{index}]: action {old} -> {new}"),
+            Change::MaskChanged { rule, index, old, new } => {
+                let name = |s: &Option<MaskStrategy>| s.map_or("default".to_string(), |s| s.to_string());
+                write!(f, "~ {rule}[{index}]: mask strategy {} -> {}", name(old), name(new))
+            }
+        }
+    }
+}
+
+fn same_condition(a: &Expr, b: &Expr) -> bool {
+    normalize(a) == normalize(b)
+}
+
+fn same_statement(a: &Statement, b: &Statement) -> bool {
+    a.action == b.action && a.mask == b.mask && same_condition(&a.condition, &b.condition)
+}
+
+fn same_statements(a: &[Statement], b: &[Statement]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| same_statement(x, y))
+}
+
+/// Rules are matched by name, or as a rename when an unmatched new rule has the
+/// same statements as a removed one. Statements are aligned by longest common
+/// subsequence; unaligned statements in the same gap are paired up as edits.
+pub fn diff_programs(old: &Program, new: &Program) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut matched = vec![false; new.rules.len()];
+    for rule in &old.rules {
+        if let Some(j) = new.rules.iter().position(|r| r.name == rule.name) {
+            matched[j] = true;
+            diff_schedules(rule, &new.rules[j], &mut changes);
+            diff_statements(&rule.name, &rule.statements, &new.rules[j].statements, &mut changes);
+        } else if let Some(j) = (0..new.rules.len()).find(|&j| {
+            !matched[j] && !old.rules.iter().any(|r| r.name == new.rules[j].name)
+                && same_statements(&rule.statements, &new.rules[j].statements)
+        }) {
+            matched[j] = true;
+            changes.push(Change::RuleRenamed { from: rule.name.clone(), to: new.rules[j].name.clone() });
+            diff_schedules(rule, &new.rules[j], &mut changes);
+        } else {
+            changes.push(Change::RuleRemoved(rule.name.clone()));
+        }
+    }
+    for (rule, _) in new.rules.iter().zip(&matched).filter(|(_, m)| !**m) {
+        changes.push(Change::RuleAdded(rule.name.clone()));
+    }
+    changes
+}
+
+fn diff_schedules(old: &Rule, new: &Rule, changes: &mut Vec<Change>) {
+    if old.schedule != new.schedule {
+        changes.push(Change::ScheduleChanged { rule: new.name.clone(), old: old.schedule.clone(), new: new.schedule.clone() });
+    }
+}
+
+fn diff_statements(rule: &str, old: &[Statement], new: &[Statement], changes: &mut Vec<Change>) {
+    // lcs[i][j]: length of the common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if same_statement(&old[i], &new[j]) { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let (mut gap_old, mut gap_new) = (Vec::new(), Vec::new());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same_statement(&old[i], &new[j]) {
+            flush_gap(rule, old, new, &mut gap_old, &mut gap_new, changes);
+            i += 1; j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            gap_new.push(j); j += 1;
+        } else {
+            gap_old.push(i); i += 1;
+        }
+    }
+    flush_gap(rule, old, new, &mut gap_old, &mut gap_new, changes);
+}
+
+fn flush_gap(rule: &str, old: &[Statement], new: &[Statement], gap_old: &mut Vec<usize>, gap_new: &mut Vec<usize>, changes: &mut Vec<Change>) {
+    for k in 0..gap_old.len().max(gap_new.len()) {
+        let rule = rule.to_string();
+        match (gap_old.get(k), gap_new.get(k)) {
+            (Some(&i), Some(&j)) => {
+                let (o, n) = (&old[i], &new[j]);
+                if !same_condition(&o.condition, &n.condition) {
+                    let (no, nn) = (normalize(&o.condition), normalize(&n.condition));
+                    let effect = if implies(&nn, &no) { Effect::Narrowed }
+                        else if implies(&no, &nn) { Effect::Broadened }
+                        else { Effect::Changed };
+                    changes.push(Change::ConditionChanged { rule: rule.clone(), index: j, old: o.condition.clone(), new: n.condition.clone(), effect });
+                }
+                if o.action != n.action {
+                    changes.push(Change::ActionChanged { rule, index: j, old: o.action, new: n.action });
+                } else if o.mask != n.mask {
+                    changes.push(Change::MaskChanged { rule, index: j, old: o.mask, new: n.mask });
+                }
+            }
+            (Some(&i), None) => changes.push(Change::StatementRemoved { rule, index: i, statement: old[i].clone() }),
+            (None, Some(&j)) => changes.push(Change::StatementAdded { rule, index: j, statement: new[j].clone() }),
+            (None, None) => unreachable!(),
+        }
+    }
+    gap_old.clear();
+    gap_new.clear();
+}
+
+//
+// Record-level diff
+//
+
+/// A sample record whose treatment differs between two program versions.
+#[derive(Debug, Clone)]
+pub struct RecordChange {
+    /// Position in the sample set.
+    pub index: usize,
+    /// The record's `id` or `record.id`, if it has one.
+    pub id: Option<String>,
+    pub before: Vec<Decision>,
+    pub after: Vec<Decision>,
+}
+
+/// What happens to a record, ignoring which rule made it happen.
+fn treatment(decisions: &[Decision]) -> BTreeSet<(String, Vec<String>)> {
+    decisions.iter()
+        .map(|d| (d.action.to_string(), d.targets.iter().map(|t| t.to_string()).collect()))
+        .collect()
+}
+
+fn record_id(record: &Value) -> Option<String> {
+    let field = |path: &[&str]| Field { segments: path.iter().map(|s| Segment::Key(s.to_string())).collect() };
+    [field(&["id"]), field(&["record", "id"])].iter()
+        .map(|f| record.get(f))
+        .find(|v| **v != Value::Null)
+        .map(|v| match v { Value::Str(s) => s.clone(), v => v.to_string() })
+}
+
+/// Evaluate both programs over `records` and report those treated differently.
+pub fn diff_records(old: &Program, new: &Program, records: &[Value], evaluator: &Evaluator) -> Result<Vec<RecordChange>, EvalError> {
+    let mut out = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let before = evaluator.evaluate(old, record)?;
+        let after = evaluator.evaluate(new, record)?;
+        if treatment(&before) != treatment(&after) {
+            out.push(RecordChange { index, id: record_id(record), before, after });
+        }
+    }
+    Ok(out)
+}
+
+impl fmt::Display for RecordChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |ds: &[Decision]| if ds.is_empty() { "nothing".to_string() } else {
+            ds.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        write!(f, "record {}", self.index + 1)?;
+        if let Some(id) = &self.id { write!(f, " (id {id})")?; }
+        write!(f, ": {} -> {}", list(&self.before), list(&self.after))
+    }
+}
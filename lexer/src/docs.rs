@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::ast::*;
+use crate::time::{format_timestamp, SECS_PER_DAY};
+
+//
+// ===== DOCUMENTATION =====
+//
+// Markdown and HTML pages for reviewers who don't read the DSL: each rule's
+// doc comment, its statements in plain English, the fields it reads, and an
+// index of which rules read each field.
+//
+
+/// A condition in plain English, e.g. `record.age_in_days > 30 days` becomes
+/// "record age in days is greater than 30 days".
+pub fn describe(expr: &Expr) -> String {
+    describe_expr(expr, false)
+}
+
+fn describe_expr(expr: &Expr, negated: bool) -> String {
+    match expr {
+        Expr::Not(e) => describe_expr(e, !negated),
+        Expr::Compare { left, op, right } => {
+            let phrase = match (op, negated) {
+                (CompOp::Eq, false) | (CompOp::Ne, true) => "is",
+                (CompOp::Ne, false) | (CompOp::Eq, true) => "is not",
+                (CompOp::Gt, false) => "is greater than",
+                (CompOp::Gt, true) => "is not greater than",
+                (CompOp::Lt, false) => "is less than",
+                (CompOp::Lt, true) => "is not less than",
+                (CompOp::Ge, false) => "is at least",
+                (CompOp::Ge, true) => "is less than",
+                (CompOp::Le, false) => "is at most",
+                (CompOp::Le, true) => "is greater than",
+                (CompOp::StartsWith, false) => "starts with",
+                (CompOp::StartsWith, true) => "does not start with",
+                (CompOp::EndsWith, false) => "ends with",
+                (CompOp::EndsWith, true) => "does not end with",
+                (CompOp::Contains, false) => "contains",
+                (CompOp::Contains, true) => "does not contain",
+            };
+            format!("{} {phrase} {}", describe_operand(left), describe_operand(right))
+        }
+        Expr::In { field, set } => {
+            let items: Vec<String> = set.iter().map(|s| format!("\"{s}\"")).collect();
+            format!("{} is {}one of {}", describe_field(field), if negated { "not " } else { "" }, items.join(", "))
+        }
+        // `is_email(x)` reads as "x is email"
+        Expr::Call(call) if call.args.len() == 1 && call.name.starts_with("is_") => {
+            let not = if negated { "not " } else { "" };
+            format!("{} is {not}{}", describe_operand(&call.args[0]), humanize(&call.name["is_".len()..]))
+        }
+        Expr::Call(call) => {
+            format!("{} {}", describe_call(call), if negated { "does not hold" } else { "holds" })
+        }
+        Expr::LooksLike(detector) => {
+            format!("the scanned value {} {}", if negated { "does not look like" } else { "looks like" }, humanize(detector.name()))
+        }
+        _ if negated => format!("it is not the case that {}", describe_expr(expr, false)),
+        Expr::Or(a, b) => format!("{} or {}", describe_expr(a, false), describe_expr(b, false)),
+        Expr::And(a, b) => format!("{} and {}", describe_expr(a, false), describe_expr(b, false)),
+        Expr::Group(e) => format!("({})", describe_expr(e, false)),
+        Expr::Quantified { quantifier, var, list, body } => {
+            let which = match quantifier { Quantifier::Any => "some", Quantifier::All => "every" };
+            format!("for {which} {var} in {}, {}", describe_operand(list), describe_expr(body, false))
+        }
+    }
+}
+
+fn describe_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Number(n) => n.to_string(),
+        Operand::Str(s) => format!("\"{s}\""),
+        Operand::Bool(b) => b.to_string(),
+        Operand::Duration { value, unit } => format!("{value} {unit}"),
+        Operand::Field(field) => describe_field(field),
+        Operand::Call(call) => describe_call(call),
+        Operand::Now => "the current time".to_string(),
+        Operand::Neg(inner) => format!("minus {}", nested(inner)),
+        Operand::Arith { left, op, right } => {
+            let word = match op {
+                ArithOp::Add => "plus", ArithOp::Sub => "minus", ArithOp::Mul => "times",
+                ArithOp::Div => "divided by", ArithOp::Rem => "modulo",
+            };
+            format!("{} {word} {}", nested(left), nested(right))
+        }
+    }
+}
+
+/// An operand inside arithmetic, parenthesized unless it is a single term.
+fn nested(operand: &Operand) -> String {
+    match operand {
+        Operand::Arith { .. } => format!("({})", describe_operand(operand)),
+        _ => describe_operand(operand),
+    }
+}
+
+fn describe_call(call: &Call) -> String {
+    let name = match call.name.as_str() { "len" => "length".to_string(), other => humanize(other) };
+    let args: Vec<String> = call.args.iter().map(describe_operand).collect();
+    format!("the {name} of {}", args.join(" and "))
+}
+
+/// `record.age_in_days` reads as "record age in days".
+fn describe_field(field: &Field) -> String {
+    let words: Vec<String> = field.segments.iter().map(|s| match s {
+        Segment::Key(k) => humanize(k),
+        Segment::Index(i) => format!("item {i}"),
+        Segment::Wildcard => "(any key)".to_string(),
+        Segment::Glob => "(at any depth)".to_string(),
+    }).collect();
+    words.join(" ")
+}
+
+fn humanize(name: &str) -> String {
+    name.replace('_', " ")
+}
+
+/// The fields a rule reads, as written and in order of first use. Quantifier
+/// variables are not fields.
+pub fn fields(rule: &Rule) -> Vec<String> {
+    let mut out = Vec::new();
+    for field in rule.statements.iter().flat_map(|st| st.condition.fields()) {
+        let name = field.to_string();
+        if !out.contains(&name) { out.push(name); }
+    }
+    out
+}
+
+/// For each field, the rules that read it, in program order.
+pub fn field_index(program: &Program) -> BTreeMap<String, Vec<String>> {
+    let mut index: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for rule in &program.rules {
+        for field in fields(rule) {
+            index.entry(field).or_default().push(rule.name.clone());
+        }
+    }
+    index
+}
+
+fn action_verb(action: Action) -> &'static str {
+    match action { Action::Delete => "Delete", Action::Mask => "Mask", Action::Notify => "Notify", Action::Encrypt => "Encrypt" }
+}
+
+/// How a statement's mask strategy reads after the verb, e.g. " (keeping the last 4 characters)".
+fn strategy_note(st: &Statement) -> String {
+    let Some(strategy) = st.mask else { return String::new() };
+    let note = match strategy {
+        MaskStrategy::Redact => "fully redacted".to_string(),
+        MaskStrategy::KeepLast(n) => format!("keeping the last {n} characters"),
+        MaskStrategy::Email => "hiding the email's local part".to_string(),
+        MaskStrategy::Hash => "as a salted hash".to_string(),
+        MaskStrategy::Month => "generalized to the month".to_string(),
+        MaskStrategy::Year => "generalized to the year".to_string(),
+    };
+    format!(" ({note})")
+}
+
+/// A rule's metadata as "owner: data-team; tags: gdpr, retention", without
+/// the quotes and brackets of the source; `None` if it has none.
+pub fn describe_metadata(rule: &Rule) -> Option<String> {
+    fn plain(literal: &Literal) -> String {
+        match literal {
+            Literal::Str(s) => s.clone(),
+            Literal::List(items) => items.iter().map(plain).collect::<Vec<_>>().join(", "),
+            literal => literal.to_string(),
+        }
+    }
+    if rule.metadata.is_empty() { return None; }
+    Some(rule.metadata.iter().map(|(k, v)| format!("{}: {}", humanize(k), plain(v))).collect::<Vec<_>>().join("; "))
+}
+
+/// When a scheduled rule is in force, e.g. "In force from 2026-01-01, on
+/// Saturday and Sunday, between 01:00 and 05:00 UTC."; `None` if always.
+pub fn describe_schedule(schedule: &Schedule) -> Option<String> {
+    if schedule.is_always() { return None; }
+    let date = |ts: i64| {
+        let s = format_timestamp(ts);
+        if ts.rem_euclid(SECS_PER_DAY) == 0 { s[..10].to_string() } else { format!("{} {}", &s[..10], &s[11..16]) }
+    };
+    let mut parts = Vec::new();
+    if let Some(from) = schedule.from { parts.push(format!("from {}", date(from))); }
+    if let Some(until) = schedule.until { parts.push(format!("until {}", date(until))); }
+    if !schedule.days.is_empty() {
+        let days: Vec<String> = schedule.days.iter().map(|d| {
+            let (first, rest) = d.name().split_at(1);
+            format!("{}{rest}", first.to_uppercase())
+        }).collect();
+        parts.push(match &days[..] {
+            [one] => format!("on {one}"),
+            [rest @ .., last] => format!("on {} and {last}", rest.join(", ")),
+            [] => unreachable!(),
+        });
+    }
+    if let Some((open, close)) = schedule.hours {
+        parts.push(format!("between {:02}:{:02} and {:02}:{:02}", open / 60, open % 60, close / 60, close % 60));
+    }
+    Some(format!("In force {} UTC.", parts.join(", ")))
+}
+
+/// Backslash-escape characters Markdown would otherwise interpret.
+fn md_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>|#".contains(c) { out.push('\\'); }
+        out.push(c);
+    }
+    out
+}
+
+pub(crate) fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A Markdown page documenting `program` under the heading `title`. Doc
+/// comments are copied as written, so they may use Markdown themselves.
+pub fn markdown(title: &str, program: &Program) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", md_escape(title));
+    let _ = writeln!(out, "## Rules\n");
+    for rule in &program.rules {
+        let _ = writeln!(out, "<a id=\"rule-{}\"></a>\n", html_escape(&rule.name));
+        let _ = writeln!(out, "### {}\n", md_escape(&rule.name));
+        if let Some(doc) = &rule.doc {
+            let _ = writeln!(out, "{doc}\n");
+        }
+        if let Some(metadata) = describe_metadata(rule) {
+            let _ = writeln!(out, "{}\n", md_escape(&metadata));
+        }
+        if let Some(schedule) = describe_schedule(&rule.schedule) {
+            let _ = writeln!(out, "*{schedule}*\n");
+        }
+        if rule.statements.is_empty() {
+            let _ = writeln!(out, "This rule has no statements.\n");
+        }
+        for st in &rule.statements {
+            let _ = writeln!(out, "- **{}**{} when {}", action_verb(st.action), strategy_note(st), md_escape(&describe(&st.condition)));
+        }
+        let fields: Vec<String> = fields(rule).iter().map(|f| format!("`{f}`")).collect();
+        if !fields.is_empty() {
+            let _ = writeln!(out, "\nFields: {}", fields.join(", "));
+        }
+        let _ = writeln!(out);
+    }
+    let index = field_index(program);
+    if !index.is_empty() {
+        let _ = writeln!(out, "## Fields\n");
+        let _ = writeln!(out, "| Field | Rules |");
+        let _ = writeln!(out, "| --- | --- |");
+        for (field, rules) in &index {
+            let links: Vec<String> = rules.iter()
+                .map(|r| format!("[{}](#rule-{})", md_escape(r), html_escape(r)))
+                .collect();
+            let _ = writeln!(out, "| `{field}` | {} |", links.join(", "));
+        }
+    }
+    out
+}
+
+/// A standalone HTML page documenting `program` under the heading `title`.
+/// Doc comments are escaped, with blank lines separating paragraphs.
+pub fn html(title: &str, program: &Program) -> String {
+    let title = html_escape(title);
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>");
+    let _ = writeln!(out, "<h1>{title}</h1>\n<h2>Rules</h2>");
+    for rule in &program.rules {
+        let name = html_escape(&rule.name);
+        let _ = writeln!(out, "<section id=\"rule-{name}\">\n<h3>{name}</h3>");
+        for para in rule.doc.iter().flat_map(|d| d.split("\n\n")) {
+            let _ = writeln!(out, "<p>{}</p>", html_escape(para.trim()));
+        }
+        if let Some(metadata) = describe_metadata(rule) {
+            let _ = writeln!(out, "<p>{}</p>", html_escape(&metadata));
+        }
+        if let Some(schedule) = describe_schedule(&rule.schedule) {
+            let _ = writeln!(out, "<p><em>{schedule}</em></p>");
+        }
+        if rule.statements.is_empty() {
+            let _ = writeln!(out, "<p>This rule has no statements.</p>");
+        } else {
+            let _ = writeln!(out, "<ul>");
+            for st in &rule.statements {
+                let _ = writeln!(out, "<li><strong>{}</strong>{} when {}</li>", action_verb(st.action), strategy_note(st), html_escape(&describe(&st.condition)));
+            }
+            let _ = writeln!(out, "</ul>");
+        }
+        let fields: Vec<String> = fields(rule).iter().map(|f| format!("<code>{}</code>", html_escape(f))).collect();
+        if !fields.is_empty() {
+            let _ = writeln!(out, "<p>Fields: {}</p>", fields.join(", "));
+        }
+        let _ = writeln!(out, "</section>");
+    }
+    let index = field_index(program);
+    if !index.is_empty() {
+        let _ = writeln!(out, "<h2>Fields</h2>\n<table>\n<tr><th>Field</th><th>Rules</th></tr>");
+        for (field, rules) in &index {
+            let links: Vec<String> = rules.iter().map(|r| {
+                let r = html_escape(r);
+                format!("<a href=\"#rule-{r}\">{r}</a>")
+            }).collect();
+            let _ = writeln!(out, "<tr><td><code>{}</code></td><td>{}</td></tr>", html_escape(field), links.join(", "));
+        }
+        let _ = writeln!(out, "</table>");
+    }
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
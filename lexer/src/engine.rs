@@ -0,0 +1,235 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use thiserror::Error;
+
+use crate::ast::Program;
+use crate::check::{check_program_with, CheckError};
+use crate::eval::{Decision, EvalError, Evaluator};
+use crate::functions::Functions;
+use crate::parser::{ParseError, Parser};
+use crate::source::{LineCol, LineIndex};
+use crate::token::{lex, LexError};
+use crate::value::Value;
+
+//
+// ===== POLICY ENGINE =====
+//
+// A compiled policy behind an atomic pointer, for long-running services. The
+// policy is one file, or a directory whose `.policy` files are compiled in
+// name order as one program. Reloading compiles the text afresh and swaps the new program in only if it
+// lexes, parses and type-checks; otherwise the old one stays and the error is
+// kept for inspection. Readers take an `Arc` snapshot, so an evaluation that
+// started before a swap finishes on the version it started with.
+//
+
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error("{path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("{path}:{at}: {error}")]
+    Lex { path: PathBuf, at: LineCol, error: LexError },
+    #[error("{path}: {error}")]
+    Parse { path: PathBuf, error: ParseError },
+    #[error("{path}: {error}")]
+    Check { path: PathBuf, error: CheckError },
+}
+
+/// One compiled version of the policy. `version` starts at 1 and increases
+/// with every successful reload.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub program: Program,
+    pub version: u64,
+}
+
+struct Shared {
+    path: PathBuf,
+    functions: Functions,
+    current: ArcSwap<Snapshot>,
+    /// Hash of the text last compiled, successfully or not; `None` if the
+    /// policy couldn't be read.
+    seen: Mutex<Option<u64>>,
+    last_error: Mutex<Option<Arc<EngineError>>>,
+}
+
+/// A handle to the current policy; clones share it.
+#[derive(Clone)]
+pub struct PolicyEngine {
+    shared: Arc<Shared>,
+}
+
+fn hash(files: &[(PathBuf, String)]) -> u64 {
+    let mut h = DefaultHasher::new();
+    files.hash(&mut h);
+    h.finish()
+}
+
+/// The text of the file at `path`, or of each `.policy` file in the directory
+/// at `path`, in name order.
+fn read(path: &Path) -> Result<Vec<(PathBuf, String)>, EngineError> {
+    let io = |source| EngineError::Io { path: path.to_path_buf(), source };
+    if !path.is_dir() {
+        return Ok(vec![(path.to_path_buf(), fs::read_to_string(path).map_err(io)?)]);
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path).map_err(io)? {
+        let file = entry.map_err(io)?.path();
+        if file.extension().is_some_and(|e| e == "policy") && file.is_file() {
+            paths.push(file);
+        }
+    }
+    paths.sort();
+    paths.into_iter().map(|file| match fs::read_to_string(&file) {
+        Ok(text) => Ok((file, text)),
+        Err(source) => Err(EngineError::Io { path: file, source }),
+    }).collect()
+}
+
+/// Compile each file and concatenate the programs.
+fn compile_all(files: &[(PathBuf, String)], functions: &Functions) -> Result<Program, EngineError> {
+    let mut program = Program { rules: Vec::new(), tests: Vec::new() };
+    for (path, text) in files {
+        let file = compile(path, text, functions)?;
+        program.rules.extend(file.rules);
+        program.tests.extend(file.tests);
+    }
+    Ok(program)
+}
+
+fn compile(path: &Path, text: &str, functions: &Functions) -> Result<Program, EngineError> {
+    let path = path.to_path_buf();
+    let tokens = match lex(text) {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(EngineError::Lex { at: LineIndex::new(text).line_col(error.offset()), path, error }),
+    };
+    let program = Parser::new(tokens).parse_program().map_err(|error| EngineError::Parse { path: path.clone(), error })?;
+    check_program_with(&program, functions).map_err(|error| EngineError::Check { path, error })?;
+    Ok(program)
+}
+
+impl PolicyEngine {
+    /// Compile the policy file or directory at `path`, checking calls against the builtins.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, EngineError> {
+        Self::load_with(path, Functions::builtins())
+    }
+
+    /// Compile the policy file or directory at `path`, checking calls against `functions`.
+    pub fn load_with(path: impl Into<PathBuf>, functions: Functions) -> Result<Self, EngineError> {
+        let path = path.into();
+        let files = read(&path)?;
+        let program = compile_all(&files, &functions)?;
+        Ok(Self {
+            shared: Arc::new(Shared {
+                path,
+                functions,
+                current: ArcSwap::from_pointee(Snapshot { program, version: 1 }),
+                seen: Mutex::new(Some(hash(&files))),
+                last_error: Mutex::new(None),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path { &self.shared.path }
+
+    /// The current version. Hold on to it to evaluate several records against
+    /// the same policy.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.shared.current.load_full()
+    }
+
+    /// Evaluate `record` against the current version.
+    pub fn evaluate(&self, evaluator: &Evaluator, record: &Value) -> Result<Vec<Decision>, EvalError> {
+        evaluator.evaluate(&self.shared.current.load().program, record)
+    }
+
+    /// The error from the last reload, if it failed. Cleared by a successful one.
+    pub fn last_error(&self) -> Option<Arc<EngineError>> {
+        self.shared.last_error.lock().unwrap().clone()
+    }
+
+    /// Recompile the policy if its text changed since it was last compiled.
+    /// Returns the new snapshot if one was swapped in; on error the current
+    /// version stays in place. An unreadable file is reported once, not on
+    /// every call until it comes back. In a directory, adding, removing or
+    /// renaming a `.policy` file is a change.
+    pub fn reload(&self) -> Result<Option<Arc<Snapshot>>, Arc<EngineError>> {
+        let shared = &self.shared;
+        // held throughout, so concurrent reloads can't swap in an older text last
+        let mut seen = shared.seen.lock().unwrap();
+        let result = match read(&shared.path) {
+            Ok(files) if *seen == Some(hash(&files)) => return Ok(None),
+            Ok(files) => {
+                *seen = Some(hash(&files));
+                compile_all(&files, &shared.functions)
+            }
+            Err(_) if seen.is_none() => return Ok(None),
+            Err(e) => {
+                *seen = None;
+                Err(e)
+            }
+        };
+        match result {
+            Ok(program) => {
+                let next = Arc::new(Snapshot { program, version: shared.current.load().version + 1 });
+                shared.current.store(next.clone());
+                *shared.last_error.lock().unwrap() = None;
+                Ok(Some(next))
+            }
+            Err(e) => {
+                let e = Arc::new(e);
+                *shared.last_error.lock().unwrap() = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    /// Poll the policy every `interval` on a background thread and reload it when
+    /// it changes, calling `on_reload` with each outcome. Watching stops when
+    /// the returned `Watcher` is dropped. Replace the file atomically (write a
+    /// new file and rename it over the old one): a half-written file that
+    /// happens to be valid, such as an empty one, would be swapped in.
+    pub fn watch<F>(&self, interval: Duration, mut on_reload: F) -> Watcher
+    where
+        F: FnMut(Result<&Snapshot, &EngineError>) + Send + 'static,
+    {
+        let engine = self.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match engine.reload() {
+                    Ok(Some(snapshot)) => on_reload(Ok(&snapshot)),
+                    Ok(None) => {}
+                    Err(e) => on_reload(Err(&e)),
+                }
+                thread::park_timeout(interval);
+            }
+        });
+        Watcher { stop, handle: Some(handle) }
+    }
+}
+
+/// Stops the background watch started by `PolicyEngine::watch` when dropped.
+pub struct Watcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
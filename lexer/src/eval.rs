@@ -0,0 +1,451 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::ast::*;
+use crate::check::{arith_type, check_program_with, CheckError, Type};
+use crate::functions::{CallContext, Functions};
+use crate::glob;
+use crate::value::Value;
+
+//
+// ===== EVALUATOR =====
+//
+
+#[derive(Debug, Error)]
+pub enum EvalError {
+    #[error("unknown duration unit '{0}'")]
+    UnknownUnit(String),
+    #[error("cannot apply '{op}' to {left} and {right}")]
+    Arith { op: ArithOp, left: &'static str, right: &'static str },
+    #[error("cannot negate {0}")]
+    Neg(&'static str),
+    #[error("cannot compare {left} {op} {right}")]
+    Compare { left: &'static str, op: CompOp, right: &'static str },
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("arithmetic overflow")]
+    Overflow,
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("function '{name}' takes {expected} argument(s), found {found}")]
+    Arity { name: String, expected: usize, found: usize },
+    #[error("argument {index} of '{name}' must be {expected}, found {found}")]
+    Argument { name: String, index: usize, expected: Type, found: &'static str },
+    #[error("in call to '{name}': {message}")]
+    Call { name: String, message: String },
+    #[error("condition must be bool, found {0}")]
+    NotBool(&'static str),
+    #[error("quantifier needs a list, found {0}")]
+    NotList(&'static str),
+    #[error("looks_like only applies when scanning string values")]
+    NotScanning,
+}
+
+/// A statement whose condition held for a record.
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub rule: String,
+    pub statement: usize,
+    pub action: Action,
+    /// Concrete locations matched by `*`/`**` paths in the condition; empty if it has none.
+    pub targets: Vec<Field>,
+}
+
+impl Decision {
+    /// The rule in `program` that made this decision, for its metadata.
+    pub fn rule<'p>(&self, program: &'p Program) -> Option<&'p Rule> {
+        program.rules.iter().find(|r| r.name == self.rule)
+    }
+
+    /// The statement in `program` that made this decision.
+    pub fn statement<'p>(&self, program: &'p Program) -> Option<&'p Statement> {
+        self.rule(program).and_then(|r| r.statements.get(self.statement))
+    }
+
+    /// The fields the decision applies to: the fields its condition reads,
+    /// with `*`/`**` paths replaced by the locations they matched.
+    pub fn fields<'a>(&'a self, program: &'a Program) -> Vec<&'a Field> {
+        let read = self.statement(program).into_iter().flat_map(|st| st.condition.fields()).filter(|f| !f.is_pattern());
+        let mut out: Vec<&Field> = Vec::new();
+        for field in read.chain(&self.targets) {
+            if !out.contains(&field) { out.push(field); }
+        }
+        out
+    }
+
+    /// The fields the action applies to: the locations `*`/`**` paths
+    /// matched, or else the condition's subject (see `Expr::subject`).
+    /// Fields read only to decide, like `record.age` in
+    /// `record.ssn != "" and record.age > 18`, are not targeted.
+    pub fn targeted<'a>(&'a self, program: &'a Program) -> Vec<&'a Field> {
+        if !self.targets.is_empty() { return self.targets.iter().collect(); }
+        self.statement(program).into_iter().flat_map(|st| st.condition.subject()).filter(|f| !f.is_pattern()).collect()
+    }
+}
+
+impl std::fmt::Display for Decision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.action)?;
+        if !self.targets.is_empty() {
+            let targets: Vec<String> = self.targets.iter().map(|t| t.to_string()).collect();
+            write!(f, " [{}]", targets.join(", "))?;
+        }
+        write!(f, " ({}[{}])", self.rule, self.statement)
+    }
+}
+
+/// Variables bound by enclosing `any`/`all` quantifiers, innermost first.
+pub(crate) struct Scope<'a> {
+    pub(crate) var: &'a str,
+    pub(crate) value: &'a Value,
+    pub(crate) parent: Option<&'a Scope<'a>>,
+}
+
+/// The scope variable holding the string `looks_like` tests. Not an
+/// identifier, so no field can shadow or read it.
+pub(crate) const SCANNED: &str = "$scanned";
+
+impl Scope<'_> {
+    fn lookup(&self, var: &str) -> Option<&Value> {
+        if self.var == var { Some(self.value) } else { self.parent?.lookup(var) }
+    }
+}
+
+/// Resolve a field, letting quantifier variables shadow top-level record keys.
+pub(crate) fn resolve<'a>(field: &Field, record: &'a Value, scope: Option<&'a Scope<'a>>) -> &'a Value {
+    if let (Some(Segment::Key(first)), Some(scope)) = (field.segments.first(), scope)
+        && let Some(bound) = scope.lookup(first) {
+        return bound.get_path(&field.segments[1..]);
+    }
+    record.get(field)
+}
+
+pub struct Evaluator {
+    now: i64,
+    functions: Functions,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self { Self::new() }
+}
+
+impl Evaluator {
+    /// An evaluator whose `now` is the current wall-clock time.
+    pub fn new() -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        Self::with_now(now)
+    }
+
+    /// An evaluator with a fixed `now`, in seconds since the Unix epoch.
+    pub fn with_now(now: i64) -> Self { Self { now, functions: Functions::builtins() } }
+
+    /// This evaluator with `now` moved, keeping its functions.
+    pub fn at(&self, now: i64) -> Self { Self { now, functions: self.functions.clone() } }
+
+    /// Register a host function callable from conditions; see `Functions::register`.
+    pub fn register<F>(&mut self, name: &str, params: Vec<Type>, ret: Type, f: F)
+    where
+        F: Fn(&CallContext, &[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.functions.register(name, params, ret, f);
+    }
+
+    /// The evaluation timestamp, in seconds since the Unix epoch.
+    pub fn now(&self) -> i64 { self.now }
+
+    /// The functions available to conditions, for `check_program_with`.
+    pub fn functions(&self) -> &Functions { &self.functions }
+
+    /// Type-check `program` against the functions this evaluator calls, so a
+    /// program that checks is one it can evaluate.
+    pub fn check(&self, program: &Program) -> Result<(), CheckError> {
+        check_program_with(program, &self.functions)
+    }
+
+    /// Evaluate every statement of `program` against `record`, returning the
+    /// ones that fire. Rules whose schedule doesn't allow `now` are skipped.
+    pub fn evaluate(&self, program: &Program, record: &Value) -> Result<Vec<Decision>, EvalError> {
+        let mut decisions = Vec::new();
+        for rule in program.rules.iter().filter(|r| r.schedule.allows(self.now)) {
+            for (i, st) in rule.statements.iter().enumerate() {
+                if let Some(targets) = self.eval_statement(st, record)? {
+                    decisions.push(Decision { rule: rule.name.clone(), statement: i, action: st.action, targets });
+                }
+            }
+        }
+        Ok(decisions)
+    }
+
+    /// Evaluate one statement; see `statement_with`.
+    fn eval_statement(&self, st: &Statement, record: &Value) -> Result<Option<Vec<Field>>, EvalError> {
+        statement_with(st, record, |cond| self.eval_expr(cond, record))
+    }
+
+    pub fn eval_expr(&self, expr: &Expr, record: &Value) -> Result<bool, EvalError> {
+        self.expr_in(expr, record, None)
+    }
+
+    pub fn eval_operand(&self, operand: &Operand, record: &Value) -> Result<Value, EvalError> {
+        self.operand_in(operand, record, None)
+    }
+
+    pub(crate) fn expr_in(&self, expr: &Expr, record: &Value, scope: Option<&Scope>) -> Result<bool, EvalError> {
+        match expr {
+            Expr::Or(a, b) => Ok(self.expr_in(a, record, scope)? || self.expr_in(b, record, scope)?),
+            Expr::And(a, b) => Ok(self.expr_in(a, record, scope)? && self.expr_in(b, record, scope)?),
+            Expr::Not(e) => Ok(!self.expr_in(e, record, scope)?),
+            Expr::Group(e) => self.expr_in(e, record, scope),
+            Expr::Compare { left, op, right } => {
+                let l = counted(self.operand_in(left, record, scope)?, right)?;
+                let r = counted(self.operand_in(right, record, scope)?, left)?;
+                compare(&l, *op, &r)
+            }
+            Expr::In { field, set } => Ok(match resolve(field, record, scope) {
+                Value::Str(s) => set.contains(s),
+                _ => false,
+            }),
+            Expr::Call(call) => match self.eval_call(call, record, scope)? {
+                Value::Bool(b) => Ok(b),
+                Value::Null => Ok(false),
+                v => Err(EvalError::NotBool(v.type_name())),
+            },
+            Expr::Quantified { quantifier, var, list, body } => {
+                for item in &self.list_in(list, record, scope)? {
+                    let inner = Scope { var, value: item, parent: scope };
+                    let held = self.expr_in(body, record, Some(&inner))?;
+                    match quantifier {
+                        Quantifier::Any if held => return Ok(true),
+                        Quantifier::All if !held => return Ok(false),
+                        _ => {}
+                    }
+                }
+                Ok(*quantifier == Quantifier::All)
+            }
+            Expr::LooksLike(detector) => match scope.and_then(|s| s.lookup(SCANNED)) {
+                Some(Value::Str(s)) => Ok(detector.matches(s)),
+                _ => Err(EvalError::NotScanning),
+            },
+        }
+    }
+
+    pub(crate) fn operand_in(&self, operand: &Operand, record: &Value, scope: Option<&Scope>) -> Result<Value, EvalError> {
+        match operand {
+            Operand::Number(n) => Ok(Value::Number(*n)),
+            Operand::Str(s) => Ok(Value::Str(s.clone())),
+            Operand::Bool(b) => Ok(Value::Bool(*b)),
+            Operand::Duration { value, unit } => {
+                let secs = unit_seconds(unit).ok_or_else(|| EvalError::UnknownUnit(unit.clone()))?;
+                value.checked_mul(secs).map(Value::Duration).ok_or(EvalError::Overflow)
+            }
+            Operand::Field(field) => Ok(resolve(field, record, scope).clone()),
+            Operand::Call(call) => self.eval_call(call, record, scope),
+            Operand::Now => Ok(Value::Timestamp(self.now)),
+            Operand::Neg(inner) => match self.operand_in(inner, record, scope)? {
+                Value::Null => Ok(Value::Null),
+                Value::Number(n) => n.checked_neg().map(Value::Number).ok_or(EvalError::Overflow),
+                Value::Float(x) => Ok(Value::Float(-x)),
+                Value::Duration(d) => d.checked_neg().map(Value::Duration).ok_or(EvalError::Overflow),
+                v => Err(EvalError::Neg(v.type_name())),
+            },
+            Operand::Arith { left, op, right } => {
+                let l = self.operand_in(left, record, scope)?;
+                let r = self.operand_in(right, record, scope)?;
+                arith(&l, *op, &r)
+            }
+        }
+    }
+
+    /// The items a quantifier ranges over; a missing list is empty.
+    pub(crate) fn list_in(&self, list: &Operand, record: &Value, scope: Option<&Scope>) -> Result<Vec<Value>, EvalError> {
+        match self.operand_in(list, record, scope)? {
+            Value::List(items) => Ok(items),
+            Value::Null => Ok(Vec::new()),
+            v => Err(EvalError::NotList(v.type_name())),
+        }
+    }
+
+    pub(crate) fn eval_call(&self, call: &Call, record: &Value, scope: Option<&Scope>) -> Result<Value, EvalError> {
+        let name = &call.name;
+        let f = self.functions.get(name).ok_or_else(|| EvalError::UnknownFunction(name.clone()))?;
+        if f.params.len() != call.args.len() {
+            return Err(EvalError::Arity { name: name.clone(), expected: f.params.len(), found: call.args.len() });
+        }
+        let mut args = Vec::with_capacity(call.args.len());
+        for (i, (arg, &param)) in call.args.iter().zip(&f.params).enumerate() {
+            let v = match (param, self.operand_in(arg, record, scope)?) {
+                (Type::Timestamp, v @ Value::Str(_)) => v.as_timestamp().map_or(v, Value::Timestamp),
+                (_, v) => v,
+            };
+            if v == Value::Null { return Ok(Value::Null); }
+            if param != Type::Any && Type::of(&v) != param {
+                return Err(EvalError::Argument { name: name.clone(), index: i, expected: param, found: v.type_name() });
+            }
+            args.push(v);
+        }
+        f.call(&CallContext { now: self.now }, &args)
+            .map_err(|message| EvalError::Call { name: name.clone(), message })
+    }
+}
+
+/// Whether `st` fires for `record`, deciding each condition with `held`. A
+/// condition over `*`/`**` paths is tried once per matched location, and the
+/// locations where it held become the targets.
+pub(crate) fn statement_with(
+    st: &Statement,
+    record: &Value,
+    mut held: impl FnMut(&Expr) -> Result<bool, EvalError>,
+) -> Result<Option<Vec<Field>>, EvalError> {
+    if glob::pattern_fields(&st.condition).is_empty() {
+        return Ok(held(&st.condition)?.then(Vec::new));
+    }
+    let mut targets = Vec::new();
+    for (cond, binding) in instances(&st.condition, record) {
+        if held(&cond)? {
+            for concrete in binding {
+                if !targets.contains(&concrete) { targets.push(concrete); }
+            }
+        }
+    }
+    Ok((!targets.is_empty()).then_some(targets))
+}
+
+/// The condition with its `*`/`**` paths replaced by each combination of
+/// concrete locations in `record`, paired with those locations.
+pub(crate) fn instances(condition: &Expr, record: &Value) -> Vec<(Expr, Vec<Field>)> {
+    let patterns = glob::pattern_fields(condition);
+    let mut bindings: Vec<Vec<Field>> = vec![Vec::new()];
+    for pattern in &patterns {
+        let matches = glob::expand(record, pattern);
+        bindings = bindings.into_iter()
+            .flat_map(|b| matches.iter().map(move |m| { let mut b = b.clone(); b.push(m.clone()); b }))
+            .collect();
+    }
+    bindings.into_iter().map(|binding| {
+        let mut cond = condition.clone();
+        for (pattern, concrete) in patterns.iter().zip(&binding) {
+            cond = glob::substitute(&cond, pattern, concrete);
+        }
+        (cond, binding)
+    }).collect()
+}
+
+fn checked(a: i64, op: ArithOp, b: i64) -> Result<i64, EvalError> {
+    use ArithOp::*;
+    if matches!(op, Div | Rem) && b == 0 { return Err(EvalError::DivisionByZero); }
+    match op {
+        Add => a.checked_add(b),
+        Sub => a.checked_sub(b),
+        Mul => a.checked_mul(b),
+        Div => a.checked_div(b),
+        Rem => a.checked_rem(b),
+    }.ok_or(EvalError::Overflow)
+}
+
+/// A number compared with a duration literal counts the literal's unit, so
+/// `record.age_in_days > 30 days` reads `age_in_days` in days.
+pub(crate) fn counted(value: Value, other: &Operand) -> Result<Value, EvalError> {
+    match (value, other) {
+        (Value::Number(n), Operand::Duration { unit, .. }) => {
+            let secs = unit_seconds(unit).ok_or_else(|| EvalError::UnknownUnit(unit.clone()))?;
+            n.checked_mul(secs).map(Value::Duration).ok_or(EvalError::Overflow)
+        }
+        (Value::Float(x), Operand::Duration { unit, .. }) => {
+            let secs = unit_seconds(unit).ok_or_else(|| EvalError::UnknownUnit(unit.clone()))?;
+            Ok(Value::Float(x * secs as f64))
+        }
+        (value, _) => Ok(value),
+    }
+}
+
+/// `v` as a timestamp if it is a date string and `other` a duration or
+/// timestamp, as in `now - record.created_at`.
+fn dated<'v>(v: &'v Value, other: &Value) -> Cow<'v, Value> {
+    match (v, other) {
+        (Value::Str(_), Value::Duration(_) | Value::Timestamp(_)) => v.as_timestamp().map_or(Cow::Borrowed(v), |ts| Cow::Owned(Value::Timestamp(ts))),
+        _ => Cow::Borrowed(v),
+    }
+}
+
+fn float(v: &Value) -> f64 {
+    match v {
+        Value::Number(n) | Value::Duration(n) => *n as f64,
+        Value::Float(x) => *x,
+        _ => unreachable!("only numbers and durations are read as floats"),
+    }
+}
+
+fn float_arith(a: f64, op: ArithOp, b: f64) -> Result<Value, EvalError> {
+    use ArithOp::*;
+    if matches!(op, Div | Rem) && b == 0.0 { return Err(EvalError::DivisionByZero); }
+    let x = match op { Add => a + b, Sub => a - b, Mul => a * b, Div => a / b, Rem => a % b };
+    if x.is_finite() { Ok(Value::Float(x)) } else { Err(EvalError::Overflow) }
+}
+
+/// `l op r`, with the result type `check::arith_type` gives the checker.
+fn arith(l: &Value, op: ArithOp, r: &Value) -> Result<Value, EvalError> {
+    use Value::*;
+    let (l, r) = (&*dated(l, r), &*dated(r, l));
+    match (l, r) {
+        (Null, _) | (_, Null) => return Ok(Null),
+        (Float(_), Number(_) | Float(_)) | (Number(_), Float(_)) => return float_arith(float(l), op, float(r)),
+        _ => {}
+    }
+    let err = || EvalError::Arith { op, left: l.type_name(), right: r.type_name() };
+    let (Number(a) | Duration(a) | Timestamp(a), Number(b) | Duration(b) | Timestamp(b)) = (l, r) else { return Err(err()) };
+    // every pairing `arith_type` allows is commutative or has the operands in this order
+    let n = match arith_type(Type::of(l), op, Type::of(r)).ok_or_else(err)? {
+        Type::Number => Number,
+        Type::Duration => Duration,
+        Type::Timestamp => Timestamp,
+        _ => unreachable!("numbers, durations and timestamps combine into one of them"),
+    };
+    checked(*a, op, *b).map(n)
+}
+
+/// Compare two values. `Null` is only equal to itself and never ordered.
+/// Numbers compare with floats by value, and with durations as seconds, the
+/// unit durations are written to JSON in; see `counted` for duration
+/// literals. Timestamps compare with date strings as timestamps.
+pub fn compare(l: &Value, op: CompOp, r: &Value) -> Result<bool, EvalError> {
+    use Value::*;
+    match (l, op, r) {
+        (Str(_), CompOp::StartsWith | CompOp::EndsWith | CompOp::Contains, Null)
+        | (Null, CompOp::StartsWith | CompOp::EndsWith | CompOp::Contains, _) => return Ok(false),
+        (Str(a), CompOp::StartsWith, Str(b)) => return Ok(a.starts_with(b.as_str())),
+        (Str(a), CompOp::EndsWith, Str(b)) => return Ok(a.ends_with(b.as_str())),
+        (Str(a), CompOp::Contains, Str(b)) => return Ok(a.contains(b.as_str())),
+        (List(items), CompOp::Contains, _) => return Ok(items.contains(r)),
+        (_, CompOp::StartsWith | CompOp::EndsWith | CompOp::Contains, _) => {
+            return Err(EvalError::Compare { left: l.type_name(), op, right: r.type_name() });
+        }
+        _ => {}
+    }
+    let (l, r) = (&*dated(l, r), &*dated(r, l));
+    let ord = match (l, r) {
+        (Null, _) | (_, Null) => {
+            let eq = l == r;
+            return Ok(match op { CompOp::Eq => eq, CompOp::Ne => !eq, _ => false });
+        }
+        (Number(a), Number(b)) | (Duration(a), Duration(b)) | (Timestamp(a), Timestamp(b))
+        | (Number(a), Duration(b)) | (Duration(a), Number(b)) => a.cmp(b),
+        (Float(_), Number(_) | Float(_) | Duration(_)) | (Number(_) | Duration(_), Float(_)) => match float(l).partial_cmp(&float(r)) {
+            Some(ord) => ord,
+            // NaN is unordered and unequal to everything
+            None => return Ok(op == CompOp::Ne),
+        },
+        (Str(a), Str(b)) => a.cmp(b),
+        (Bool(a), Bool(b)) if matches!(op, CompOp::Eq | CompOp::Ne) => a.cmp(b),
+        _ => return Err(EvalError::Compare { left: l.type_name(), op, right: r.type_name() }),
+    };
+    Ok(match op {
+        CompOp::Eq => ord == Ordering::Equal,
+        CompOp::Ne => ord != Ordering::Equal,
+        CompOp::Gt => ord == Ordering::Greater,
+        CompOp::Lt => ord == Ordering::Less,
+        CompOp::Ge => ord != Ordering::Less,
+        CompOp::Le => ord != Ordering::Greater,
+        CompOp::StartsWith | CompOp::EndsWith | CompOp::Contains => unreachable!("handled above"),
+    })
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+
+use crate::ast::*;
+use crate::eval::{compare, counted, instances, resolve, EvalError, Evaluator, Scope};
+use crate::value::Value;
+
+//
+// ===== EXPLAIN =====
+//
+
+/// How one condition or sub-condition evaluated. Children are only the
+/// sub-conditions that were evaluated; `and`/`or` short-circuit as usual.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub expr: String,
+    pub result: bool,
+    /// Fields, calls and quantifier variables read by this predicate, with their values.
+    pub values: Vec<(String, Value)>,
+    pub children: Vec<Trace>,
+}
+
+/// The evaluation of one statement against a record.
+#[derive(Debug, Clone)]
+pub struct StatementTrace {
+    pub rule: String,
+    pub statement: usize,
+    pub action: Action,
+    pub fired: bool,
+    /// Whether the rule's schedule allowed the evaluation time. If not, the
+    /// condition wasn't evaluated and there are no traces.
+    pub in_force: bool,
+    /// One trace per combination of `*`/`**` locations, or a single trace if
+    /// the condition has no patterns.
+    pub traces: Vec<Trace>,
+}
+
+impl Evaluator {
+    /// Evaluate every statement of `program` against `record`, recording why
+    /// each condition held or not.
+    pub fn explain(&self, program: &Program, record: &Value) -> Result<Vec<StatementTrace>, EvalError> {
+        let mut out = Vec::new();
+        for rule in &program.rules {
+            let in_force = rule.schedule.allows(self.now());
+            for (i, st) in rule.statements.iter().enumerate() {
+                let traces = if !in_force { Vec::new() } else {
+                    instances(&st.condition, record).iter()
+                        .map(|(cond, _)| self.trace(cond, record, None))
+                        .collect::<Result<Vec<_>, _>>()?
+                };
+                out.push(StatementTrace {
+                    rule: rule.name.clone(),
+                    statement: i,
+                    action: st.action,
+                    fired: traces.iter().any(|t| t.result),
+                    in_force,
+                    traces,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    fn trace(&self, expr: &Expr, record: &Value, scope: Option<&Scope>) -> Result<Trace, EvalError> {
+        let leaf = |result, values| Trace { expr: expr.to_string(), result, values, children: Vec::new() };
+        Ok(match expr {
+            Expr::Or(a, b) | Expr::And(a, b) => {
+                let is_or = matches!(expr, Expr::Or(..));
+                let first = self.trace(a, record, scope)?;
+                let mut children = vec![first];
+                // evaluate the right side only if the left didn't decide
+                if children[0].result != is_or {
+                    children.push(self.trace(b, record, scope)?);
+                }
+                let result = children.last().unwrap().result;
+                Trace { expr: expr.to_string(), result, values: Vec::new(), children }
+            }
+            Expr::Not(e) => {
+                let inner = self.trace(e, record, scope)?;
+                Trace { expr: expr.to_string(), result: !inner.result, values: Vec::new(), children: vec![inner] }
+            }
+            Expr::Group(e) => self.trace(e, record, scope)?,
+            Expr::Compare { left, op, right } => {
+                let l = self.operand_in(left, record, scope)?;
+                let r = self.operand_in(right, record, scope)?;
+                let result = compare(&counted(l.clone(), right)?, *op, &counted(r.clone(), left)?)?;
+                let values = [(left, l), (right, r)].into_iter()
+                    .filter(|(o, _)| !is_literal(o))
+                    .map(|(o, v)| (o.to_string(), v))
+                    .collect();
+                leaf(result, values)
+            }
+            Expr::In { field, set } => {
+                let v = resolve(field, record, scope);
+                let result = matches!(v, Value::Str(s) if set.contains(s));
+                leaf(result, vec![(field.to_string(), v.clone())])
+            }
+            Expr::Call(call) => {
+                let v = self.eval_call(call, record, scope)?;
+                let result = match &v {
+                    Value::Bool(b) => *b,
+                    Value::Null => false,
+                    v => return Err(EvalError::NotBool(v.type_name())),
+                };
+                leaf(result, vec![(call.to_string(), v)])
+            }
+            Expr::LooksLike(_) => leaf(self.expr_in(expr, record, scope)?, Vec::new()),
+            Expr::Quantified { quantifier, var, list, body } => {
+                let items = self.list_in(list, record, scope)?;
+                let mut children = Vec::new();
+                let mut result = *quantifier == Quantifier::All;
+                for item in &items {
+                    let inner = Scope { var, value: item, parent: scope };
+                    let mut t = self.trace(body, record, Some(&inner))?;
+                    t.values.insert(0, (var.clone(), item.clone()));
+                    let held = t.result;
+                    children.push(t);
+                    if held == (*quantifier == Quantifier::Any) {
+                        result = held;
+                        break;
+                    }
+                }
+                Trace { expr: expr.to_string(), result, values: vec![(list.to_string(), Value::List(items))], children }
+            }
+        })
+    }
+}
+
+fn is_literal(operand: &Operand) -> bool {
+    matches!(operand, Operand::Number(_) | Operand::Str(_) | Operand::Bool(_) | Operand::Duration { .. })
+}
+
+impl Trace {
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{:width$}{} => {}", "", self.expr, self.result, width = depth * 2)?;
+        if !self.values.is_empty() {
+            let values: Vec<String> = self.values.iter().map(|(k, v)| format!("{k} = {v}")).collect();
+            write!(f, "  ({})", values.join(", "))?;
+        }
+        writeln!(f)?;
+        for child in &self.children {
+            child.write_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+impl fmt::Display for StatementTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match (self.in_force, self.fired) {
+            (false, _) => "not in force at this time",
+            (_, true) => "fired",
+            (_, false) => "did not fire",
+        };
+        writeln!(f, "{}[{}] {}: {outcome}", self.rule, self.statement, self.action)?;
+        if self.in_force && self.traces.is_empty() {
+            writeln!(f, "  (no locations matched)")?;
+        }
+        for t in &self.traces {
+            t.write_indented(f, 1)?;
+        }
+        Ok(())
+    }
+}
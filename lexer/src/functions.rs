@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::check::{Type, TypeError};
+use crate::value::Value;
+
+//
+// ===== FUNCTION REGISTRY =====
+//
+
+/// What a function can see of the evaluation besides its arguments.
+pub struct CallContext {
+    /// The evaluator's `now`, in seconds since the Unix epoch.
+    pub now: i64,
+}
+
+pub type NativeFn = dyn Fn(&CallContext, &[Value]) -> Result<Value, String> + Send + Sync;
+
+#[derive(Clone)]
+pub struct Function {
+    /// Parameter types; `Type::Any` accepts anything but `null` and leaves
+    /// checking to the function.
+    pub params: Vec<Type>,
+    pub ret: Type,
+    imp: Arc<NativeFn>,
+}
+
+impl Function {
+    pub fn call(&self, ctx: &CallContext, args: &[Value]) -> Result<Value, String> {
+        (self.imp)(ctx, args)
+    }
+}
+
+/// Functions callable from conditions, keyed by name.
+#[derive(Clone, Default)]
+pub struct Functions {
+    map: HashMap<String, Function>,
+}
+
+impl Functions {
+    pub fn new() -> Self { Self::default() }
+
+    /// The built-in functions: `len`, `lower`, `upper`, `trim`, `age` and `is_email`.
+    pub fn builtins() -> Self {
+        let mut fns = Self::new();
+        fns.register("len", vec![Type::Any], Type::Number, |_, args| match &args[0] {
+            Value::Str(s) => Ok(Value::Number(s.chars().count() as i64)),
+            Value::List(items) => Ok(Value::Number(items.len() as i64)),
+            v => Err(format!("expected string or list, found {}", v.type_name())),
+        });
+        fns.register("lower", vec![Type::String], Type::String, |_, args| Ok(Value::Str(as_str(&args[0]).to_lowercase())));
+        fns.register("upper", vec![Type::String], Type::String, |_, args| Ok(Value::Str(as_str(&args[0]).to_uppercase())));
+        fns.register("trim", vec![Type::String], Type::String, |_, args| Ok(Value::Str(as_str(&args[0]).trim().to_string())));
+        fns.register("age", vec![Type::Timestamp], Type::Duration, |ctx, args| match args[0] {
+            Value::Timestamp(ts) => ctx.now.checked_sub(ts).map(Value::Duration).ok_or_else(|| "arithmetic overflow".to_string()),
+            _ => unreachable!("argument types are checked before the call"),
+        });
+        fns.register("is_email", vec![Type::String], Type::Bool, |_, args| Ok(Value::Bool(is_email(as_str(&args[0])))));
+        fns
+    }
+
+    /// Register (or replace) a function. Arguments are type-checked against
+    /// `params` before `f` is called; a `null` argument, for any parameter,
+    /// short-circuits the call to `null`.
+    pub fn register<F>(&mut self, name: &str, params: Vec<Type>, ret: Type, f: F)
+    where
+        F: Fn(&CallContext, &[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.map.insert(name.to_string(), Function { params, ret, imp: Arc::new(f) });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> { self.map.get(name) }
+
+    /// Check a call's arity and argument types, returning its result type.
+    pub fn check_call(&self, name: &str, args: &[Type]) -> Result<Type, TypeError> {
+        let f = self.get(name).ok_or_else(|| TypeError::UnknownFunction(name.to_string()))?;
+        if f.params.len() != args.len() {
+            return Err(TypeError::Arity { name: name.to_string(), expected: f.params.len(), found: args.len() });
+        }
+        for (i, (&param, &arg)) in f.params.iter().zip(args).enumerate() {
+            if !param.accepts(arg) {
+                return Err(TypeError::Argument { name: name.to_string(), index: i, expected: param, found: arg });
+            }
+        }
+        Ok(f.ret)
+    }
+}
+
+fn as_str(v: &Value) -> &str {
+    match v {
+        Value::Str(s) => s,
+        _ => unreachable!("argument types are checked before the call"),
+    }
+}
+
+pub(crate) fn is_email(s: &str) -> bool {
+    let Some((local, domain)) = s.split_once('@') else { return false };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !s.chars().any(char::is_whitespace)
+}
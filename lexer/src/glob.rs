@@ -0,0 +1,139 @@
+use crate::ast::*;
+use crate::value::Value;
+
+//
+// ===== FIELD PATTERNS =====
+//
+
+/// Every concrete path in `value` matching `pattern`, in document order.
+/// Only locations that exist in `value` are returned.
+pub fn expand(value: &Value, pattern: &Field) -> Vec<Field> {
+    let mut out = Vec::new();
+    expand_into(value, &pattern.segments, &mut Vec::new(), &mut out);
+    let mut seen = Vec::new();
+    out.retain(|f| if seen.contains(f) { false } else { seen.push(f.clone()); true });
+    out
+}
+
+fn expand_into(value: &Value, pattern: &[Segment], prefix: &mut Vec<Segment>, out: &mut Vec<Field>) {
+    let Some((seg, rest)) = pattern.split_first() else {
+        out.push(Field { segments: prefix.clone() });
+        return;
+    };
+    match seg {
+        Segment::Key(_) | Segment::Index(_) => {
+            let child = value.get_path(std::slice::from_ref(seg));
+            if *child != Value::Null {
+                prefix.push(seg.clone());
+                expand_into(child, rest, prefix, out);
+                prefix.pop();
+            }
+        }
+        Segment::Wildcard => for_each_child(value, |step, child| {
+            prefix.push(step);
+            expand_into(child, rest, prefix, out);
+            prefix.pop();
+        }),
+        Segment::Glob => {
+            // zero levels, then one more level with the `**` still pending
+            expand_into(value, rest, prefix, out);
+            for_each_child(value, |step, child| {
+                prefix.push(step);
+                expand_into(child, pattern, prefix, out);
+                prefix.pop();
+            });
+        }
+    }
+}
+
+fn for_each_child(value: &Value, mut f: impl FnMut(Segment, &Value)) {
+    match value {
+        Value::Map(m) => for (k, v) in m { f(Segment::Key(k.clone()), v) },
+        Value::List(items) => for (i, v) in items.iter().enumerate() { f(Segment::Index(i), v) },
+        _ => {}
+    }
+}
+
+/// The distinct pattern fields in `expr`, ignoring paths rooted at quantifier variables.
+pub fn pattern_fields(expr: &Expr) -> Vec<Field> {
+    let mut out = Vec::new();
+    collect_expr(expr, &mut Vec::new(), &mut out);
+    out
+}
+
+fn collect_expr<'a>(expr: &'a Expr, bound: &mut Vec<&'a str>, out: &mut Vec<Field>) {
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => { collect_expr(a, bound, out); collect_expr(b, bound, out) }
+        Expr::Not(e) | Expr::Group(e) => collect_expr(e, bound, out),
+        Expr::Compare { left, right, .. } => { collect_operand(left, bound, out); collect_operand(right, bound, out) }
+        Expr::In { field, .. } => collect_field(field, bound, out),
+        Expr::Call(call) => for a in &call.args { collect_operand(a, bound, out) },
+        Expr::LooksLike(_) => {}
+        Expr::Quantified { var, list, body, .. } => {
+            collect_operand(list, bound, out);
+            bound.push(var);
+            collect_expr(body, bound, out);
+            bound.pop();
+        }
+    }
+}
+
+fn collect_operand(operand: &Operand, bound: &[&str], out: &mut Vec<Field>) {
+    match operand {
+        Operand::Field(field) => collect_field(field, bound, out),
+        Operand::Call(call) => for a in &call.args { collect_operand(a, bound, out) },
+        Operand::Neg(inner) => collect_operand(inner, bound, out),
+        Operand::Arith { left, right, .. } => { collect_operand(left, bound, out); collect_operand(right, bound, out) }
+        Operand::Number(_) | Operand::Str(_) | Operand::Bool(_) | Operand::Duration { .. } | Operand::Now => {}
+    }
+}
+
+fn collect_field(field: &Field, bound: &[&str], out: &mut Vec<Field>) {
+    let rooted_at_var = matches!(field.segments.first(), Some(Segment::Key(k)) if bound.contains(&k.as_str()));
+    if field.is_pattern() && !rooted_at_var && !out.contains(field) {
+        out.push(field.clone());
+    }
+}
+
+/// `expr` with every occurrence of `pattern` replaced by the concrete path `with`.
+pub fn substitute(expr: &Expr, pattern: &Field, with: &Field) -> Expr {
+    let sub = |e: &Expr| Box::new(substitute(e, pattern, with));
+    let op = |o: &Operand| substitute_operand(o, pattern, with);
+    match expr {
+        Expr::Or(a, b) => Expr::Or(sub(a), sub(b)),
+        Expr::And(a, b) => Expr::And(sub(a), sub(b)),
+        Expr::Not(e) => Expr::Not(sub(e)),
+        Expr::Group(e) => Expr::Group(sub(e)),
+        Expr::Compare { left, op: cmp, right } => Expr::Compare { left: op(left), op: *cmp, right: op(right) },
+        Expr::In { field, set } => Expr::In { field: swap(field, pattern, with), set: set.clone() },
+        Expr::Call(call) => Expr::Call(Call { name: call.name.clone(), args: call.args.iter().map(op).collect() }),
+        Expr::LooksLike(detector) => Expr::LooksLike(*detector),
+        Expr::Quantified { quantifier, var, list, body } => {
+            let shadowed = pattern.segments.first() == Some(&Segment::Key(var.clone()));
+            Expr::Quantified {
+                quantifier: *quantifier,
+                var: var.clone(),
+                list: op(list),
+                body: if shadowed { body.clone() } else { sub(body) },
+            }
+        }
+    }
+}
+
+fn substitute_operand(operand: &Operand, pattern: &Field, with: &Field) -> Operand {
+    let sub = |o: &Operand| Box::new(substitute_operand(o, pattern, with));
+    match operand {
+        Operand::Field(field) => Operand::Field(swap(field, pattern, with)),
+        Operand::Call(call) => Operand::Call(Call {
+            name: call.name.clone(),
+            args: call.args.iter().map(|a| substitute_operand(a, pattern, with)).collect(),
+        }),
+        Operand::Neg(inner) => Operand::Neg(sub(inner)),
+        Operand::Arith { left, op, right } => Operand::Arith { left: sub(left), op: *op, right: sub(right) },
+        other => other.clone(),
+    }
+}
+
+fn swap(field: &Field, pattern: &Field, with: &Field) -> Field {
+    if field == pattern { with.clone() } else { field.clone() }
+}
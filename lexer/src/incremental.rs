@@ -0,0 +1,229 @@
+use std::ops::Range;
+use thiserror::Error;
+
+use crate::ast::*;
+use crate::parser::{Item, ParseError, Parser};
+use crate::token::{LexError, Lexer, Token};
+
+//
+// ===== INCREMENTAL DOCUMENT =====
+//
+// Source text kept lexed and parsed across edits, for editors and watchers.
+// An edit re-lexes from the first token it can have touched until the new
+// tokens line up with the old ones again, then re-parses only the top-level
+// blocks whose tokens changed. Both steps rely on the lexer and parser being
+// stateless at the boundaries they restart from, so the result is always what
+// a full `lex` + `parse_program` of the new text would give.
+//
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum DocumentError {
+    #[error(transparent)]
+    Lex(#[from] LexError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+}
+
+/// How much work the last edit did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EditStats {
+    pub relexed_tokens: usize,
+    pub reparsed_items: usize,
+}
+
+/// A top-level block found by brace matching, with its AST if it parsed on its own.
+#[derive(Debug, Clone)]
+struct Slot {
+    tokens: Range<usize>,
+    /// Whether the block ends at its own closing `}` rather than at end of input.
+    closed: bool,
+    item: Option<Item>,
+}
+
+#[derive(Debug, Clone)]
+struct Lexed {
+    /// Owned, since the text they came from changes.
+    tokens: Vec<Token<'static>>,
+    spans: Vec<Range<usize>>,
+}
+
+pub struct Document {
+    text: String,
+    lexed: Result<Lexed, LexError>,
+    slots: Vec<Slot>,
+    stats: EditStats,
+}
+
+impl Document {
+    pub fn new(text: impl Into<String>) -> Self {
+        let mut doc = Self { text: text.into(), lexed: Err(LexError::Unterminated(0)), slots: Vec::new(), stats: EditStats::default() };
+        doc.reload();
+        doc
+    }
+
+    pub fn text(&self) -> &str { &self.text }
+
+    pub fn tokens(&self) -> Result<&[Token<'static>], &LexError> {
+        self.lexed.as_ref().map(|l| l.tokens.as_slice())
+    }
+
+    /// Byte range of each token; empty if lexing failed.
+    pub fn spans(&self) -> &[Range<usize>] {
+        self.lexed.as_ref().map_or(&[], |l| l.spans.as_slice())
+    }
+
+    pub fn stats(&self) -> EditStats { self.stats }
+
+    /// The program, or the error a full lex and parse of the text would report.
+    pub fn program(&self) -> Result<Program, DocumentError> {
+        let lexed = self.lexed.as_ref().map_err(|e| e.clone())?;
+        let mut program = Program { rules: Vec::new(), tests: Vec::new() };
+        for slot in &self.slots {
+            match &slot.item {
+                Some(Item::Rule(rule)) => program.rules.push(rule.clone()),
+                Some(Item::Test(test)) => program.tests.push(test.clone()),
+                None => {
+                    // the block didn't parse on its own; carry on as a full parse would
+                    let rest = Parser::new(lexed.tokens[slot.tokens.start..].to_vec()).parse_program()?;
+                    program.rules.extend(rest.rules);
+                    program.tests.extend(rest.tests);
+                    break;
+                }
+            }
+        }
+        Ok(program)
+    }
+
+    /// Replace the byte range `range` with `text`.
+    ///
+    /// # Panics
+    ///
+    /// Like `String::replace_range`, if the range is out of bounds or not on
+    /// character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        self.text.replace_range(range.clone(), text);
+        let Ok(lexed) = &mut self.lexed else {
+            self.reload();
+            return;
+        };
+        let delta = text.len() as isize - range.len() as isize;
+        let new_end = range.start + text.len();
+
+        // The lexer looks at most one character past a token, so tokens ending
+        // before the edit are unaffected. Re-lexing starts right after the last
+        // of them, since the edit may open or close a comment in the gap.
+        let first = lexed.spans.partition_point(|s| s.end < range.start);
+        let restart = first.checked_sub(1).map_or(0, |i| lexed.spans[i].end);
+        let (mut new_tokens, mut new_spans) = (Vec::new(), Vec::new());
+        let mut resume = lexed.tokens.len();
+        let mut search = first;
+        for next in Lexer::at(&self.text, restart) {
+            let (tok, span) = match next {
+                Ok(t) => t,
+                Err(e) => {
+                    self.lexed = Err(e);
+                    self.slots.clear();
+                    self.stats = EditStats::default();
+                    return;
+                }
+            };
+            // a token starting where an old one did, past the edit, begins the unchanged tail
+            if span.start >= new_end {
+                let old_start = (span.start as isize - delta) as usize;
+                while search < lexed.spans.len() && lexed.spans[search].start < old_start { search += 1; }
+                if lexed.spans.get(search).is_some_and(|s| s.start == old_start) {
+                    resume = search;
+                    break;
+                }
+            }
+            new_tokens.push(tok.into_owned());
+            new_spans.push(span);
+        }
+
+        let relexed = new_tokens.len();
+        for span in &mut lexed.spans[resume..] {
+            *span = (span.start as isize + delta) as usize..(span.end as isize + delta) as usize;
+        }
+        lexed.tokens.splice(first..resume, new_tokens);
+        lexed.spans.splice(first..resume, new_spans);
+
+        let reparsed = reparse(&mut self.slots, &lexed.tokens, first..resume, relexed);
+        self.stats = EditStats { relexed_tokens: relexed, reparsed_items: reparsed };
+    }
+
+    /// Lex and parse the whole text from scratch.
+    fn reload(&mut self) {
+        self.lexed = Lexer::new(&self.text)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|ts| {
+                let (tokens, spans) = ts.into_iter().map(|(t, s)| (t.into_owned(), s)).unzip();
+                Lexed { tokens, spans }
+            });
+        self.slots.clear();
+        let mut reparsed = 0;
+        if let Ok(lexed) = &self.lexed {
+            let mut start = 0;
+            while start < lexed.tokens.len() {
+                let slot = parse_slot(&lexed.tokens, start);
+                start = slot.tokens.end;
+                self.slots.push(slot);
+                reparsed += 1;
+            }
+        }
+        self.stats = EditStats { relexed_tokens: self.lexed.as_ref().map_or(0, |l| l.tokens.len()), reparsed_items: reparsed };
+    }
+}
+
+/// Update `slots` after the old tokens `changed` were replaced by `inserted`
+/// new ones, returning how many blocks were parsed again.
+fn reparse(slots: &mut Vec<Slot>, tokens: &[Token<'static>], changed: Range<usize>, inserted: usize) -> usize {
+    let delta = inserted as isize - changed.len() as isize;
+    let changed_end = changed.start + inserted;
+
+    // blocks closed before the change are untouched
+    let kept = slots.iter().position(|s| !s.closed || s.tokens.end > changed.start).unwrap_or(slots.len());
+    let mut start = slots.get(kept).map_or_else(|| slots.last().map_or(0, |s| s.tokens.end), |s| s.tokens.start);
+
+    let mut fresh = Vec::new();
+    let mut old = kept;
+    let mut resume = slots.len();
+    while start < tokens.len() {
+        // a block starting where an old one did, past the change, begins the unchanged tail
+        if start >= changed_end {
+            let old_start = (start as isize - delta) as usize;
+            while old < slots.len() && slots[old].tokens.start < old_start { old += 1; }
+            if slots.get(old).is_some_and(|s| s.tokens.start == old_start) {
+                resume = old;
+                break;
+            }
+        }
+        let slot = parse_slot(tokens, start);
+        start = slot.tokens.end;
+        fresh.push(slot);
+    }
+
+    let reparsed = fresh.len();
+    for slot in &mut slots[resume..] {
+        slot.tokens = (slot.tokens.start as isize + delta) as usize..(slot.tokens.end as isize + delta) as usize;
+    }
+    slots.splice(kept..resume, fresh);
+    reparsed
+}
+
+/// The block starting at token `start`: up to the `}` matching its first `{`,
+/// or to the end of input.
+fn parse_slot(tokens: &[Token<'static>], start: usize) -> Slot {
+    let mut depth = 0usize;
+    let (mut end, mut closed) = (tokens.len(), false);
+    for (i, tok) in tokens.iter().enumerate().skip(start) {
+        match tok {
+            Token::Symbol('{') => depth += 1,
+            Token::Symbol('}') if depth <= 1 => { end = i + 1; closed = true; break; }
+            Token::Symbol('}') => depth -= 1,
+            _ => {}
+        }
+    }
+    let mut parser = Parser::new(tokens[start..end].to_vec());
+    let item = parser.parse_item().ok().filter(|_| parser.at_end());
+    Slot { tokens: start..end, closed, item }
+}
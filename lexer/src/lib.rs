@@ -0,0 +1,55 @@
+pub mod analyze;
+pub mod api;
+pub mod audit;
+pub mod ast;
+pub mod check;
+pub mod coverage;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+pub mod diff;
+pub mod docs;
+pub mod engine;
+pub mod eval;
+pub mod explain;
+pub mod functions;
+pub mod glob;
+pub mod incremental;
+pub mod mask;
+pub mod overlay;
+pub mod parser;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod scan;
+pub mod server;
+pub mod source;
+pub mod sql;
+pub mod testing;
+pub mod time;
+pub mod token;
+pub mod value;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use analyze::{analyze, Lint, LintCode, LintConfig, Severity};
+pub use ast::*;
+pub use audit::{policy_hash, AuditError, AuditEvent, AuditSink, Auditor, JsonLinesSink, Logged, MemorySink};
+pub use check::{check_program, check_program_with, CheckError, Type, TypeError};
+pub use coverage::{BranchCoverage, Coverage, RuleCoverage, StatementCoverage};
+#[cfg(feature = "crypto")]
+pub use crypto::{Cipher, CryptoError, Keyring};
+pub use diff::{diff_programs, diff_records, Change, Effect, RecordChange};
+pub use engine::{EngineError, PolicyEngine, Snapshot, Watcher};
+pub use eval::{Decision, EvalError, Evaluator};
+pub use explain::{StatementTrace, Trace};
+pub use functions::{CallContext, Functions};
+pub use incremental::{Document, DocumentError, EditStats};
+pub use mask::{MaskError, Masker};
+pub use overlay::{resolve, OverlayError, TenantError, TenantReport, Tenants};
+pub use parser::{Item, ParseError, Parser, MAX_DEPTH};
+pub use scan::{Candidate, Finding, ScanReport};
+pub use server::{Server, MAX_BODY};
+pub use source::{LineCol, LineIndex, Spans};
+pub use sql::{to_sql, Dialect, Param, Schema, SqlError, SqlStatement, StatementError};
+pub use testing::{run_tests, TestResult};
+pub use token::{lex, Keyword, LexError, Lexer, Op, Token};
+pub use value::{records_from_jsonl, RecordError, Value};
@@ -0,0 +1,152 @@
+use std::fmt::Write as _;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::ast::{Action, Field, MaskStrategy, Program};
+use crate::check::Type;
+use crate::eval::Decision;
+use crate::time::{civil_from_days, days_from_civil, format_timestamp, parse_timestamp, SECS_PER_DAY};
+use crate::value::Value;
+
+//
+// ===== MASKING =====
+//
+// What the `mask` action does to a record. The strategy is the one written on
+// the statement, as in `then mask(last 4)`, or else the masker's default for
+// the value's type, or else `redact`:
+//
+//   strategy   string                        number                 date
+//   redact     `*` per character             null                   null
+//   last N     `*` but the last N chars      as a string, like one  -
+//   email      `a****@example.com`           -                      -
+//   hash       hex HMAC-SHA256               non-negative number    hex HMAC-SHA256
+//   month      -                             -                      first of the month
+//   year       -                             -                      January 1st
+//
+// Strings holding an ISO-8601 date or date-time are masked as dates and
+// written back as strings of the same form, `month` turning `2024-05-17`
+// into `2024-05-01`. Floats are masked like numbers.
+//
+// `email` stars a string with no `@` entirely. Hashes are keyed with the
+// masker's salt over the value's JSON, so equal values mask equally within
+// one salt and can still be joined on.
+//
+
+#[derive(Debug, Error)]
+#[error("{field}: cannot mask a {found} with `{strategy}`")]
+pub struct MaskError {
+    pub field: String,
+    pub strategy: MaskStrategy,
+    pub found: &'static str,
+}
+
+/// Masks field values, for the `mask` action.
+pub struct Masker {
+    salt: Vec<u8>,
+    defaults: Vec<(Type, MaskStrategy)>,
+}
+
+fn stars(s: &str, keep: usize) -> String {
+    let n = s.chars().count();
+    s.chars().enumerate().map(|(i, c)| if i + keep < n { '*' } else { c }).collect()
+}
+
+/// `ts` as a string shaped like `like`, a date alone or a date-time.
+fn restring(ts: i64, like: &str) -> String {
+    let mut s = format_timestamp(ts);
+    if like.len() == "YYYY-MM-DD".len() { s.truncate(like.len()); }
+    s
+}
+
+fn truncate(ts: i64, to: MaskStrategy) -> i64 {
+    let (year, month, _) = civil_from_days(ts.div_euclid(SECS_PER_DAY));
+    let month = if to == MaskStrategy::Year { 1 } else { month };
+    days_from_civil(year, month, 1) * SECS_PER_DAY
+}
+
+impl Masker {
+    /// Hash with `salt`; every type defaults to `redact`.
+    pub fn new(salt: &[u8]) -> Self {
+        Masker { salt: salt.to_vec(), defaults: Vec::new() }
+    }
+
+    /// Mask values of type `ty` with `strategy` unless a statement names one.
+    pub fn default_for(mut self, ty: Type, strategy: MaskStrategy) -> Self {
+        self.defaults.retain(|(t, _)| *t != ty);
+        self.defaults.push((ty, strategy));
+        self
+    }
+
+    /// The strategy for `value` when the statement names `written`, if any.
+    pub fn strategy(&self, written: Option<MaskStrategy>, value: &Value) -> MaskStrategy {
+        written
+            .or_else(|| {
+                let ty = if value.as_timestamp().is_some() { Type::Timestamp } else { Type::of(value) };
+                self.defaults.iter().find(|(t, _)| *t == ty).map(|(_, s)| *s)
+            })
+            .unwrap_or(MaskStrategy::Redact)
+    }
+
+    fn hash(&self, value: &Value) -> [u8; 32] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.salt).expect("HMAC accepts keys of any length");
+        mac.update(value.to_json().to_string().as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    /// `value`, at `field`, masked with `strategy`.
+    pub fn mask(&self, field: &Field, strategy: MaskStrategy, value: &Value) -> Result<Value, MaskError> {
+        use MaskStrategy::*;
+        if let Value::Str(s) = value && let Some(ts) = parse_timestamp(s) {
+            return Ok(match self.mask(field, strategy, &Value::Timestamp(ts))? {
+                Value::Timestamp(ts) => Value::Str(restring(ts, s)),
+                masked => masked,
+            });
+        }
+        Ok(match (strategy, value) {
+            (Redact, Value::Str(s)) => Value::Str(stars(s, 0)),
+            (Redact, _) => Value::Null,
+            (KeepLast(n), Value::Str(s)) => Value::Str(stars(s, n as usize)),
+            (KeepLast(n), Value::Number(x)) => Value::Str(stars(&x.to_string(), n as usize)),
+            (KeepLast(n), Value::Float(x)) => Value::Str(stars(&x.to_string(), n as usize)),
+            (Email, Value::Str(s)) => match s.rsplit_once('@') {
+                Some((local, domain)) => {
+                    let first = local.chars().next().map_or(0, char::len_utf8);
+                    Value::Str(format!("{}{}@{domain}", &local[..first], stars(&local[first..], 0)))
+                }
+                None => Value::Str(stars(s, 0)),
+            },
+            (Hash, Value::Number(_) | Value::Float(_)) => {
+                let bytes = self.hash(value);
+                Value::Number(i64::from_be_bytes(bytes[..8].try_into().unwrap()) & i64::MAX)
+            }
+            (Hash, _) => {
+                let mut hex = String::with_capacity(64);
+                for b in self.hash(value) { let _ = write!(hex, "{b:02x}"); }
+                Value::Str(hex)
+            }
+            (Month | Year, Value::Timestamp(ts)) => Value::Timestamp(truncate(*ts, strategy)),
+            _ => return Err(MaskError { field: field.to_string(), strategy, found: value.type_name() }),
+        })
+    }
+
+    /// Mask the fields matched by the `mask` decisions among `decisions`,
+    /// made by evaluating `record` against `program`; see `Decision::targeted`.
+    /// Only strings, numbers and dates are masked; other values, and missing
+    /// fields, are left as they are. Returns the fields masked.
+    pub fn apply(&self, program: &Program, decisions: &[Decision], record: &mut Value) -> Result<Vec<Field>, MaskError> {
+        let mut done: Vec<Field> = Vec::new();
+        for decision in decisions.iter().filter(|d| d.action == Action::Mask) {
+            let written = decision.statement(program).and_then(|st| st.mask);
+            for field in decision.targeted(program) {
+                if done.contains(field) { continue; }
+                let Some(slot) = record.get_mut(field) else { continue };
+                if !matches!(slot, Value::Str(_) | Value::Number(_) | Value::Float(_) | Value::Timestamp(_)) { continue; }
+                *slot = self.mask(field, self.strategy(written, slot), slot)?;
+                done.push(field.clone());
+            }
+        }
+        Ok(done)
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+
+use crate::ast::*;
+use crate::diff::{diff_programs, Change};
+
+//
+// ===== TENANT OVERLAYS =====
+//
+// One base program shared by many tenants, each with an overlay that adds,
+// overrides or disables rules by name. Resolution is strict: adding a rule
+// the base already has, overriding or disabling one it doesn't, or naming a
+// rule twice is an error, so renaming a base rule can't silently leave a
+// tenant's override behind as an extra rule.
+//
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum OverlayError {
+    #[error("rule '{0}' is already in the base policy; use `override rule {0}` to replace it")]
+    AlreadyInBase(String),
+    #[error("cannot {verb} rule '{rule}': the base policy has no such rule")]
+    NotInBase { verb: &'static str, rule: String },
+    #[error("rule '{0}' is changed more than once")]
+    Repeated(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TenantError {
+    #[error("no tenant '{0}'")]
+    Unknown(String),
+    #[error("tenant '{tenant}': {error}")]
+    Overlay { tenant: String, #[source] error: OverlayError },
+}
+
+/// The effective program of `base` with `overlay` applied. Overridden rules
+/// keep their place, added ones follow the base's, and the overlay's tests
+/// follow the base's tests.
+pub fn resolve(base: &Program, overlay: &Overlay) -> Result<Program, OverlayError> {
+    let mut rules: Vec<Option<Rule>> = base.rules.iter().cloned().map(Some).collect();
+    let mut added = Vec::new();
+    let mut named = HashSet::new();
+    for item in &overlay.items {
+        let name = item.rule();
+        if !named.insert(name) {
+            return Err(OverlayError::Repeated(name.to_string()));
+        }
+        match (item, base.rules.iter().position(|r| r.name == name)) {
+            (OverlayItem::Add(_), Some(_)) => return Err(OverlayError::AlreadyInBase(name.to_string())),
+            (OverlayItem::Add(rule), None) => added.push(rule.clone()),
+            (OverlayItem::Override(rule), Some(i)) => {
+                let doc = rule.doc.clone().or_else(|| base.rules[i].doc.clone());
+                let metadata = if rule.metadata.is_empty() { base.rules[i].metadata.clone() } else { rule.metadata.clone() };
+                rules[i] = Some(Rule { doc, metadata, ..rule.clone() });
+            }
+            (OverlayItem::Disable(_), Some(i)) => rules[i] = None,
+            (item, None) => {
+                let verb = if matches!(item, OverlayItem::Override(_)) { "override" } else { "disable" };
+                return Err(OverlayError::NotInBase { verb, rule: name.to_string() });
+            }
+        }
+    }
+    Ok(Program {
+        rules: rules.into_iter().flatten().chain(added).collect(),
+        tests: base.tests.iter().chain(&overlay.tests).cloned().collect(),
+    })
+}
+
+/// A base program and the overlays of its tenants.
+#[derive(Debug, Clone)]
+pub struct Tenants {
+    base: Program,
+    overlays: BTreeMap<String, Overlay>,
+}
+
+/// How one tenant's effective program differs from the base, in overlay
+/// order: `RuleAdded` and `RuleRemoved` for added and disabled rules, and the
+/// differences an override makes to its base rule.
+#[derive(Debug, Clone)]
+pub struct TenantReport {
+    pub tenant: String,
+    pub changes: Vec<Change>,
+}
+
+impl Tenants {
+    pub fn new(base: Program) -> Self {
+        Self { base, overlays: BTreeMap::new() }
+    }
+
+    /// Add a tenant, replacing any of the same name.
+    pub fn tenant(mut self, name: impl Into<String>, overlay: Overlay) -> Self {
+        self.overlays.insert(name.into(), overlay);
+        self
+    }
+
+    pub fn base(&self) -> &Program { &self.base }
+
+    /// Tenant names, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.overlays.keys().map(String::as_str)
+    }
+
+    /// The effective program of `tenant`.
+    pub fn effective(&self, tenant: &str) -> Result<Program, TenantError> {
+        let overlay = self.overlays.get(tenant).ok_or_else(|| TenantError::Unknown(tenant.to_string()))?;
+        resolve(&self.base, overlay).map_err(|error| TenantError::Overlay { tenant: tenant.to_string(), error })
+    }
+
+    /// Where each tenant differs from the base, in tenant order.
+    pub fn report(&self) -> Result<Vec<TenantReport>, TenantError> {
+        self.names().map(|tenant| {
+            let effective = self.effective(tenant)?;
+            let mut changes = Vec::new();
+            for item in &self.overlays[tenant].items {
+                match item {
+                    OverlayItem::Add(rule) => changes.push(Change::RuleAdded(rule.name.clone())),
+                    OverlayItem::Disable(name) => changes.push(Change::RuleRemoved(name.clone())),
+                    OverlayItem::Override(rule) => {
+                        // compared one rule at a time, so nothing reads as a rename
+                        let only = |p: &Program| Program {
+                            rules: p.rules.iter().filter(|r| r.name == rule.name).cloned().collect(),
+                            tests: Vec::new(),
+                        };
+                        changes.extend(diff_programs(&only(&self.base), &only(&effective)));
+                    }
+                }
+            }
+            Ok(TenantReport { tenant: tenant.to_string(), changes })
+        }).collect()
+    }
+}
+
+impl fmt::Display for TenantReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.changes.len() {
+            0 => return writeln!(f, "tenant {}: same as base", self.tenant),
+            1 => writeln!(f, "tenant {}: 1 change", self.tenant)?,
+            n => writeln!(f, "tenant {}: {n} changes", self.tenant)?,
+        }
+        for change in &self.changes {
+            writeln!(f, "  {change}")?;
+        }
+        Ok(())
+    }
+}
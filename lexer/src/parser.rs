@@ -0,0 +1,684 @@
+use thiserror::Error;
+
+use crate::ast::*;
+use crate::time::{parse_time_of_day, parse_timestamp};
+use crate::token::{Keyword, Op, Token};
+
+//
+// ===== PARSER =====
+//
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParseError {
+    #[error("unexpected end of input")]
+    Eof,
+    #[error("unexpected token: {0:?}")]
+    Unexpected(Token<'static>),
+    #[error("expected {expected}, found {found:?}")]
+    Expected { expected: String, found: Token<'static> },
+    #[error("expression nested more than {MAX_DEPTH} levels deep")]
+    TooDeep,
+    #[error("metadata key '{0}' given twice")]
+    DuplicateKey(String),
+}
+
+/// Nesting limit for parentheses, `not`, quantifiers and unary minus, so that
+/// hostile input fails with an error instead of overflowing the stack.
+pub const MAX_DEPTH: usize = 64;
+
+/// A top-level block of a program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Rule(Rule),
+    Test(Test),
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token<'a>>) -> Self { Self { tokens, pos: 0, depth: 0 } }
+    fn peek(&self) -> Option<&Token<'a>> { self.tokens.get(self.pos) }
+    fn advance(&mut self) -> Option<Token<'a>> {
+        let t = self.tokens.get(self.pos).cloned();
+        if t.is_some() { self.pos += 1; }
+        t
+    }
+    fn expect_symbol(&mut self, ch: char) -> Result<(), ParseError> {
+        match self.advance() {
+            Some(Token::Symbol(c)) if c == ch => Ok(()),
+            Some(t) => Err(ParseError::Expected { expected: format!("'{}'", ch), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+    fn expect_keyword(&mut self, kw: Keyword) -> Result<(), ParseError> {
+        match self.advance() {
+            Some(Token::Keyword(k)) if k == kw => Ok(()),
+            Some(t) => Err(ParseError::Expected { expected: kw.to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+    fn match_keyword(&mut self, kw: Keyword) -> bool {
+        if matches!(self.peek(), Some(Token::Keyword(k)) if *k == kw) {
+            self.pos += 1; true
+        } else { false }
+    }
+    fn match_symbol(&mut self, ch: char) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(c)) if *c == ch) {
+            self.pos += 1; true
+        } else { false }
+    }
+    fn match_ident(&mut self, s: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(id)) if id == s) {
+            self.pos += 1; true
+        } else { false }
+    }
+    fn match_op(&mut self, op: Op) -> bool {
+        if matches!(self.peek(), Some(Token::Operator(o)) if *o == op) {
+            self.pos += 1; true
+        } else { false }
+    }
+    fn expect_ident(&mut self) -> Result<String, ParseError> {
+        match self.advance() {
+            Some(Token::Ident(s)) => Ok(s.into_owned()),
+            Some(t) => Err(ParseError::Expected { expected: "identifier".to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+    fn nested<T>(&mut self, f: fn(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth >= MAX_DEPTH { return Err(ParseError::TooDeep); }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+    fn expect_number(&mut self) -> Result<i64, ParseError> {
+        match self.advance() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(t) => Err(ParseError::Expected { expected: "number".to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+    // program := { item }
+    pub fn parse_program(&mut self) -> Result<Program, ParseError> {
+        let mut program = Program { rules: Vec::new(), tests: Vec::new() };
+        while self.peek().is_some() {
+            match self.parse_item()? {
+                Item::Rule(rule) => program.rules.push(rule),
+                Item::Test(test) => program.tests.push(test),
+            }
+        }
+        Ok(program)
+    }
+
+    // item := rule | test
+    pub fn parse_item(&mut self) -> Result<Item, ParseError> {
+        let doc = self.parse_doc();
+        match self.peek() {
+            Some(Token::Keyword(Keyword::Rule)) => Ok(Item::Rule(Rule { doc, ..self.parse_rule()? })),
+            // doc comments document rules only
+            Some(tok) if doc.is_some() => {
+                Err(ParseError::Expected { expected: "rule after doc comment".to_string(), found: tok.clone().into_owned() })
+            }
+            // `test` is contextual so that it stays usable as a field name
+            Some(Token::Ident(id)) if id == "test" => Ok(Item::Test(self.parse_test()?)),
+            Some(tok) => Err(ParseError::Unexpected(tok.clone().into_owned())),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+    // overlay := { [ 'override' ] rule | 'disable' 'rule' ident [';'] | test }
+    pub fn parse_overlay(&mut self) -> Result<Overlay, ParseError> {
+        let mut overlay = Overlay::default();
+        while self.peek().is_some() {
+            // like `test`, `override` and `disable` are contextual
+            if self.match_ident("disable") {
+                self.expect_keyword(Keyword::Rule)?;
+                overlay.items.push(OverlayItem::Disable(self.expect_ident()?));
+                let _ = self.match_symbol(';');
+                continue;
+            }
+            let doc = self.parse_doc();
+            let overriding = self.match_ident("override");
+            if doc.is_none() && !overriding {
+                match self.parse_item()? {
+                    Item::Rule(rule) => overlay.items.push(OverlayItem::Add(rule)),
+                    Item::Test(test) => overlay.tests.push(test),
+                }
+                continue;
+            }
+            let rule = match self.peek() {
+                Some(Token::Keyword(Keyword::Rule)) => Rule { doc, ..self.parse_rule()? },
+                Some(tok) => {
+                    let expected = if overriding { "rule after `override`" } else { "rule after doc comment" };
+                    return Err(ParseError::Expected { expected: expected.to_string(), found: tok.clone().into_owned() });
+                }
+                None => return Err(ParseError::Eof),
+            };
+            overlay.items.push(if overriding { OverlayItem::Override(rule) } else { OverlayItem::Add(rule) });
+        }
+        Ok(overlay)
+    }
+
+    /// The `///` lines at the current position, joined with newlines.
+    fn parse_doc(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+        while let Some(Token::Doc(line)) = self.peek() {
+            lines.push(line.to_string());
+            self.advance();
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// Whether every token has been consumed.
+    pub fn at_end(&self) -> bool { self.pos == self.tokens.len() }
+
+    /// The index of the token `error`, returned by this parser, complains
+    /// about: the one just consumed or the one next up. `None` at end of input.
+    pub fn error_token(&self, error: &ParseError) -> Option<usize> {
+        match error {
+            ParseError::Eof => None,
+            ParseError::TooDeep | ParseError::DuplicateKey(_) => self.pos.checked_sub(1),
+            ParseError::Unexpected(found) | ParseError::Expected { found, .. } => [self.pos.checked_sub(1), Some(self.pos)]
+                .into_iter().flatten()
+                .find(|&i| self.tokens.get(i) == Some(found)),
+        }
+    }
+
+    // rule := 'rule' ident [ metadata ] schedule '{' { statement [';'] } '}'
+    fn parse_rule(&mut self) -> Result<Rule, ParseError> {
+        self.expect_keyword(Keyword::Rule)?;
+        let name = self.expect_ident()?;
+        let metadata = if self.match_symbol('[') { self.parse_metadata()? } else { Vec::new() };
+        let schedule = self.parse_schedule()?;
+        self.expect_symbol('{')?;
+        let mut statements = Vec::new();
+        while !self.match_symbol('}') {
+            statements.push(self.parse_statement()?);
+            // optional semicolon
+            let _ = self.match_symbol(';');
+        }
+        Ok(Rule { doc: None, name, metadata, schedule, statements })
+    }
+
+    // metadata := '[' [ ident ':' literal { ',' ident ':' literal } ] ']'
+    fn parse_metadata(&mut self) -> Result<Vec<(String, Literal)>, ParseError> {
+        let mut metadata: Vec<(String, Literal)> = Vec::new();
+        if self.match_symbol(']') { return Ok(metadata); }
+        loop {
+            let key = self.expect_ident()?;
+            if metadata.iter().any(|(k, _)| *k == key) { return Err(ParseError::DuplicateKey(key)); }
+            self.expect_symbol(':')?;
+            metadata.push((key, self.parse_literal()?));
+            if self.match_symbol(']') { return Ok(metadata); }
+            self.expect_symbol(',')?;
+        }
+    }
+
+    // schedule := [ 'from' date ] [ 'until' date ] [ 'on' day { ',' day } ]
+    //             [ 'between' time 'and' time ]
+    // The words are contextual, like `test`.
+    fn parse_schedule(&mut self) -> Result<Schedule, ParseError> {
+        let mut schedule = Schedule::default();
+        if self.match_ident("from") { schedule.from = Some(self.parse_date()?); }
+        if self.match_ident("until") { schedule.until = Some(self.parse_date()?); }
+        if self.match_ident("on") {
+            loop {
+                let day = match self.advance() {
+                    Some(Token::Ident(id)) if let Some(day) = Weekday::from_name(&id) => day,
+                    Some(t) => return Err(ParseError::Expected { expected: "day of the week".to_string(), found: t.into_owned() }),
+                    None => return Err(ParseError::Eof),
+                };
+                if !schedule.days.contains(&day) { schedule.days.push(day); }
+                if !self.match_symbol(',') { break; }
+            }
+            schedule.days.sort();
+        }
+        if self.match_ident("between") {
+            let open = self.parse_time_of_day()?;
+            self.expect_keyword(Keyword::And)?;
+            schedule.hours = Some((open, self.parse_time_of_day()?));
+        }
+        Ok(schedule)
+    }
+
+    fn parse_date(&mut self) -> Result<i64, ParseError> {
+        match self.advance() {
+            Some(Token::Str(s)) if let Some(ts) = parse_timestamp(&s) => Ok(ts),
+            Some(t) => Err(ParseError::Expected { expected: "date like \"2026-01-01\"".to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+    fn parse_time_of_day(&mut self) -> Result<u32, ParseError> {
+        match self.advance() {
+            Some(Token::Str(s)) if let Some(minutes) = parse_time_of_day(&s) => Ok(minutes),
+            Some(t) => Err(ParseError::Expected { expected: "time of day like \"05:00\"".to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+
+    // test := 'test' string [ 'at' date ] '{' 'given' '{' [ key ':' literal { ',' key ':' literal } ] '}'
+    //         'expect' expectation { 'and' expectation } '}'
+    fn parse_test(&mut self) -> Result<Test, ParseError> {
+        self.advance();
+        let name = match self.advance() {
+            Some(Token::Str(s)) => s,
+            Some(t) => return Err(ParseError::Expected { expected: "test name".to_string(), found: t.into_owned() }),
+            None => return Err(ParseError::Eof),
+        };
+        let at = if self.match_ident("at") { Some(self.parse_date()?) } else { None };
+        self.expect_symbol('{')?;
+        self.expect_word("given")?;
+        self.expect_symbol('{')?;
+        let mut given = Vec::new();
+        if !self.match_symbol('}') {
+            loop {
+                let field = self.parse_key_path()?;
+                self.expect_symbol(':')?;
+                given.push((field, self.parse_literal()?));
+                if self.match_symbol('}') { break; }
+                self.expect_symbol(',')?;
+            }
+        }
+        self.expect_word("expect")?;
+        let mut expect = vec![self.parse_expectation()?];
+        while self.match_keyword(Keyword::And) {
+            expect.push(self.parse_expectation()?);
+        }
+        self.expect_symbol('}')?;
+        Ok(Test { name: name.into_owned(), at, given, expect })
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        if self.match_ident(word) { return Ok(()); }
+        match self.advance() {
+            Some(t) => Err(ParseError::Expected { expected: word.to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+    // key := ident { '.' ident }
+    fn parse_key_path(&mut self) -> Result<Field, ParseError> {
+        let mut segments = vec![Segment::Key(self.expect_ident()?)];
+        while self.match_symbol('.') {
+            segments.push(Segment::Key(self.expect_ident()?));
+        }
+        Ok(Field { segments })
+    }
+
+    // literal := 'true' | 'false' | ['-'] number [unit] | string | ident | '[' [ literal { ',' literal } ] ']'
+    fn parse_literal(&mut self) -> Result<Literal, ParseError> {
+        if self.match_op(Op::Minus) {
+            let n = self.expect_number()?;
+            return Ok(Literal::Number(-n));
+        }
+        match self.advance() {
+            Some(Token::Number(n)) => match self.peek() {
+                Some(Token::Ident(unit)) => {
+                    let unit = unit.clone();
+                    self.advance();
+                    Ok(Literal::Duration { value: n, unit: unit.into_owned() })
+                }
+                _ => Ok(Literal::Number(n)),
+            },
+            Some(Token::Str(s)) => Ok(Literal::Str(s.into_owned())),
+            Some(Token::Ident(id)) => Ok(match &*id {
+                "true" => Literal::Bool(true),
+                "false" => Literal::Bool(false),
+                _ => Literal::Str(id.into_owned()),
+            }),
+            Some(Token::Symbol('[')) => {
+                let mut items = Vec::new();
+                if !self.match_symbol(']') {
+                    loop {
+                        items.push(self.parse_literal()?);
+                        if self.match_symbol(']') { break; }
+                        self.expect_symbol(',')?;
+                    }
+                }
+                Ok(Literal::List(items))
+            }
+            Some(t) => Err(ParseError::Expected { expected: "literal".to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+    // expectation := 'nothing' | ['no'] action
+    fn parse_expectation(&mut self) -> Result<Expectation, ParseError> {
+        if self.match_ident("nothing") {
+            Ok(Expectation::Nothing)
+        } else if self.match_ident("no") {
+            Ok(Expectation::NotFires(self.parse_action()?))
+        } else {
+            Ok(Expectation::Fires(self.parse_action()?))
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect_keyword(Keyword::If)?;
+        let condition = self.parse_condition()?;
+        self.expect_keyword(Keyword::Then)?;
+        let action = self.parse_action()?;
+        let mask = if action == Action::Mask && self.match_symbol('(') {
+            let strategy = self.parse_mask_strategy()?;
+            self.expect_symbol(')')?;
+            Some(strategy)
+        } else {
+            None
+        };
+        Ok(Statement { condition, action, mask })
+    }
+
+    // strategy := 'redact' | 'last' NUMBER | 'email' | 'hash' | 'month' | 'year'
+    fn parse_mask_strategy(&mut self) -> Result<MaskStrategy, ParseError> {
+        let expected = || "mask strategy (redact, last N, email, hash, month or year)".to_string();
+        match self.advance() {
+            Some(Token::Ident(name)) => match name.as_ref() {
+                "redact" => Ok(MaskStrategy::Redact),
+                "email" => Ok(MaskStrategy::Email),
+                "hash" => Ok(MaskStrategy::Hash),
+                "month" => Ok(MaskStrategy::Month),
+                "year" => Ok(MaskStrategy::Year),
+                "last" => match self.advance() {
+                    Some(Token::Number(n)) => u32::try_from(n).map(MaskStrategy::KeepLast).map_err(|_| ParseError::Expected {
+                        expected: "character count".to_string(), found: Token::Number(n),
+                    }),
+                    Some(t) => Err(ParseError::Expected { expected: "character count".to_string(), found: t.into_owned() }),
+                    None => Err(ParseError::Eof),
+                },
+                _ => Err(ParseError::Expected { expected: expected(), found: Token::Ident(name).into_owned() }),
+            },
+            Some(t) => Err(ParseError::Expected { expected: expected(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+
+    fn parse_condition(&mut self) -> Result<Expr, ParseError> {
+        self.parse_or()
+    }
+
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_and()?;
+        while self.match_keyword(Keyword::Or) {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_not()?;
+        while self.match_keyword(Keyword::And) {
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ParseError> {
+        self.nested(Self::parse_not_inner)
+    }
+
+    fn parse_not_inner(&mut self) -> Result<Expr, ParseError> {
+        if self.match_keyword(Keyword::Not) {
+            let inner = self.parse_not()?;
+            Ok(Expr::Not(Box::new(inner)))
+        } else if self.match_keyword(Keyword::Any) {
+            self.parse_quantified(Quantifier::Any)
+        } else if self.match_keyword(Keyword::All) {
+            self.parse_quantified(Quantifier::All)
+        } else {
+            self.parse_predicate()
+        }
+    }
+
+    // quantified := ('any' | 'all') ident 'in' operand ':' not
+    fn parse_quantified(&mut self, quantifier: Quantifier) -> Result<Expr, ParseError> {
+        let var = self.expect_ident()?;
+        self.expect_keyword(Keyword::In)?;
+        let list = self.parse_operand()?;
+        self.expect_symbol(':')?;
+        let body = self.parse_not()?;
+        Ok(Expr::Quantified { quantifier, var, list, body: Box::new(body) })
+    }
+
+
+    fn parse_predicate(&mut self) -> Result<Expr, ParseError> {
+        // `(` opens either a grouped condition or a parenthesized operand such
+        // as `(a + b) * 2 > c`; the latter is followed by an operator.
+        if matches!(self.peek(), Some(Token::Symbol('('))) && !self.paren_is_operand() {
+            self.advance();
+            let inner = self.parse_condition()?;
+            self.expect_symbol(')')?;
+            return Ok(Expr::Group(Box::new(inner)));
+        }
+
+        // `looks_like(ssn)`; the word is contextual, like `test`
+        if matches!(self.peek(), Some(Token::Ident(word)) if word == "looks_like")
+            && matches!(self.tokens.get(self.pos + 1), Some(Token::Symbol('('))) {
+            self.pos += 2;
+            let detector = match self.advance() {
+                Some(Token::Ident(id)) if let Some(detector) = Detector::from_name(&id) => detector,
+                Some(t) => {
+                    let names: Vec<&str> = Detector::ALL.map(Detector::name).to_vec();
+                    return Err(ParseError::Expected { expected: format!("detector ({})", names.join(", ")), found: t.into_owned() });
+                }
+                None => return Err(ParseError::Eof),
+            };
+            self.expect_symbol(')')?;
+            return Ok(Expr::LooksLike(detector));
+        }
+
+        let save = self.pos;
+
+        if let Ok(field) = self.parse_field()
+            && self.match_keyword(Keyword::In) {
+            self.expect_symbol('[')?;
+            let mut list = Vec::new();
+            loop {
+                let ident = self.expect_ident()?;
+                list.push(ident);
+                if self.match_symbol(']') { break; }
+                self.expect_symbol(',')?;
+            }
+            return Ok(Expr::In { field, set: list });
+        }
+        self.pos = save;
+
+        match self.parse_operand()? {
+            // a call on its own is a boolean predicate, e.g. `is_email(field)`
+            Operand::Call(call) if !self.at_comp_op() => Ok(Expr::Call(call)),
+            left => {
+                let op = self.parse_comp_op()?;
+                let right = self.parse_operand()?;
+                Ok(Expr::Compare { left, op, right })
+            }
+        }
+    }
+
+    /// Whether the `(` at the current position is closed by a `)` followed by an operator.
+    fn paren_is_operand(&self) -> bool {
+        let mut depth = 0usize;
+        for (i, t) in self.tokens[self.pos..].iter().enumerate() {
+            match t {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => {
+                    depth -= 1;
+                    if depth == 0 { return self.is_operator_at(self.pos + i + 1); }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn is_operator_at(&self, pos: usize) -> bool {
+        match self.tokens.get(pos) {
+            Some(Token::Operator(_)) => true,
+            Some(Token::Ident(word)) => ["starts_with","ends_with","contains"].contains(&&**word),
+            _ => false,
+        }
+    }
+
+    fn parse_comp_op(&mut self) -> Result<CompOp, ParseError> {
+        use CompOp::*;
+        if self.match_op(Op::EqEq) { return Ok(Eq); }
+        if self.match_op(Op::NotEq) { return Ok(Ne); }
+        if self.match_op(Op::GtEq) { return Ok(Ge); }
+        if self.match_op(Op::LtEq) { return Ok(Le); }
+        if self.match_op(Op::Gt)  { return Ok(Gt); }
+        if self.match_op(Op::Lt)  { return Ok(Lt); }
+        if self.match_ident("starts_with") { return Ok(StartsWith); }
+        if self.match_ident("ends_with") { return Ok(EndsWith); }
+        if self.match_ident("contains") { return Ok(Contains); }
+        Err(self.unexpected("comparison operator"))
+    }
+
+    fn at_comp_op(&self) -> bool {
+        match self.peek() {
+            Some(Token::Operator(op)) => matches!(op, Op::EqEq | Op::NotEq | Op::GtEq | Op::LtEq | Op::Gt | Op::Lt),
+            _ => self.is_operator_at(self.pos),
+        }
+    }
+
+    fn unexpected(&mut self, expected: &str) -> ParseError {
+        match self.peek().cloned() {
+            Some(t) => ParseError::Expected { expected: expected.to_string(), found: t.into_owned() },
+            None => ParseError::Eof,
+        }
+    }
+
+    // operand := term { ('+' | '-') term }
+    fn parse_operand(&mut self) -> Result<Operand, ParseError> {
+        let mut left = self.parse_term()?;
+        loop {
+            let op = if self.match_op(Op::Plus) { ArithOp::Add }
+                else if self.match_op(Op::Minus) { ArithOp::Sub }
+                else { break };
+            let right = self.parse_term()?;
+            left = Operand::Arith { left: Box::new(left), op, right: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    // term := unary { ('*' | '/' | '%') unary }
+    fn parse_term(&mut self) -> Result<Operand, ParseError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = if self.match_op(Op::Star) { ArithOp::Mul }
+                else if self.match_op(Op::Slash) { ArithOp::Div }
+                else if self.match_op(Op::Percent) { ArithOp::Rem }
+                else { break };
+            let right = self.parse_unary()?;
+            left = Operand::Arith { left: Box::new(left), op, right: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    // unary := '-' unary | primary
+    fn parse_unary(&mut self) -> Result<Operand, ParseError> {
+        self.nested(Self::parse_unary_inner)
+    }
+
+    fn parse_unary_inner(&mut self) -> Result<Operand, ParseError> {
+        if self.match_op(Op::Minus) {
+            let inner = self.parse_unary()?;
+            return Ok(Operand::Neg(Box::new(inner)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Operand, ParseError> {
+        match self.peek().cloned() {
+            Some(Token::Number(_)) => {
+                let n = self.expect_number()?;
+                if let Some(Token::Ident(unit)) = self.peek().cloned()
+                    && !self.at_comp_op() {
+                    // duration
+                    self.advance();
+                    Ok(Operand::Duration { value: n, unit: unit.into_owned() })
+                } else {
+                    Ok(Operand::Number(n))
+                }
+            }
+            Some(Token::Str(s)) => {
+                self.advance();
+                Ok(Operand::Str(s.into_owned()))
+            }
+            Some(Token::Ident(name)) if matches!(self.tokens.get(self.pos + 1), Some(Token::Symbol('('))) => {
+                self.pos += 2;
+                let mut args = Vec::new();
+                if !self.match_symbol(')') {
+                    loop {
+                        args.push(self.parse_operand()?);
+                        if self.match_symbol(')') { break; }
+                        self.expect_symbol(',')?;
+                    }
+                }
+                Ok(Operand::Call(Call { name: name.into_owned(), args }))
+            }
+            Some(Token::Ident(b)) if b == "true" || b == "false" => {
+                self.advance();
+                Ok(Operand::Bool(b == "true"))
+            }
+            Some(Token::Keyword(Keyword::Now)) => {
+                self.advance();
+                Ok(Operand::Now)
+            }
+            Some(Token::Symbol('(')) => {
+                self.advance();
+                let inner = self.parse_operand()?;
+                self.expect_symbol(')')?;
+                Ok(inner)
+            }
+            _ => {
+                let field = self.parse_field()?;
+                Ok(Operand::Field(field))
+            }
+        }
+    }
+
+
+    // field := ident { '.' (ident | '*' | '**') | '[' number ']' }
+    fn parse_field(&mut self) -> Result<Field, ParseError> {
+        let first = self.expect_ident()?;
+        let mut segs = vec![Segment::Key(first)];
+        loop {
+            if self.match_symbol('.') {
+                if self.match_op(Op::Star) { segs.push(Segment::Wildcard); }
+                else if self.match_op(Op::StarStar) { segs.push(Segment::Glob); }
+                else { segs.push(Segment::Key(self.expect_ident()?)); }
+            } else if self.match_symbol('[') {
+                let n = self.expect_number()?;
+                self.expect_symbol(']')?;
+                segs.push(Segment::Index(n as usize));
+            } else {
+                break;
+            }
+        }
+        Ok(Field { segments: segs })
+    }
+
+    fn parse_action(&mut self) -> Result<Action, ParseError> {
+        match self.advance() {
+            Some(Token::Keyword(k)) => match k {
+                Keyword::Delete  => Ok(Action::Delete),
+                Keyword::Mask    => Ok(Action::Mask),
+                Keyword::Notify  => Ok(Action::Notify),
+                Keyword::Encrypt => Ok(Action::Encrypt),
+                _ => Err(ParseError::Expected { expected: "action keyword".to_string(), found: Token::Keyword(k) })
+            },
+            Some(t) => Err(ParseError::Expected { expected: "action keyword".to_string(), found: t.into_owned() }),
+            None => Err(ParseError::Eof),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use std::ops::Range;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::PyTypeInfo;
+use pyo3::types::{
+    PyBool, PyDate, PyDateAccess, PyDateTime, PyDelta, PyDeltaAccess, PyDict, PyFloat, PyInt, PyList, PyString, PyTimeAccess,
+    PyTuple, PyTzInfo, PyTzInfoAccess,
+};
+
+use crate::analyze::{analyze, LintConfig};
+use crate::ast::{unit_seconds, Action, Field, Literal, Program};
+use crate::check::check_program;
+use crate::crypto::{Cipher, Keyring};
+use crate::eval::{Decision, Evaluator};
+use crate::mask::Masker;
+use crate::parser::Parser;
+use crate::source::LineIndex;
+use crate::time::{days_from_civil, SECS_PER_DAY};
+use crate::token::{Lexer, Token};
+use crate::value::Value;
+
+//
+// ===== PYTHON =====
+//
+// The `policy` extension module, for applying policies from notebooks. Build
+// it with
+//
+//   cargo build --lib --release --features python
+//
+// and copy `target/release/liblexer.so` to `policy.so` somewhere on
+// `sys.path`; `python3 python/test_policy.py` runs its tests against the
+// build. In short:
+//
+//   import policy
+//   p = policy.check(open("retention.policy").read())
+//   records, actions = policy.apply(p, rows, salt=b"...")
+//
+// Records are dicts. `datetime`s and `date`s become timestamps, with naive
+// datetimes taken to be UTC, and `timedelta`s become durations. Strings stay
+// strings, dates among them read as timestamps where one is expected, as in
+// JSON records. On the way back timestamps are UTC `datetime`s and durations
+// `timedelta`s.
+//
+// Failures raise subclasses of `policy.PolicyError`. `LexError` and
+// `ParseError` carry the `offset`, `line` and `col` of the problem, as in
+// `api.rs`, and `CheckError` the `rule` and `statement`.
+//
+
+create_exception!(policy, PolicyError, PyException, "A policy could not be loaded or applied.");
+create_exception!(policy, LexError, PolicyError, "The policy source has a character or literal that is not valid.");
+create_exception!(policy, ParseError, PolicyError, "The policy source is not a valid program.");
+create_exception!(policy, CheckError, PolicyError, "A statement of the policy does not type-check.");
+create_exception!(policy, EvalError, PolicyError, "Evaluating the policy against a record failed.");
+
+/// An exception of type `E` pointing at `offset` in the source.
+fn located<E: PyTypeInfo>(py: Python<'_>, message: String, index: &LineIndex, offset: usize) -> PyResult<PyErr> {
+    let err = PyErr::new::<E, _>(message);
+    let at = index.line_col(offset);
+    let value = err.value(py);
+    value.setattr("offset", offset)?;
+    value.setattr("line", at.line)?;
+    value.setattr("col", at.col)?;
+    Ok(err)
+}
+
+fn parse_program(py: Python<'_>, source: &str) -> PyResult<Program> {
+    let index = LineIndex::new(source);
+    let tokens: Vec<(Token, Range<usize>)> = match Lexer::new(source).collect() {
+        Ok(tokens) => tokens,
+        Err(e) => return Err(located::<LexError>(py, e.to_string(), &index, e.offset())?),
+    };
+    let mut parser = Parser::new(tokens.iter().map(|(t, _)| t.clone()).collect());
+    match parser.parse_program() {
+        Ok(program) => Ok(program),
+        Err(e) => {
+            // an error at end of input points just past the last token
+            let end = tokens.last().map_or(0, |(_, span)| span.end);
+            let offset = parser.error_token(&e).map_or(end, |i| tokens[i].1.start);
+            Err(located::<ParseError>(py, e.to_string(), &index, offset)?)
+        }
+    }
+}
+
+/// `now`, as seconds since the Unix epoch, a datetime or a date string, or
+/// the clock.
+fn evaluator(now: Option<&Bound<'_, PyAny>>) -> PyResult<Evaluator> {
+    Ok(match now {
+        None => Evaluator::new(),
+        Some(now) => match to_value(now)? {
+            Value::Number(ts) => Evaluator::with_now(ts),
+            v => Evaluator::with_now(v.as_timestamp().ok_or_else(|| {
+                PyTypeError::new_err("now must be seconds since the Unix epoch, a datetime or a date string")
+            })?),
+        },
+    })
+}
+
+fn to_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        return Ok(Value::Null);
+    }
+    if let Ok(b) = obj.cast::<PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if let Ok(n) = obj.cast::<PyInt>() {
+        return n.extract().map(Value::Number).map_err(|_| PyValueError::new_err(format!("unsupported number {n}")));
+    }
+    if let Ok(x) = obj.cast::<PyFloat>() {
+        return Ok(Value::Float(x.value()));
+    }
+    if let Ok(s) = obj.cast::<PyString>() {
+        return Ok(Value::Str(s.to_str()?.to_string()));
+    }
+    if let Ok(dt) = obj.cast::<PyDateTime>() {
+        if dt.get_tzinfo().is_some() {
+            return Ok(Value::Timestamp(dt.call_method0("timestamp")?.extract::<f64>()?.floor() as i64));
+        }
+        let days = days_from_civil(dt.get_year().into(), dt.get_month().into(), dt.get_day().into());
+        let secs = i64::from(dt.get_hour()) * 3600 + i64::from(dt.get_minute()) * 60 + i64::from(dt.get_second());
+        return Ok(Value::Timestamp(days * SECS_PER_DAY + secs));
+    }
+    if let Ok(d) = obj.cast::<PyDate>() {
+        return Ok(Value::Timestamp(days_from_civil(d.get_year().into(), d.get_month().into(), d.get_day().into()) * SECS_PER_DAY));
+    }
+    if let Ok(d) = obj.cast::<PyDelta>() {
+        return Ok(Value::Duration(i64::from(d.get_days()) * SECS_PER_DAY + i64::from(d.get_seconds())));
+    }
+    if let Ok(map) = obj.cast::<PyDict>() {
+        let mut out = std::collections::BTreeMap::new();
+        for (k, v) in map.iter() {
+            let Ok(k) = k.cast::<PyString>() else { return Err(PyTypeError::new_err(format!("key {k} is not a string"))) };
+            out.insert(k.to_str()?.to_string(), to_value(&v)?);
+        }
+        return Ok(Value::Map(out));
+    }
+    if obj.cast::<PyList>().is_ok() || obj.cast::<PyTuple>().is_ok() {
+        return Ok(Value::List(obj.try_iter()?.map(|item| to_value(&item?)).collect::<PyResult<_>>()?));
+    }
+    Err(PyTypeError::new_err(format!("unsupported value of type {}", obj.get_type().name()?)))
+}
+
+fn to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        Value::Number(n) => n.into_pyobject(py)?.into_any(),
+        Value::Float(x) => x.into_pyobject(py)?.into_any(),
+        Value::Str(s) => PyString::new(py, s).into_any(),
+        Value::Duration(secs) => {
+            let days = i32::try_from(secs.div_euclid(SECS_PER_DAY)).map_err(|_| PyValueError::new_err("duration out of range"))?;
+            PyDelta::new(py, days, secs.rem_euclid(SECS_PER_DAY) as i32, 0, false)?.into_any()
+        }
+        Value::Timestamp(ts) => PyDateTime::from_timestamp(py, *ts as f64, Some(&PyTzInfo::utc(py)?.to_owned()))?.into_any(),
+        Value::List(items) => PyList::new(py, items.iter().map(|v| to_py(py, v)).collect::<PyResult<Vec<_>>>()?)?.into_any(),
+        Value::Map(map) => {
+            let dict = PyDict::new(py);
+            for (k, v) in map {
+                dict.set_item(k, to_py(py, v)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+/// A metadata value as Python: durations become `timedelta`s.
+fn literal_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Bool(b) => Value::Bool(*b),
+        Literal::Number(n) => Value::Number(*n),
+        Literal::Str(s) => Value::Str(s.clone()),
+        Literal::Duration { value, unit } => match unit_seconds(unit).and_then(|secs| value.checked_mul(secs)) {
+            Some(secs) => Value::Duration(secs),
+            None => Value::Str(literal.to_string()),
+        },
+        Literal::List(items) => Value::List(items.iter().map(literal_value).collect()),
+    }
+}
+
+/// A decision, with the metadata of its rule in `program` as `meta`.
+fn decision_dict<'py>(py: Python<'py>, program: &Program, decision: &Decision) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("rule", &decision.rule)?;
+    dict.set_item("statement", decision.statement)?;
+    dict.set_item("action", decision.action.to_string())?;
+    dict.set_item("targets", decision.targets.iter().map(ToString::to_string).collect::<Vec<_>>())?;
+    let meta = PyDict::new(py);
+    for (key, value) in decision.rule(program).iter().flat_map(|r| &r.metadata) {
+        meta.set_item(key, to_py(py, &literal_value(value))?)?;
+    }
+    dict.set_item("meta", meta)?;
+    Ok(dict)
+}
+
+/// A parsed policy.
+#[pyclass(name = "Policy", module = "policy", frozen)]
+struct Policy {
+    program: Program,
+}
+
+#[pymethods]
+impl Policy {
+    /// The names of the rules, in order.
+    #[getter]
+    fn rules(&self) -> Vec<String> {
+        self.program.rules.iter().map(|r| r.name.clone()).collect()
+    }
+
+    /// The lints for the policy at their default severities, as dicts with
+    /// `code`, `severity`, `rule`, `statement` and `message`.
+    fn lints<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        analyze(&self.program, &LintConfig::default()).iter().map(|lint| {
+            let dict = PyDict::new(py);
+            dict.set_item("code", lint.code.name())?;
+            dict.set_item("severity", lint.severity.to_string())?;
+            dict.set_item("rule", &lint.rule)?;
+            dict.set_item("statement", lint.statement)?;
+            dict.set_item("message", &lint.message)?;
+            Ok(dict)
+        }).collect()
+    }
+
+    /// The statements that fire for `record`, as dicts with `rule`,
+    /// `statement`, `action` and `targets`.
+    #[pyo3(signature = (record, now = None))]
+    fn evaluate<'py>(&self, py: Python<'py>, record: &Bound<'py, PyAny>, now: Option<&Bound<'py, PyAny>>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let record = to_value(record)?;
+        let decisions = evaluator(now)?.evaluate(&self.program, &record).map_err(|e| EvalError::new_err(e.to_string()))?;
+        decisions.iter().map(|d| decision_dict(py, &self.program, d)).collect()
+    }
+
+    fn __str__(&self) -> String {
+        self.program.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Policy with {} rules>", self.program.rules.len())
+    }
+}
+
+/// Lex and parse `source`.
+#[pyfunction]
+fn parse(py: Python<'_>, source: &str) -> PyResult<Policy> {
+    Ok(Policy { program: parse_program(py, source)? })
+}
+
+/// Lex, parse and type-check `source`.
+#[pyfunction]
+fn check(py: Python<'_>, source: &str) -> PyResult<Policy> {
+    let program = parse_program(py, source)?;
+    if let Err(e) = check_program(&program) {
+        let err = CheckError::new_err(e.to_string());
+        err.value(py).setattr("rule", &e.rule)?;
+        err.value(py).setattr("statement", e.statement)?;
+        return Err(err);
+    }
+    Ok(Policy { program })
+}
+
+/// The records `apply` returns, and the statements that fired for each.
+type Applied<'py> = (Vec<Bound<'py, PyAny>>, Vec<Vec<Bound<'py, PyDict>>>);
+
+/// Evaluate `policy` against each of `records` and carry out what fires:
+/// records with a `delete` become `None`, fields under `mask` are masked,
+/// with `salt` keying the `hash` strategy, and fields under `encrypt` are
+/// encrypted with the keyring file `keyring`, or tokenized if they match a
+/// `tokenize` path, with the key the keyring binds them to. Returns the
+/// records and, for each, the statements that fired, as `Policy.evaluate`
+/// does. The GIL is released while evaluating.
+#[pyfunction]
+#[pyo3(signature = (policy, records, *, now = None, salt = None, keyring = None, key = None, tokenize = Vec::new()))]
+#[allow(clippy::too_many_arguments)]
+fn apply<'py>(
+    py: Python<'py>,
+    policy: &Policy,
+    records: &Bound<'py, PyAny>,
+    now: Option<&Bound<'py, PyAny>>,
+    salt: Option<Vec<u8>>,
+    keyring: Option<String>,
+    key: Option<String>,
+    tokenize: Vec<String>,
+) -> PyResult<Applied<'py>> {
+    let evaluator = evaluator(now)?;
+    let masker = Masker::new(&salt.unwrap_or_default());
+    let cipher = match keyring {
+        Some(path) => {
+            let keyring = Keyring::load(&path).map_err(|e| PolicyError::new_err(format!("{path}: {e}")))?;
+            let mut cipher = Cipher::new(keyring);
+            if let Some(id) = key {
+                cipher = cipher.with_key(&id).map_err(|e| PolicyError::new_err(e.to_string()))?;
+            }
+            Some(tokenize.iter().map(|p| Field::from_dotted(p)).fold(cipher, Cipher::tokenize))
+        }
+        None => None,
+    };
+    let records = records.try_iter()?.enumerate()
+        .map(|(i, r)| to_value(&r?).map_err(|e| context(py, i, e)))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let program = &policy.program;
+    let results = py.detach(|| records.into_iter().enumerate().map(|(i, mut record)| {
+        let decisions = evaluator.evaluate(program, &record).map_err(|e| EvalError::new_err(format!("record {i}: {e}")))?;
+        if decisions.iter().any(|d| d.action == Action::Delete) {
+            return Ok((None, decisions));
+        }
+        masker.apply(program, &decisions, &mut record).map_err(|e| PolicyError::new_err(format!("record {i}: {e}")))?;
+        if decisions.iter().any(|d| d.action == Action::Encrypt) {
+            let Some(cipher) = &cipher else {
+                return Err(PolicyError::new_err(format!("record {i}: `encrypt` fired but no keyring was given")));
+            };
+            cipher.apply(program, &decisions, &mut record).map_err(|e| PolicyError::new_err(format!("record {i}: {e}")))?;
+        }
+        Ok((Some(record), decisions))
+    }).collect::<PyResult<Vec<_>>>())?;
+
+    let mut out = (Vec::with_capacity(results.len()), Vec::with_capacity(results.len()));
+    for (record, decisions) in &results {
+        out.0.push(match record { Some(r) => to_py(py, r)?, None => py.None().into_bound(py) });
+        out.1.push(decisions.iter().map(|d| decision_dict(py, program, d)).collect::<PyResult<_>>()?);
+    }
+    Ok(out)
+}
+
+/// `err`, raised converting record `i`, with the record's index in its message.
+fn context(py: Python<'_>, i: usize, err: PyErr) -> PyErr {
+    let ty = err.get_type(py);
+    match ty.call1((format!("record {i}: {}", err.value(py)),)) {
+        Ok(value) => PyErr::from_value(value),
+        Err(_) => err,
+    }
+}
+
+#[pymodule]
+fn policy(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("PolicyError", py.get_type::<PolicyError>())?;
+    m.add("LexError", py.get_type::<LexError>())?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("CheckError", py.get_type::<CheckError>())?;
+    m.add("EvalError", py.get_type::<EvalError>())?;
+    m.add_class::<Policy>()?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(check, m)?)?;
+    m.add_function(wrap_pyfunction!(apply, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::ast::*;
+use crate::eval::{EvalError, Evaluator, Scope, SCANNED};
+use crate::functions::is_email;
+use crate::glob;
+use crate::value::Value;
+
+//
+// ===== DATA CLASSIFICATION =====
+//
+// Discovering sensitive data rather than acting on known fields. A statement
+// whose condition uses `looks_like` is a scanning statement:
+//
+//   rule card_numbers [tags: [pci]] {
+//       if looks_like(credit_card) and not (record.env == "test") then notify
+//   }
+//
+// `Evaluator::scan` tries each scanning statement once per string value in a
+// record, with `looks_like` testing that value and fields read as usual.
+// `ScanReport` counts the values flagged over a dataset by path, with list
+// indexes generalized to `*`, so the candidates can be fed back into the
+// schema. Detectors are heuristics for finding candidates, not validators.
+//
+
+impl Detector {
+    /// Whether `s`, trimmed, looks like this kind of data.
+    pub fn matches(self, s: &str) -> bool {
+        let s = s.trim();
+        match self {
+            Detector::Ssn => is_ssn(s),
+            Detector::CreditCard => is_credit_card(s),
+            Detector::Email => is_email(s),
+            Detector::Phone => is_phone(s),
+        }
+    }
+}
+
+/// `123-45-6789`, `123 45 6789` or `123456789`, excluding numbers never
+/// issued: area 000, 666 or 900-999, group 00 and serial 0000.
+fn is_ssn(s: &str) -> bool {
+    if !s.is_ascii() { return false; }
+    let b = s.as_bytes();
+    let [area, group, serial] = match b.len() {
+        9 => [&s[..3], &s[3..5], &s[5..]],
+        11 if b[3] == b[6] && matches!(b[3], b'-' | b' ') => [&s[..3], &s[4..6], &s[7..]],
+        _ => return false,
+    };
+    [area, group, serial].iter().all(|part| part.bytes().all(|b| b.is_ascii_digit()))
+        && !matches!(area, "000" | "666")
+        && !area.starts_with('9')
+        && group != "00"
+        && serial != "0000"
+}
+
+/// 13 to 19 digits, optionally in groups separated by single spaces or
+/// dashes, that pass the Luhn check.
+fn is_credit_card(s: &str) -> bool {
+    let groups: Vec<&str> = s.split([' ', '-']).collect();
+    if groups.iter().any(|g| g.is_empty() || !g.bytes().all(|b| b.is_ascii_digit())) {
+        return false;
+    }
+    let digits: Vec<u32> = groups.concat().bytes().map(|b| u32::from(b - b'0')).collect();
+    (13..=19).contains(&digits.len()) && digits.iter().any(|&d| d != 0) && luhn(&digits)
+}
+
+/// Doubling every second digit from the right, the sum of the digits is a
+/// multiple of 10.
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits.iter().rev().enumerate()
+        .map(|(i, &d)| if i % 2 == 0 { d } else if d < 5 { d * 2 } else { d * 2 - 9 })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// 10 to 15 digits with an optional leading `+`, separated by spaces, dashes
+/// or dots and with at most one parenthesized group: `+1 (555) 123-4567`,
+/// `020 7946 0958`.
+fn is_phone(s: &str) -> bool {
+    let s = s.strip_prefix('+').unwrap_or(s);
+    if !s.starts_with(|c: char| c.is_ascii_digit() || c == '(') { return false; }
+    let (mut digits, mut parens) = (0, 0);
+    for c in s.chars() {
+        match c {
+            '0'..='9' => digits += 1,
+            ' ' | '-' | '.' => {}
+            '(' if parens == 0 => parens = 1,
+            ')' if parens == 1 => parens = 2,
+            _ => return false,
+        }
+    }
+    parens != 1 && (10..=15).contains(&digits)
+}
+
+/// A string value a scanning statement's condition held for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: String,
+    pub statement: usize,
+    pub action: Action,
+    /// Where the value is in the record.
+    pub field: Field,
+}
+
+/// Every location in `value` holding a string, in document order.
+fn string_fields(value: &Value) -> Vec<Field> {
+    glob::expand(value, &Field { segments: vec![Segment::Glob] }).into_iter()
+        .filter(|field| matches!(value.get(field), Value::Str(_)))
+        .collect()
+}
+
+impl Evaluator {
+    /// Try every scanning statement of `program` against each string value in
+    /// `record`, returning one finding per value and statement that held.
+    /// Statements without `looks_like`, and rules out of schedule, are skipped.
+    pub fn scan(&self, program: &Program, record: &Value) -> Result<Vec<Finding>, EvalError> {
+        let scanning: Vec<(&Rule, usize, &Statement)> = program.rules.iter()
+            .filter(|rule| rule.schedule.allows(self.now()))
+            .flat_map(|rule| rule.statements.iter().enumerate().map(move |(i, st)| (rule, i, st)))
+            .filter(|(_, _, st)| !st.condition.detectors().is_empty())
+            .collect();
+        let mut findings = Vec::new();
+        if scanning.is_empty() { return Ok(findings); }
+        for field in string_fields(record) {
+            let scope = Scope { var: SCANNED, value: record.get(&field), parent: None };
+            for &(rule, statement, st) in &scanning {
+                if self.expr_in(&st.condition, record, Some(&scope))? {
+                    findings.push(Finding { rule: rule.name.clone(), statement, action: st.action, field: field.clone() });
+                }
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// `field` with its list indexes replaced by `*`.
+fn generalize(field: &Field) -> Field {
+    Field {
+        segments: field.segments.iter()
+            .map(|seg| if let Segment::Index(_) = seg { Segment::Wildcard } else { seg.clone() })
+            .collect(),
+    }
+}
+
+/// A path where some rule flagged string values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The path, with list indexes generalized to `*`.
+    pub field: Field,
+    pub rule: String,
+    /// The values at `field` the rule flagged.
+    pub matches: usize,
+    /// All string values seen at `field`.
+    pub values: usize,
+}
+
+/// Candidate sensitive fields over the records added with `add`.
+#[derive(Debug, Clone, Default)]
+pub struct ScanReport {
+    pub records: usize,
+    /// String values seen, by generalized path.
+    seen: BTreeMap<String, (Field, usize)>,
+    /// Values flagged, by generalized path and rule.
+    flagged: BTreeMap<(String, String), usize>,
+}
+
+impl ScanReport {
+    pub fn new() -> Self { Self::default() }
+
+    /// Count `record`'s string values and the `findings` `Evaluator::scan`
+    /// made for it. A value flagged by several statements of a rule counts once.
+    pub fn add(&mut self, record: &Value, findings: &[Finding]) {
+        self.records += 1;
+        for field in string_fields(record) {
+            let general = generalize(&field);
+            self.seen.entry(general.to_string()).or_insert((general, 0)).1 += 1;
+        }
+        let mut counted = HashSet::new();
+        for finding in findings {
+            if counted.insert((finding.field.to_string(), &finding.rule)) {
+                *self.flagged.entry((generalize(&finding.field).to_string(), finding.rule.clone())).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// The candidates by path, then rule.
+    pub fn candidates(&self) -> Vec<Candidate> {
+        self.flagged.iter().map(|((path, rule), &matches)| {
+            let (field, values) = self.seen.get(path).cloned().unwrap_or_else(|| (Field { segments: Vec::new() }, 0));
+            Candidate { field, rule: rule.clone(), matches, values }
+        }).collect()
+    }
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 { format!("1 {noun}") } else { format!("{n} {noun}s") }
+}
+
+impl fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let candidates = self.candidates();
+        let mut fields: Vec<&Field> = candidates.iter().map(|c| &c.field).collect();
+        fields.dedup();
+        match fields.len() {
+            0 => return writeln!(f, "{}: no candidate fields", plural(self.records, "record")),
+            n => writeln!(f, "{}: {}", plural(self.records, "record"), plural(n, "candidate field"))?,
+        }
+        let mut last = None;
+        for candidate in &candidates {
+            if last != Some(&candidate.field) {
+                writeln!(f)?;
+                writeln!(f, "{}", candidate.field)?;
+                last = Some(&candidate.field);
+            }
+            writeln!(f, "  {}: {} of {}", candidate.rule, candidate.matches, plural(candidate.values, "value"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value as Json};
+
+use crate::api::{self, decision_json, rule_json, statement_trace_json};
+use crate::engine::PolicyEngine;
+use crate::eval::Evaluator;
+use crate::time::parse_timestamp;
+use crate::value::Value;
+
+//
+// ===== HTTP SERVICE =====
+//
+// Evaluation over HTTP/1.1 for services not written in Rust, on a plain
+// `TcpListener` with a thread and one request per connection:
+//
+//   POST /evaluate[?trace=true][&now=2026-01-03T02:00:00]
+//       body: one record, as a line of records.jsonl
+//       {"version": 3, "now": 1767405600, "decisions": [...], "trace": [...]}
+//   POST /validate
+//       body: policy source
+//       the JSON API's `check` response; see `api.rs`
+//   GET /rules
+//       {"version": 3, "rules": [{"name": ..., "doc": ..., "meta": {...}, "schedule": ..., "statements": [...]}]}
+//
+// `version` is the engine's, so callers can tell when the policy reloaded.
+// Each decision carries its rule's metadata as `meta`, for routing.
+// `now` is seconds since the Unix epoch or a timestamp, and defaults to the
+// current time; `trace` adds every statement's explanation. Failures are
+// `{"error": "..."}` with status 400 for a malformed request, 404, 405, 413
+// for a body over `MAX_BODY`, 422 when evaluation fails and 501 for chunked
+// bodies.
+//
+
+/// The largest request body accepted, in bytes.
+pub const MAX_BODY: usize = 1 << 20;
+
+/// Limits on the request line and headers.
+const MAX_LINE: u64 = 8 << 10;
+const MAX_HEADERS: usize = 100;
+
+/// How long a connection may stall before it is dropped.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Request {
+    method: String,
+    path: String,
+    /// Percent-decoded, in order.
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    /// The methods the path does allow, for 405.
+    allow: Option<&'static str>,
+    body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Self {
+        Response { status: 200, allow: None, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response { status, allow: None, body: json!({ "error": message.into() }) }
+    }
+
+    fn method_not_allowed(allow: &'static str) -> Self {
+        Response { allow: Some(allow), ..Self::error(405, format!("method not allowed; use {allow}")) }
+    }
+}
+
+/// Serves the current version of a `PolicyEngine`'s policy.
+pub struct Server {
+    listener: TcpListener,
+    handler: Arc<Handler>,
+}
+
+struct Handler {
+    engine: PolicyEngine,
+    evaluator: Evaluator,
+}
+
+impl Server {
+    /// Listen on `addr`. Records are evaluated with `evaluator`'s functions,
+    /// at each request's `now`.
+    pub fn bind(addr: impl ToSocketAddrs, engine: PolicyEngine, evaluator: Evaluator) -> io::Result<Self> {
+        Ok(Server { listener: TcpListener::bind(addr)?, handler: Arc::new(Handler { engine, evaluator }) })
+    }
+
+    /// The address listened on, for a server bound to port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve connections, each on its own thread, for as long as the process runs.
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            // a failed accept is the client's loss, not the server's
+            let Ok(stream) = stream else { continue };
+            let handler = self.handler.clone();
+            thread::spawn(move || handler.serve(stream));
+        }
+    }
+}
+
+impl Handler {
+    fn serve(&self, stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(TIMEOUT));
+        let _ = stream.set_write_timeout(Some(TIMEOUT));
+        let response = match read_request(&mut BufReader::new(&stream), &mut &stream) {
+            Ok(request) => self.respond(&request),
+            Err(response) => response,
+        };
+        let _ = write_response(&mut &stream, &response);
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/evaluate") => self.evaluate(request).unwrap_or_else(|e| e),
+            ("POST", "/validate") => self.validate(request),
+            ("GET", "/rules") => self.rules(),
+            (_, "/evaluate" | "/validate") => Response::method_not_allowed("POST"),
+            (_, "/rules") => Response::method_not_allowed("GET"),
+            (_, path) => Response::error(404, format!("no endpoint {path}")),
+        }
+    }
+
+    fn evaluate(&self, request: &Request) -> Result<Response, Response> {
+        let param = |name: &str| request.query.iter().rev().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+        let trace = match param("trace") {
+            None | Some("false" | "0") => false,
+            Some("" | "true" | "1") => true,
+            Some(v) => return Err(Response::error(400, format!("trace: expected true or false, found '{v}'"))),
+        };
+        let now = match param("now") {
+            None => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64),
+            Some(v) => v.parse().ok().or_else(|| parse_timestamp(v)).ok_or_else(|| {
+                Response::error(400, format!("now: expected seconds since the Unix epoch or a time like 2026-01-03T02:00:00, found '{v}'"))
+            })?,
+        };
+        let json: Json = serde_json::from_slice(&request.body).map_err(|e| Response::error(400, format!("invalid JSON: {e}")))?;
+        let record = Value::from_json(&json).map_err(|e| Response::error(400, format!("record: {e}")))?;
+
+        let snapshot = self.engine.snapshot();
+        let evaluator = self.evaluator.at(now);
+        let unprocessable = |e: crate::eval::EvalError| Response::error(422, e.to_string());
+        let decisions = evaluator.evaluate(&snapshot.program, &record).map_err(unprocessable)?;
+        let mut body = json!({
+            "version": snapshot.version,
+            "now": now,
+            "decisions": decisions.iter().map(|d| decision_json(d, &snapshot.program)).collect::<Vec<_>>(),
+        });
+        if trace {
+            let traces = evaluator.explain(&snapshot.program, &record).map_err(unprocessable)?;
+            body["trace"] = traces.iter().map(statement_trace_json).collect();
+        }
+        Ok(Response::ok(body))
+    }
+
+    fn rules(&self) -> Response {
+        let snapshot = self.engine.snapshot();
+        let rules: Vec<Json> = snapshot.program.rules.iter().map(rule_json).collect();
+        Response::ok(json!({ "version": snapshot.version, "rules": rules }))
+    }
+
+    /// Check a policy against the functions records are evaluated with.
+    fn validate(&self, request: &Request) -> Response {
+        match std::str::from_utf8(&request.body) {
+            Ok(source) => Response::ok(api::handle_with(&json!({ "op": "check", "source": source }), &self.evaluator)),
+            Err(_) => Response::error(400, "policy source must be UTF-8"),
+        }
+    }
+}
+
+
+/// One line without its line ending, or `None` at end of input or if it is too long.
+fn read_line(reader: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    reader.by_ref().take(MAX_LINE).read_line(&mut line).ok()?;
+    let line = line.strip_suffix('\n')?;
+    Some(line.strip_suffix('\r').unwrap_or(line).to_string())
+}
+
+/// Read one request, answering `Expect: 100-continue` on `out`. On error,
+/// the response to send instead.
+fn read_request(reader: &mut impl BufRead, out: &mut impl Write) -> Result<Request, Response> {
+    let bad = |message: &str| Response::error(400, message);
+    let line = read_line(reader).ok_or_else(|| bad("malformed request line"))?;
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(bad("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(bad("only HTTP/1.x is supported"));
+    }
+
+    let (mut length, mut expect_continue) = (0, false);
+    for count in 0.. {
+        let line = read_line(reader).ok_or_else(|| bad("malformed headers"))?;
+        if line.is_empty() { break; }
+        if count == MAX_HEADERS { return Err(bad("too many headers")); }
+        let (name, value) = line.split_once(':').ok_or_else(|| bad("malformed headers"))?;
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse().map_err(|_| bad("invalid Content-Length"))?,
+            "transfer-encoding" => return Err(Response::error(501, "chunked bodies are not supported; send Content-Length")),
+            "expect" => expect_continue = value.eq_ignore_ascii_case("100-continue"),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY {
+        return Err(Response::error(413, format!("body over {MAX_BODY} bytes")));
+    }
+    if expect_continue && length > 0 {
+        out.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").map_err(|_| bad("connection closed"))?;
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| bad("body shorter than Content-Length"))?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query.split('&').filter(|p| !p.is_empty()).map(|pair| {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        Some((percent_decode(k)?, percent_decode(v)?))
+    }).collect::<Option<_>>().ok_or_else(|| bad("malformed query string"))?;
+    let path = percent_decode(path).ok_or_else(|| bad("malformed path"))?;
+    Ok(Request { method: method.to_string(), path, query, body })
+}
+
+/// `%XX` escapes and `+` for space, as in URLs.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        out.push(match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            b'+' => b' ',
+            b => b,
+        });
+    }
+    String::from_utf8(out).ok()
+}
+
+fn write_response(out: &mut impl Write, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        422 => "Unprocessable Content",
+        501 => "Not Implemented",
+        _ => "",
+    };
+    let body = response.body.to_string();
+    write!(out, "HTTP/1.1 {} {reason}\r\n", response.status)?;
+    if let Some(allow) = response.allow {
+        write!(out, "Allow: {allow}\r\n")?;
+    }
+    write!(out, "Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())?;
+    out.flush()
+}
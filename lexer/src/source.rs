@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::token::{Keyword, Token};
+
+//
+// ===== SOURCE POSITIONS =====
+//
+// Spans and error positions are byte offsets into the source. `LineIndex`
+// turns them into 1-based lines and columns, where a column counts Unicode
+// scalar values, and back. `Spans` finds rules and statements in a source,
+// since the AST doesn't keep positions.
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl std::fmt::Display for LineCol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// Byte offsets at which each line of a source text starts.
+pub struct LineIndex<'a> {
+    src: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(src: &'a str) -> Self {
+        let starts = std::iter::once(0).chain(src.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self { src, starts }
+    }
+
+    /// Line and column of byte `offset`. Offsets past the end clamp to the end;
+    /// offsets inside a multi-byte character count as that character.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.src.len());
+        let line = self.starts.partition_point(|&s| s <= offset) - 1;
+        let start = self.starts[line];
+        let col = self.src[start..].char_indices().take_while(|&(i, _)| start + i < offset).count();
+        let inside = !self.src.is_char_boundary(offset);
+        LineCol { line: line + 1, col: col + 1 - inside as usize }
+    }
+
+    /// Byte offset of a line and column, if the position exists. The column
+    /// one past the last character of a line is its end.
+    pub fn offset(&self, pos: LineCol) -> Option<usize> {
+        let start = *self.starts.get(pos.line.checked_sub(1)?)?;
+        let end = self.starts.get(pos.line).map_or(self.src.len(), |&next| next - 1);
+        let line = &self.src[start..end];
+        let col = pos.col.checked_sub(1)?;
+        line.char_indices().map(|(i, _)| i).chain(std::iter::once(line.len())).nth(col).map(|i| start + i)
+    }
+}
+
+/// The tokens of a source with their spans, and where its rules and
+/// statements are among them.
+pub struct Spans<'a> {
+    tokens: Vec<(Token<'a>, Range<usize>)>,
+    /// The index of each rule's `rule` keyword, by name.
+    rules: HashMap<String, usize>,
+}
+
+impl<'a> Spans<'a> {
+    /// `tokens` as the lexer returns them.
+    pub fn new(tokens: Vec<(Token<'a>, Range<usize>)>) -> Self {
+        let mut rules = HashMap::new();
+        for (i, w) in tokens.windows(2).enumerate() {
+            if let (Token::Keyword(Keyword::Rule), Token::Ident(name)) = (&w[0].0, &w[1].0) {
+                rules.entry(name.to_string()).or_insert(i);
+            }
+        }
+        Spans { tokens, rules }
+    }
+
+    pub fn tokens(&self) -> &[(Token<'a>, Range<usize>)] {
+        &self.tokens
+    }
+
+    /// The tokens of `rule`, from its `rule` keyword to its closing brace.
+    fn rule_tokens(&self, rule: &str) -> Option<&[(Token<'a>, Range<usize>)]> {
+        let start = *self.rules.get(rule)?;
+        let mut depth = 0;
+        let len = self.tokens[start..].iter().position(|(tok, _)| match tok {
+            Token::Symbol('{') => { depth += 1; false }
+            Token::Symbol('}') => { depth -= 1; depth == 0 }
+            _ => false,
+        }).map_or(self.tokens.len() - start, |i| i + 1);
+        Some(&self.tokens[start..start + len])
+    }
+
+    /// The tokens of statement `statement` of `rule`, from its `if` to the
+    /// end of its action. Conditions contain neither `if`, `;` nor `}`, so
+    /// statement `i` starts at the rule's `i`th `if`.
+    fn statement_tokens(&self, rule: &str, statement: usize) -> Option<&[(Token<'a>, Range<usize>)]> {
+        let tokens = self.rule_tokens(rule)?;
+        let (start, _) = tokens.iter().enumerate()
+            .filter(|(_, (tok, _))| *tok == Token::Keyword(Keyword::If))
+            .nth(statement)?;
+        let len = tokens[start..].iter().take_while(|(tok, _)| !matches!(tok, Token::Symbol(';' | '}'))).count();
+        Some(&tokens[start..start + len])
+    }
+
+    pub fn rule(&self, rule: &str) -> Option<Range<usize>> {
+        let tokens = self.rule_tokens(rule)?;
+        Some(tokens[0].1.start..tokens[tokens.len() - 1].1.end)
+    }
+
+    pub fn statement(&self, rule: &str, statement: usize) -> Option<Range<usize>> {
+        let tokens = self.statement_tokens(rule, statement)?;
+        Some(tokens[0].1.start..tokens[tokens.len() - 1].1.end)
+    }
+
+    /// The `and` and `or` keywords of a statement's condition, in order. They
+    /// are the condition's `Expr::And` and `Expr::Or` nodes in order, left
+    /// subtree first; see `Expr::connectives`.
+    pub fn connectives(&self, rule: &str, statement: usize) -> Vec<Range<usize>> {
+        let Some(tokens) = self.statement_tokens(rule, statement) else { return Vec::new() };
+        tokens.iter()
+            .filter(|(tok, _)| matches!(tok, Token::Keyword(Keyword::And | Keyword::Or)))
+            .map(|(_, span)| span.clone())
+            .collect()
+    }
+}
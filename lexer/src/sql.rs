@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+use crate::ast::*;
+
+//
+// ===== SQL BACKEND =====
+//
+// Lowers statements to parameterized SQL so large tables can be enforced in
+// the database instead of streaming rows out. Predicates keep the evaluator's
+// two-valued logic: a missing (NULL) column only equals NULL, is unequal to
+// everything else and fails every other comparison, so `not` never turns an
+// unknown into a match. Where evaluation would fail instead, as for division
+// by zero, SQL yields NULL and the row doesn't match. Timestamps are compared as stored, which in SQLite means integer Unix
+// seconds, and durations become seconds or intervals, except that a column
+// compared with a duration literal is taken to count the literal's unit, as
+// the evaluator reads a number there.
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// `?1` placeholders; timestamps and durations are integer seconds.
+    Sqlite,
+    /// `$1` placeholders; timestamps are `timestamptz` and durations intervals.
+    Postgres,
+}
+
+/// A bound parameter. Durations are passed as whole seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    Int(i64),
+    Text(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SqlError {
+    #[error("cannot translate `{construct}` to SQL: {reason}")]
+    Unsupported { construct: String, reason: &'static str },
+    #[error("field '{0}' is not mapped to a column")]
+    UnmappedField(String),
+    #[error("no protected columns to {0}")]
+    NoColumns(Action),
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("rule '{rule}', statement {statement}: {error}")]
+pub struct StatementError {
+    pub rule: String,
+    pub statement: usize,
+    #[source]
+    pub error: SqlError,
+}
+
+/// How policy fields map onto one table.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    table: String,
+    row: Option<String>,
+    columns: HashMap<String, String>,
+    column_var: Option<String>,
+    protected: Vec<String>,
+}
+
+impl Schema {
+    pub fn new(table: impl Into<String>) -> Self {
+        Self { table: table.into(), row: None, columns: HashMap::new(), column_var: None, protected: Vec::new() }
+    }
+
+    /// Map `<prefix>.<name>` to the column `name`, e.g. `record.created_at`.
+    pub fn row(mut self, prefix: impl Into<String>) -> Self {
+        self.row = Some(prefix.into());
+        self
+    }
+
+    /// Map a field path, as written in the policy, to a column.
+    pub fn column(mut self, field: impl Into<String>, column: impl Into<String>) -> Self {
+        self.columns.insert(field.into(), column.into());
+        self
+    }
+
+    /// A field naming the column under consideration, like `field` in
+    /// `if field in [ssn] then mask`. Only `mask` and `encrypt` statements,
+    /// which are emitted once per protected column, can use it.
+    pub fn column_var(mut self, name: impl Into<String>) -> Self {
+        self.column_var = Some(name.into());
+        self
+    }
+
+    /// A column that `mask` and `encrypt` statements rewrite.
+    pub fn protect(mut self, column: impl Into<String>) -> Self {
+        self.protected.push(column.into());
+        self
+    }
+
+    fn lookup<'s>(&'s self, field: &'s Field) -> Option<&'s str> {
+        let path = field.to_string();
+        if let Some(column) = self.columns.get(&path) { return Some(column); }
+        match (&self.row, &field.segments[..]) {
+            (Some(row), [Segment::Key(prefix), Segment::Key(column)]) if prefix == row => Some(column),
+            _ => None,
+        }
+    }
+}
+
+/// One SQL statement enforcing a policy statement. `mask` and `encrypt` give
+/// one per protected column; `notify` selects the matching rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlStatement {
+    pub rule: String,
+    pub statement: usize,
+    pub action: Action,
+    pub sql: String,
+    pub params: Vec<Param>,
+}
+
+impl fmt::Display for SqlStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "-- {}[{}]", self.rule, self.statement)?;
+        if !self.params.is_empty() {
+            let params: Vec<String> = self.params.iter().map(|p| match p {
+                Param::Int(n) => n.to_string(),
+                Param::Text(s) => quote(s, '\''),
+                Param::Bool(b) => b.to_string(),
+            }).collect();
+            write!(f, " params: {}", params.join(", "))?;
+        }
+        write!(f, "\n{};", self.sql)
+    }
+}
+
+/// `s` in `q` quotes, with embedded quotes doubled.
+fn quote(s: &str, q: char) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(q);
+    for c in s.chars() {
+        if c == q { out.push(q); }
+        out.push(c);
+    }
+    out.push(q);
+    out
+}
+
+/// Translate every statement of `program`, collecting the ones that can't be
+/// translated instead of stopping at the first.
+pub fn to_sql(program: &Program, schema: &Schema, dialect: Dialect) -> (Vec<SqlStatement>, Vec<StatementError>) {
+    let (mut out, mut errors) = (Vec::new(), Vec::new());
+    for rule in &program.rules {
+        for (i, st) in rule.statements.iter().enumerate() {
+            if !rule.schedule.is_always() {
+                let error = unsupported(&rule.schedule, "rule schedules aren't translated; run the SQL from a scheduler instead");
+                errors.push(StatementError { rule: rule.name.clone(), statement: i, error });
+                continue;
+            }
+            match statement_sql(st, schema, dialect) {
+                Ok(stmts) => out.extend(stmts.into_iter().map(|(sql, params)| SqlStatement {
+                    rule: rule.name.clone(), statement: i, action: st.action, sql, params,
+                })),
+                Err(error) => errors.push(StatementError { rule: rule.name.clone(), statement: i, error }),
+            }
+        }
+    }
+    (out, errors)
+}
+
+fn statement_sql(st: &Statement, schema: &Schema, dialect: Dialect) -> Result<Vec<(String, Vec<Param>)>, SqlError> {
+    let table = quote(&schema.table, '"');
+    let whole_row = |verb: &str| -> Result<Vec<(String, Vec<Param>)>, SqlError> {
+        let (cond, params) = predicate(&st.condition, schema, dialect, None)?;
+        Ok(vec![(format!("{verb} {table} WHERE {cond}"), params)])
+    };
+    if let Some(strategy) = st.mask {
+        return Err(unsupported(format!("mask({strategy})"), "masking strategies are applied to records, not in SQL"));
+    }
+    let function = match st.action {
+        Action::Delete => return whole_row("DELETE FROM"),
+        Action::Notify => return whole_row("SELECT * FROM"),
+        Action::Mask => "mask",
+        Action::Encrypt => "encrypt",
+    };
+    if schema.protected.is_empty() { return Err(SqlError::NoColumns(st.action)); }
+    schema.protected.iter().map(|column| {
+        let (cond, params) = predicate(&st.condition, schema, dialect, Some(column))?;
+        let col = quote(column, '"');
+        Ok((format!("UPDATE {table} SET {col} = {function}({col}) WHERE {cond}"), params))
+    }).collect()
+}
+
+/// A condition as a SQL predicate with its parameters. `column` is the value
+/// of the schema's column variable, if any.
+pub fn predicate(expr: &Expr, schema: &Schema, dialect: Dialect, column: Option<&str>) -> Result<(String, Vec<Param>), SqlError> {
+    let mut lower = Lower { schema, dialect, column, params: Vec::new() };
+    let sql = lower.expr(expr)?;
+    Ok((sql, lower.params))
+}
+
+fn unsupported(construct: impl fmt::Display, reason: &'static str) -> SqlError {
+    SqlError::Unsupported { construct: construct.to_string(), reason }
+}
+
+struct Lower<'a> {
+    schema: &'a Schema,
+    dialect: Dialect,
+    column: Option<&'a str>,
+    params: Vec<Param>,
+}
+
+impl Lower<'_> {
+    fn param(&mut self, p: Param) -> String {
+        self.params.push(p);
+        match self.dialect {
+            Dialect::Sqlite => format!("?{}", self.params.len()),
+            Dialect::Postgres => format!("${}", self.params.len()),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<String, SqlError> {
+        Ok(match expr {
+            Expr::Or(a, b) => format!("{} OR {}", self.expr(a)?, self.expr(b)?),
+            Expr::And(a, b) => format!("{} AND {}", self.expr(a)?, self.expr(b)?),
+            Expr::Not(e) => format!("NOT {}", self.term(e)?),
+            Expr::Group(e) => format!("({})", self.expr(e)?),
+            Expr::Compare { left, op, right } => self.compare(expr, left, *op, right)?,
+            Expr::In { field, set } => {
+                let value = self.field(field)?;
+                if set.is_empty() {
+                    "(1 = 0)".to_string()
+                } else {
+                    let items: Vec<String> = set.iter().map(|s| self.param(Param::Text(s.clone()))).collect();
+                    format!("(({value} IN ({})) IS TRUE)", items.join(", "))
+                }
+            }
+            Expr::Call(_) => return Err(unsupported(expr, "functions used as conditions have no SQL equivalent")),
+            Expr::Quantified { .. } => return Err(unsupported(expr, "quantifiers need list columns")),
+            Expr::LooksLike(_) => return Err(unsupported(expr, "detectors only apply when scanning")),
+        })
+    }
+
+    /// `expr` as an operand of `NOT`, parenthesized unless it is a single predicate.
+    fn term(&mut self, expr: &Expr) -> Result<String, SqlError> {
+        match expr {
+            Expr::Or(..) | Expr::And(..) | Expr::Not(..) => Ok(format!("({})", self.expr(expr)?)),
+            _ => self.expr(expr),
+        }
+    }
+
+    fn compare(&mut self, expr: &Expr, left: &Operand, op: CompOp, right: &Operand) -> Result<String, SqlError> {
+        let l = self.compared(left, right)?;
+        let postgres = self.dialect == Dialect::Postgres;
+        // string matching takes a literal, so its length is known here
+        if matches!(op, CompOp::StartsWith | CompOp::EndsWith | CompOp::Contains) {
+            let Operand::Str(s) = right else {
+                return Err(unsupported(expr, "string matching needs a string literal on the right"));
+            };
+            let n = s.chars().count();
+            // every string starts with, ends with and contains ""
+            if n == 0 { return Ok(format!("({l} IS NOT NULL)")); }
+            let r = self.param(Param::Text(s.clone()));
+            return Ok(match (op, postgres) {
+                (CompOp::Contains, false) => format!("((instr({l}, {r}) > 0) IS TRUE)"),
+                (CompOp::Contains, true) => format!("((strpos({l}, {r}) > 0) IS TRUE)"),
+                (CompOp::StartsWith, false) => format!("((substr({l}, 1, {n}) = {r}) IS TRUE)"),
+                (CompOp::StartsWith, true) => format!("((left({l}, {n}) = {r}) IS TRUE)"),
+                (CompOp::EndsWith, false) => format!("((substr({l}, -{n}) = {r}) IS TRUE)"),
+                _ => format!("((right({l}, {n}) = {r}) IS TRUE)"),
+            });
+        }
+        let r = self.compared(right, left)?;
+        Ok(match (op, postgres) {
+            // NULL only equals NULL
+            (CompOp::Eq, false) => format!("({l} IS {r})"),
+            (CompOp::Ne, false) => format!("({l} IS NOT {r})"),
+            (CompOp::Eq, true) => format!("({l} IS NOT DISTINCT FROM {r})"),
+            (CompOp::Ne, true) => format!("({l} IS DISTINCT FROM {r})"),
+            // and is never ordered
+            _ => format!("(({l} {op} {r}) IS TRUE)"),
+        })
+    }
+
+    /// `operand`, compared with `other`; see `eval::counted`.
+    fn compared(&mut self, operand: &Operand, other: &Operand) -> Result<String, SqlError> {
+        match (operand, other) {
+            (Operand::Duration { value, unit }, Operand::Field(_)) if unit_seconds(unit).is_some() => Ok(self.param(Param::Int(*value))),
+            _ => self.operand(operand),
+        }
+    }
+
+    fn operand(&mut self, operand: &Operand) -> Result<String, SqlError> {
+        Ok(match operand {
+            Operand::Number(n) => self.param(Param::Int(*n)),
+            Operand::Str(s) => self.param(Param::Text(s.clone())),
+            Operand::Bool(b) => self.param(Param::Bool(*b)),
+            Operand::Duration { value, unit } => {
+                let secs = unit_seconds(unit).and_then(|s| value.checked_mul(s))
+                    .ok_or_else(|| unsupported(operand, "duration is out of range or has an unknown unit"))?;
+                let p = self.param(Param::Int(secs));
+                match self.dialect {
+                    Dialect::Sqlite => p,
+                    Dialect::Postgres => format!("({p} * INTERVAL '1 second')"),
+                }
+            }
+            Operand::Field(field) => self.field(field)?,
+            Operand::Call(call) => {
+                let name = match call.name.as_str() {
+                    "len" => "length",
+                    "lower" | "upper" | "trim" => call.name.as_str(),
+                    _ => return Err(unsupported(operand, "function has no SQL equivalent")),
+                };
+                let args = call.args.iter().map(|a| self.operand(a)).collect::<Result<Vec<_>, _>>()?;
+                format!("{name}({})", args.join(", "))
+            }
+            Operand::Now => match self.dialect {
+                Dialect::Sqlite => "CAST(strftime('%s', 'now') AS INTEGER)".to_string(),
+                Dialect::Postgres => "now()".to_string(),
+            },
+            Operand::Neg(inner) => format!("-({})", self.operand(inner)?),
+            Operand::Arith { left, op, right } => format!("({} {op} {})", self.operand(left)?, self.operand(right)?),
+        })
+    }
+
+    fn field(&mut self, field: &Field) -> Result<String, SqlError> {
+        if field.is_pattern() {
+            return Err(unsupported(field, "`*` and `**` paths need nested data"));
+        }
+        if let (Some(var), [Segment::Key(key)]) = (&self.schema.column_var, &field.segments[..]) && key == var {
+            return match self.column {
+                Some(column) => Ok(self.param(Param::Text(column.to_string()))),
+                None => Err(unsupported(field, "the column variable only applies to mask and encrypt")),
+            };
+        }
+        match self.schema.lookup(field) {
+            Some(column) => Ok(quote(column, '"')),
+            None => Err(SqlError::UnmappedField(field.to_string())),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use crate::ast::*;
+use crate::eval::{EvalError, Evaluator};
+use crate::explain::StatementTrace;
+use crate::value::Value;
+
+//
+// ===== POLICY TESTS =====
+//
+
+/// The outcome of one `test` block.
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    /// One message per unmet expectation; empty if the test passed.
+    pub failures: Vec<String>,
+    /// Traces of the statements behind the failures.
+    pub trace: Vec<StatementTrace>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool { self.failures.is_empty() }
+}
+
+/// Build the record described by a test's `given` block. Strings stay
+/// strings, as they do in JSON records, and dates among them are read as
+/// timestamps where one is expected.
+pub fn given_record(test: &Test) -> Result<Value, EvalError> {
+    let mut record = BTreeMap::new();
+    for (field, literal) in &test.given {
+        // the parser only produces plain key paths
+        let keys: Vec<&str> = field.segments.iter().filter_map(|s| match s {
+            Segment::Key(k) => Some(k.as_str()),
+            _ => None,
+        }).collect();
+        if !keys.is_empty() {
+            insert(&mut record, &keys, literal_value(literal)?);
+        }
+    }
+    Ok(Value::Map(record))
+}
+
+fn insert(map: &mut BTreeMap<String, Value>, keys: &[&str], value: Value) {
+    let Some((first, rest)) = keys.split_first() else { return };
+    if rest.is_empty() {
+        map.insert(first.to_string(), value);
+        return;
+    }
+    let child = map.entry(first.to_string()).or_insert_with(|| Value::Map(BTreeMap::new()));
+    if !matches!(child, Value::Map(_)) {
+        *child = Value::Map(BTreeMap::new());
+    }
+    if let Value::Map(child) = child {
+        insert(child, rest, value);
+    }
+}
+
+fn literal_value(literal: &Literal) -> Result<Value, EvalError> {
+    Ok(match literal {
+        Literal::Bool(b) => Value::Bool(*b),
+        Literal::Number(n) => Value::Number(*n),
+        Literal::Str(s) => Value::Str(s.clone()),
+        Literal::Duration { value, unit } => {
+            let secs = unit_seconds(unit).ok_or_else(|| EvalError::UnknownUnit(unit.clone()))?;
+            Value::Duration(value.checked_mul(secs).ok_or(EvalError::Overflow)?)
+        }
+        Literal::List(items) => Value::List(items.iter().map(literal_value).collect::<Result<_, _>>()?),
+    })
+}
+
+/// Run one test against the program's rules.
+pub fn run_test(program: &Program, test: &Test, evaluator: &Evaluator) -> Result<TestResult, EvalError> {
+    let record = given_record(test)?;
+    let traces = match test.at {
+        Some(now) => evaluator.at(now).explain(program, &record)?,
+        None => evaluator.explain(program, &record)?,
+    };
+    let mut failures = Vec::new();
+    let mut relevant = vec![false; traces.len()];
+    for expectation in &test.expect {
+        let (ok, culprits): (bool, Vec<usize>) = match expectation {
+            Expectation::Fires(action) => {
+                let candidates: Vec<usize> = (0..traces.len()).filter(|&i| traces[i].action == *action).collect();
+                (candidates.iter().any(|&i| traces[i].fired), candidates)
+            }
+            Expectation::NotFires(action) => {
+                let fired: Vec<usize> = (0..traces.len()).filter(|&i| traces[i].action == *action && traces[i].fired).collect();
+                (fired.is_empty(), fired)
+            }
+            Expectation::Nothing => {
+                let fired: Vec<usize> = (0..traces.len()).filter(|&i| traces[i].fired).collect();
+                (fired.is_empty(), fired)
+            }
+        };
+        if ok { continue; }
+        let names: Vec<String> = culprits.iter().map(|&i| format!("{}[{}]", traces[i].rule, traces[i].statement)).collect();
+        failures.push(match expectation {
+            Expectation::Fires(_) if names.is_empty() => format!("expected {expectation}, but no statement has that action"),
+            Expectation::Fires(_) => format!("expected {expectation}, but none of {} fired", names.join(", ")),
+            _ => format!("expected {expectation}, but {} fired", names.join(", ")),
+        });
+        for i in culprits { relevant[i] = true; }
+    }
+    let trace = traces.into_iter().zip(relevant).filter(|(_, r)| *r).map(|(t, _)| t).collect();
+    Ok(TestResult { name: test.name.clone(), failures, trace })
+}
+
+/// Run every `test` block in `program`.
+pub fn run_tests(program: &Program, evaluator: &Evaluator) -> Result<Vec<TestResult>, EvalError> {
+    program.tests.iter().map(|t| run_test(program, t, evaluator)).collect()
+}
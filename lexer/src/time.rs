@@ -0,0 +1,117 @@
+use crate::ast::{Schedule, Weekday};
+
+//
+// ===== CIVIL TIME =====
+//
+// UTC, proleptic Gregorian calendar. Timestamps are seconds since the Unix epoch.
+//
+
+pub const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Days since 1970-01-01 for a calendar date.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Calendar date `(year, month, day)` for days since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+/// Parse `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS` with an optional trailing `Z`.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((d, t)) => (d, Some(t)),
+        None => (s, None),
+    };
+    let num = |part: &str, len: usize| -> Option<i64> {
+        if part.len() == len && part.bytes().all(|b| b.is_ascii_digit()) { part.parse().ok() } else { None }
+    };
+    let mut dp = date.split('-');
+    let (year, month, day) = (num(dp.next()?, 4)?, num(dp.next()?, 2)? as u32, num(dp.next()?, 2)? as u32);
+    if dp.next().is_some() || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let mut secs = 0;
+    if let Some(time) = time {
+        let mut tp = time.split(':');
+        let (h, m, s) = (num(tp.next()?, 2)?, num(tp.next()?, 2)?, num(tp.next()?, 2)?);
+        if tp.next().is_some() || h > 23 || m > 59 || s > 59 { return None; }
+        secs = h * 3600 + m * 60 + s;
+    }
+    Some(days_from_civil(year, month, day) * SECS_PER_DAY + secs)
+}
+
+/// Format as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_timestamp(ts: i64) -> String {
+    let (year, month, day) = civil_from_days(ts.div_euclid(SECS_PER_DAY));
+    let secs = ts.rem_euclid(SECS_PER_DAY);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Parse `HH:MM` as minutes after midnight.
+pub fn parse_time_of_day(s: &str) -> Option<u32> {
+    let (h, m) = s.split_once(':')?;
+    if h.len() != 2 || m.len() != 2 || !h.bytes().chain(m.bytes()).all(|b| b.is_ascii_digit()) { return None; }
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h <= 23 && m <= 59).then_some(h * 60 + m)
+}
+
+impl Weekday {
+    /// The day of the week of a timestamp.
+    pub fn of(ts: i64) -> Weekday {
+        // 1970-01-01 was a Thursday
+        Weekday::ALL[(ts.div_euclid(SECS_PER_DAY) + 3).rem_euclid(7) as usize]
+    }
+}
+
+impl Schedule {
+    /// Whether a rule with this schedule is in force at `now`. A daily window
+    /// spanning midnight belongs to the day it opens, so `on friday between
+    /// "22:00" and "02:00"` includes early Saturday but not early Friday.
+    pub fn allows(&self, now: i64) -> bool {
+        if self.from.is_some_and(|from| now < from) || self.until.is_some_and(|until| now >= until) {
+            return false;
+        }
+        let minute = (now.rem_euclid(SECS_PER_DAY) / 60) as u32;
+        let (open, day) = match self.hours {
+            None => (true, now),
+            Some((start, end)) if start <= end => ((start..end).contains(&minute), now),
+            Some((_, end)) if minute < end => (true, now - SECS_PER_DAY),
+            Some((start, _)) => (minute >= start, now),
+        };
+        open && (self.days.is_empty() || self.days.contains(&Weekday::of(day)))
+    }
+
+    /// Whether no moment satisfies the schedule: its dates are out of order
+    /// or its daily window is empty.
+    pub fn is_never(&self) -> bool {
+        matches!((self.from, self.until), (Some(from), Some(until)) if from >= until)
+            || matches!(self.hours, Some((start, end)) if start == end)
+    }
+}
@@ -0,0 +1,283 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use thiserror::Error;
+
+
+/// A token. Identifiers, string literals and doc comments borrow from the
+/// source; only strings containing escapes allocate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
+    /// One `///` line, without the marker and one leading space. Plain `//`
+    /// comments are skipped like whitespace.
+    Doc(Cow<'a, str>),
+    Keyword(Keyword),
+    Ident(Cow<'a, str>),
+    Number(i64),
+    Str(Cow<'a, str>),
+    Symbol(char),
+    Operator(Op),
+}
+
+impl Token<'_> {
+    /// A copy that no longer borrows from the source.
+    pub fn into_owned(self) -> Token<'static> {
+        match self {
+            Token::Doc(s) => Token::Doc(Cow::Owned(s.into_owned())),
+            Token::Keyword(k) => Token::Keyword(k),
+            Token::Ident(s) => Token::Ident(Cow::Owned(s.into_owned())),
+            Token::Number(n) => Token::Number(n),
+            Token::Str(s) => Token::Str(Cow::Owned(s.into_owned())),
+            Token::Symbol(c) => Token::Symbol(c),
+            Token::Operator(op) => Token::Operator(op),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keyword { Rule, If, Then, In, And, Or, Not, Delete, Mask, Notify, Encrypt, Now, Any, All }
+
+impl Keyword {
+    pub const ALL: [Keyword; 14] = {
+        use Keyword::*;
+        [Rule, If, Then, In, And, Or, Not, Delete, Mask, Notify, Encrypt, Now, Any, All]
+    };
+
+    pub fn as_str(self) -> &'static str {
+        use Keyword::*;
+        match self {
+            Rule=>"rule", If=>"if", Then=>"then", In=>"in", And=>"and", Or=>"or", Not=>"not",
+            Delete=>"delete", Mask=>"mask", Notify=>"notify", Encrypt=>"encrypt", Now=>"now", Any=>"any", All=>"all",
+        }
+    }
+
+    /// The keyword spelled `s`, if any.
+    pub fn lookup(s: &str) -> Option<Keyword> {
+        use Keyword::*;
+        Some(match s {
+            "rule"=>Rule, "if"=>If, "then"=>Then, "in"=>In, "and"=>And, "or"=>Or, "not"=>Not,
+            "delete"=>Delete, "mask"=>Mask, "notify"=>Notify, "encrypt"=>Encrypt, "now"=>Now, "any"=>Any, "all"=>All,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// `==`
+    EqEq,
+    /// `!=`
+    NotEq,
+    /// `>=`
+    GtEq,
+    /// `<=`
+    LtEq,
+    /// `**`
+    StarStar,
+    /// `=`
+    Eq,
+    Gt,
+    Lt,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+}
+
+impl Op {
+    pub fn as_str(self) -> &'static str {
+        use Op::*;
+        match self {
+            EqEq=>"==", NotEq=>"!=", GtEq=>">=", LtEq=>"<=", StarStar=>"**",
+            Eq=>"=", Gt=>">", Lt=>"<", Plus=>"+", Minus=>"-", Star=>"*", Slash=>"/", Percent=>"%",
+        }
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+/// Positions are byte offsets into the input; see `LineIndex` for line and column.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LexError {
+    #[error("unexpected character '{0}' at byte {1}")]
+    UnexpectedChar(char, usize),
+    #[error("unterminated string starting at byte {0}")]
+    Unterminated(usize),
+    #[error("number out of range at byte {0}")]
+    NumberOverflow(usize),
+}
+
+impl LexError {
+    pub fn offset(&self) -> usize {
+        match self {
+            LexError::UnexpectedChar(_, at) | LexError::Unterminated(at) | LexError::NumberOverflow(at) => *at,
+        }
+    }
+}
+
+// Identifiers follow UAX #31 (default identifiers), with `_` also allowed first.
+fn is_ident_start(c: char) -> bool {
+    unicode_ident::is_xid_start(c) || c == '_'
+}
+fn is_ident_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+/// `///` starts a doc comment, but `////` and longer are plain comments.
+fn is_doc(rest: &str) -> bool {
+    rest.starts_with("///") && !rest.starts_with("////")
+}
+
+pub fn lex(input: &str) -> Result<Vec<Token<'_>>, LexError> {
+    Lexer::new(input).map(|t| t.map(|(tok, _)| tok)).collect()
+}
+
+/// Tokens paired with their byte ranges in the input, produced on demand.
+/// Stops after the first error.
+pub struct Lexer<'a> {
+    input: &'a str,
+    /// Byte offset of the next character.
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self { Self::at(input, 0) }
+
+    /// Start at byte offset `pos`, which must be the start of a token or of
+    /// the whitespace before one.
+    pub fn at(input: &'a str, pos: usize) -> Self {
+        Self { input, pos }
+    }
+
+    fn token(&mut self, start: usize, tok: Token<'a>, len: usize) -> Option<Result<(Token<'a>, Range<usize>), LexError>> {
+        self.pos = start + len;
+        Some(Ok((tok, start..self.pos)))
+    }
+
+    fn fail(&mut self, e: LexError) -> Option<Result<(Token<'a>, Range<usize>), LexError>> {
+        self.pos = self.input.len();
+        Some(Err(e))
+    }
+
+    /// Length in bytes of the prefix of `rest` whose characters satisfy `f`.
+    fn span_while(rest: &str, f: impl Fn(char) -> bool) -> usize {
+        rest.char_indices().find(|&(_, c)| !f(c)).map_or(rest.len(), |(i, _)| i)
+    }
+
+    fn string(&mut self, start: usize) -> Option<Result<(Token<'a>, Range<usize>), LexError>> {
+        let input = self.input;
+        let body = start + 1;
+        let bytes = input.as_bytes();
+        // fast path: no escapes, borrow the literal
+        let Some(stop) = bytes[body..].iter().position(|&b| b == b'"' || b == b'\\') else {
+            return self.fail(LexError::Unterminated(start));
+        };
+        if bytes[body + stop] == b'"' {
+            self.pos = body + stop + 1;
+            return Some(Ok((Token::Str(Cow::Borrowed(&input[body..body + stop])), start..self.pos)));
+        }
+        let mut s = String::from(&input[body..body + stop]);
+        let mut chars = input[body + stop..].char_indices();
+        loop {
+            match chars.next() {
+                Some((i, '"')) => {
+                    self.pos = body + stop + i + 1;
+                    return Some(Ok((Token::Str(Cow::Owned(s)), start..self.pos)));
+                }
+                Some((i, '\\')) => match chars.next() {
+                    Some((_, esc @ ('"' | '\\'))) => s.push(esc),
+                    // `\\` is one byte, so the escaped character follows it directly
+                    Some((_, other)) => return self.fail(LexError::UnexpectedChar(other, body + stop + i + 1)),
+                    None => return self.fail(LexError::Unterminated(start)),
+                },
+                Some((_, ch)) => s.push(ch),
+                None => return self.fail(LexError::Unterminated(start)),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<(Token<'a>, Range<usize>), LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.input;
+        // skip whitespace and plain comments
+        loop {
+            self.pos += Self::span_while(&input[self.pos..], char::is_whitespace);
+            let rest = &input[self.pos..];
+            if !rest.starts_with("//") || is_doc(rest) { break; }
+            self.pos += rest.find('\n').unwrap_or(rest.len());
+        }
+        let start = self.pos;
+        let rest = &input[start..];
+        let c = rest.chars().next()?;
+
+        // doc comment
+        if is_doc(rest) {
+            let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+            let text = line[3..].strip_prefix(' ').unwrap_or(&line[3..]);
+            return self.token(start, Token::Doc(Cow::Borrowed(text)), line.len());
+        }
+
+        // identifier / keyword
+        if is_ident_start(c) {
+            let word = &rest[..Self::span_while(rest, is_ident_continue)];
+            let tok = match Keyword::lookup(word) {
+                Some(k) => Token::Keyword(k),
+                None => Token::Ident(Cow::Borrowed(word)),
+            };
+            return self.token(start, tok, word.len());
+        }
+
+        // number
+        if c.is_ascii_digit() {
+            let digits = &rest[..Self::span_while(rest, |c| c.is_ascii_digit())];
+            return match digits.parse() {
+                Ok(n) => self.token(start, Token::Number(n), digits.len()),
+                Err(_) => self.fail(LexError::NumberOverflow(start)),
+            };
+        }
+
+        // string literal, with `\"` and `\\` escapes
+        if c == '"' {
+            return self.string(start);
+        }
+
+        let next_is = |b: u8| rest.as_bytes().get(1) == Some(&b);
+        let op = match c {
+            '=' if next_is(b'=') => Some((Op::EqEq, 2)),
+            '!' if next_is(b'=') => Some((Op::NotEq, 2)),
+            '>' if next_is(b'=') => Some((Op::GtEq, 2)),
+            '<' if next_is(b'=') => Some((Op::LtEq, 2)),
+            '*' if next_is(b'*') => Some((Op::StarStar, 2)),
+            '=' => Some((Op::Eq, 1)),
+            '>' => Some((Op::Gt, 1)),
+            '<' => Some((Op::Lt, 1)),
+            '+' => Some((Op::Plus, 1)),
+            '-' => Some((Op::Minus, 1)),
+            '*' => Some((Op::Star, 1)),
+            '/' => Some((Op::Slash, 1)),
+            '%' => Some((Op::Percent, 1)),
+            _ => None,
+        };
+        if let Some((op, len)) = op {
+            return self.token(start, Token::Operator(op), len);
+        }
+
+        // symbols
+        if matches!(c, '{' | '}' | '(' | ')' | '[' | ']' | ',' | '.' | ';' | ':') {
+            return self.token(start, Token::Symbol(c), 1);
+        }
+
+        // unknown
+        self.fail(LexError::UnexpectedChar(c, start))
+    }
+}
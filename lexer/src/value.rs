@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use thiserror::Error;
+
+use crate::ast::{Field, Segment};
+use crate::time::{format_timestamp, parse_timestamp};
+
+/// A runtime value: a record, one of its fields or an evaluated operand.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(i64),
+    /// A number with a fractional part or outside `i64`, as JSON allows.
+    Float(f64),
+    Str(String),
+    /// A span of time in seconds.
+    Duration(i64),
+    /// Seconds since the Unix epoch.
+    Timestamp(i64),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Look up a dotted field path, e.g. `user.country`. Missing fields are `Null`.
+    pub fn get(&self, field: &Field) -> &Value {
+        self.get_path(&field.segments)
+    }
+
+    /// The value at a concrete path, if it exists.
+    pub fn get_mut(&mut self, field: &Field) -> Option<&mut Value> {
+        let mut cur = self;
+        for seg in &field.segments {
+            cur = match (cur, seg) {
+                (Value::Map(m), Segment::Key(k)) => m.get_mut(k)?,
+                (Value::List(items), Segment::Index(i)) => items.get_mut(*i)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+
+    /// Look up a path relative to this value. Out-of-range indexes are `Null`.
+    pub fn get_path(&self, segments: &[Segment]) -> &Value {
+        let mut cur = self;
+        for seg in segments {
+            cur = match (cur, seg) {
+                (Value::Map(m), Segment::Key(k)) => m.get(k).unwrap_or(&Value::Null),
+                (Value::List(items), Segment::Index(i)) => items.get(*i).unwrap_or(&Value::Null),
+                // patterns have no single value; see `glob::expand`
+                _ => return &Value::Null,
+            };
+        }
+        cur
+    }
+
+    /// Convert parsed JSON. Integers that fit `i64` become numbers and other
+    /// numbers floats. Strings stay strings, even those holding a date; they
+    /// are read as timestamps where one is expected, see `as_timestamp`.
+    pub fn from_json(json: &serde_json::Value) -> Result<Value, String> {
+        use serde_json::Value as Json;
+        Ok(match json {
+            Json::Null => Value::Null,
+            Json::Bool(b) => Value::Bool(*b),
+            Json::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => Value::Number(i),
+                (None, Some(f)) => Value::Float(f),
+                (None, None) => return Err(format!("unsupported number {n}")),
+            },
+            Json::String(s) => Value::Str(s.clone()),
+            Json::Array(items) => Value::List(items.iter().map(Value::from_json).collect::<Result<_, _>>()?),
+            Json::Object(m) => Value::Map(m.iter().map(|(k, v)| Ok((k.clone(), Value::from_json(v)?))).collect::<Result<_, String>>()?),
+        })
+    }
+
+    /// Convert to JSON; durations become seconds and timestamps ISO-8601 strings.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+        match self {
+            Value::Null => Json::Null,
+            Value::Bool(b) => Json::Bool(*b),
+            Value::Number(n) | Value::Duration(n) => Json::from(*n),
+            Value::Float(x) => serde_json::Number::from_f64(*x).map_or(Json::Null, Json::Number),
+            Value::Str(s) => Json::String(s.clone()),
+            Value::Timestamp(ts) => Json::String(format_timestamp(*ts)),
+            Value::List(items) => Json::Array(items.iter().map(Value::to_json).collect()),
+            Value::Map(m) => Json::Object(m.iter().map(|(k, v)| (k.clone(), v.to_json())).collect()),
+        }
+    }
+
+    /// The timestamp a value stands for: a timestamp, or a string holding an
+    /// ISO-8601 date or date-time.
+    pub fn as_timestamp(&self) -> Option<i64> {
+        match self {
+            Value::Timestamp(ts) => Some(*ts),
+            Value::Str(s) => parse_timestamp(s),
+            _ => None,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::Float(_) => "float",
+            Value::Str(_) => "string",
+            Value::Duration(_) => "duration",
+            Value::Timestamp(_) => "timestamp",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+impl From<bool> for Value { fn from(b: bool) -> Self { Value::Bool(b) } }
+impl From<i64> for Value { fn from(n: i64) -> Self { Value::Number(n) } }
+impl From<f64> for Value { fn from(x: f64) -> Self { Value::Float(x) } }
+impl From<&str> for Value { fn from(s: &str) -> Self { Value::Str(s.to_string()) } }
+impl From<String> for Value { fn from(s: String) -> Self { Value::Str(s) } }
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Value {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Value::Map(iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Float(x) => write!(f, "{x:?}"),
+            Value::Str(s) => write!(f, "{s:?}"),
+            Value::Duration(secs) => write!(f, "{secs} seconds"),
+            Value::Timestamp(ts) => write!(f, "{}", format_timestamp(*ts)),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, v) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{v}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in m.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{k}: {v}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("line {line}: {message}")]
+pub struct RecordError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parse JSON-lines records, one object per non-blank line.
+pub fn records_from_jsonl(src: &str) -> Result<Vec<Value>, RecordError> {
+    src.lines().enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            let err = |message: String| RecordError { line: i + 1, message };
+            let json: serde_json::Value = serde_json::from_str(l).map_err(|e| err(e.to_string()))?;
+            match Value::from_json(&json).map_err(err)? {
+                v @ Value::Map(_) => Ok(v),
+                v => Err(RecordError { line: i + 1, message: format!("expected an object, found {}", v.type_name()) }),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,48 @@
+use std::ptr;
+
+use crate::api;
+
+//
+// ===== WEBASSEMBLY =====
+//
+// `api::call` behind a plain C ABI with no imports, so any wasm runtime can
+// load the module. Build it with
+//
+//   cargo build --lib --release --target wasm32-unknown-unknown --no-default-features
+//
+// The host copies a UTF-8 request into a buffer from `policy_alloc`, calls
+// `policy_call`, reads the response at the returned pointer and length
+// (packed as `ptr << 32 | len`) and releases both buffers with `policy_free`.
+// `wasm/policy.mjs` wraps this for JavaScript, and `node wasm/test.mjs` runs
+// its tests against the build.
+//
+
+/// A buffer of `len` bytes for the host to write a request into.
+#[unsafe(no_mangle)]
+pub extern "C" fn policy_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()).cast()
+}
+
+/// Release a buffer from `policy_alloc` or a response from `policy_call`.
+///
+/// # Safety
+///
+/// `ptr` and `len` must describe such a buffer, and it must not be used after.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn policy_free(ptr: *mut u8, len: usize) {
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)) });
+}
+
+/// Handle the request in `ptr[..len]` and return the response, to be freed
+/// with `policy_free`.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn policy_call(ptr: *const u8, len: usize) -> u64 {
+    let request = unsafe { std::slice::from_raw_parts(ptr, len) };
+    let response = api::call(&String::from_utf8_lossy(request)).into_bytes().into_boxed_slice();
+    let len = response.len() as u64;
+    (Box::into_raw(response).cast::<u8>() as u64) << 32 | len
+}
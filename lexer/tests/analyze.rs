@@ -0,0 +1,108 @@
+mod common;
+
+use common::*;
+use lexer::analyze::{implies, normalize};
+use lexer::*;
+
+fn lints(src: &str) -> Vec<(LintCode, String, Option<usize>)> {
+    analyze(&parse(src), &LintConfig::default()).into_iter().map(|l| (l.code, l.rule, l.statement)).collect()
+}
+
+#[test]
+fn normalize_strips_groups_and_applies_de_morgan() {
+    assert_eq!(normalize(&condition("not (a > 1 and (b < 2 or not c == 3))")),
+               normalize(&condition("not a > 1 or (not b < 2 and c == 3)")));
+    assert_eq!(normalize(&condition("not not (x > 1)")), condition("x > 1"));
+    assert_eq!(normalize(&condition("not any e in xs: e == \"a\"")),
+               condition("all e in xs: not e == \"a\""));
+}
+
+#[test]
+fn contradictions_never_fire() {
+    for cond in ["x > 5 and x < 3", "x in [a, b] and x == \"c\"", "1 > 2", "is_email(x) and not is_email(x)",
+                 "x >= 10 and not x > 5", "age > 30 days and age <= 1 weeks"] {
+        assert_eq!(lints(&format!("rule r {{ if {cond} then delete }}")), [(LintCode::NeverFires, "r".into(), Some(0))], "{cond}");
+    }
+}
+
+#[test]
+fn tautologies_are_always_true() {
+    for cond in ["x > 5 or not x > 5", "x != \"a\" or x in [a]", "2 > 1", "x < 10 or not x < 3"] {
+        assert_eq!(lints(&format!("rule r {{ if {cond} then delete }}")), [(LintCode::AlwaysTrue, "r".into(), Some(0))], "{cond}");
+    }
+}
+
+#[test]
+fn missing_fields_keep_ranges_from_being_tautologies() {
+    // a missing `x` fails both comparisons
+    assert!(lints("rule r { if x > 5 or x <= 5 then delete }").is_empty());
+    assert!(lints("rule r { if not x > 5 and not x < 3 then delete }").is_empty());
+}
+
+#[test]
+fn strict_bounds_leave_room_for_floats_between_integers() {
+    let src = "rule r { if record.score > 5 and record.score < 6 then delete }";
+    assert!(Evaluator::with_now(0).eval_expr(&condition("record.score > 5 and record.score < 6"),
+                                            &record(r#"{"record": {"score": 5.5}}"#)).unwrap());
+    assert!(lints(src).is_empty());
+    assert!(lints("rule r { if x < 10 or x > 9 then delete }").is_empty());
+    assert_eq!(lints("rule r { if x > 5 and x <= 5 then delete }"), [(LintCode::NeverFires, "r".into(), Some(0))]);
+
+    let n = |src: &str| normalize(&condition(src));
+    assert!(!implies(&n("x > 5"), &n("x >= 6")));
+    assert!(implies(&n("x >= 6"), &n("x > 5")));
+    assert!(!implies(&n("x > 5 and x < 7"), &n("x == 6")));
+    assert!(implies(&n("x > 5 and x < 7"), &n("x >= 5 and x != 7")));
+}
+
+#[test]
+fn later_statements_implied_by_earlier_ones_are_subsumed() {
+    let src = r#"
+        rule a {
+            if user.failed_logins > 5 then notify;
+            if user.failed_logins > 10 and user.country == "de" then notify;
+            if user.failed_logins > 10 then delete
+        }
+        rule b {
+            if (user.failed_logins > 5) then notify;
+            if user.country in [de, fr] or user.failed_logins >= 6 then notify;
+            if user.failed_logins > 3 then notify
+        }
+    "#;
+    assert_eq!(lints(src), [
+        (LintCode::Subsumed, "a".into(), Some(1)),
+        (LintCode::Subsumed, "b".into(), Some(0)),
+    ]);
+}
+
+#[test]
+fn severities_are_configurable() {
+    let program = parse("rule r { if 1 > 2 then delete; if 1 > 2 then delete; if x == 1 then mask; if x == 1 then mask }");
+    let mut config = LintConfig::default();
+    config.set(LintCode::NeverFires, Severity::Allow);
+    config.set(LintCode::Subsumed, Severity::Deny);
+    let found: Vec<_> = analyze(&program, &config).into_iter().map(|l| (l.code, l.severity, l.statement)).collect();
+    assert_eq!(found, [(LintCode::Subsumed, Severity::Deny, Some(3))]);
+}
+
+#[test]
+fn mixed_script_and_confusable_identifiers() {
+    // `рау` is all Cyrillic, `usеr` has a Cyrillic `е`, `ℓogins` a letterlike symbol
+    let src = "
+        rule рау { if usеr.name == \"x\" then mask }
+        rule r {
+            if user.failed_logins > 5 then notify;
+            if user.ℓogins > 5 and usеr.id == 1 then notify
+        }
+    ";
+    assert_eq!(lints(src), [
+        (LintCode::Confusable, "рау".into(), None),
+        (LintCode::MixedScript, "рау".into(), Some(0)),
+        (LintCode::Confusable, "рау".into(), Some(0)),
+        (LintCode::Confusable, "r".into(), Some(1)),
+    ]);
+    // plain identifiers in any single script are fine
+    assert!(lints("rule règle { if 名前.長さ > 3 or straße == \"x\" then mask }").is_empty());
+    // precomposed and decomposed `é` look the same
+    assert_eq!(lints("rule r { if café == 1 or cafe\u{301} == 2 then mask }"), [(LintCode::Confusable, "r".into(), Some(0))]);
+}
@@ -0,0 +1,96 @@
+use lexer::api;
+use serde_json::{json, Value as Json};
+
+fn call(request: Json) -> Json {
+    serde_json::from_str(&api::call(&request.to_string())).unwrap()
+}
+
+#[test]
+fn tokens_and_rules_carry_spans() {
+    let src = "rule a {\n  if x > 1 then notify;\n  if y == \"é\" then delete\n}\n";
+    let out = call(json!({ "op": "lex", "source": src }));
+    assert_eq!(out["ok"], true);
+    let tokens = out["tokens"].as_array().unwrap();
+    assert_eq!(tokens[0], json!({
+        "kind": "keyword", "text": "rule",
+        "span": { "start": { "offset": 0, "line": 1, "col": 1 }, "end": { "offset": 4, "line": 1, "col": 5 } },
+    }));
+    let close = tokens.iter().find(|t| t["text"] == "\"é\"").unwrap();
+    assert_eq!(close["kind"], "string");
+    assert_eq!(close["span"]["end"]["col"], 14, "columns count characters, not bytes");
+
+    let out = call(json!({ "op": "parse", "source": src }));
+    assert_eq!(out["ok"], true);
+    let rule = &out["rules"][0];
+    assert_eq!(rule["name"], "a");
+    assert_eq!(rule["span"]["end"]["line"], 4);
+    let statements = rule["statements"].as_array().unwrap();
+    assert_eq!(statements[1]["action"], "delete");
+    assert_eq!(statements[1]["span"]["start"], json!({ "offset": 35, "line": 3, "col": 3 }));
+    assert_eq!(statements[1]["span"]["end"]["offset"], 59);
+}
+
+#[test]
+fn lex_and_parse_errors_point_at_the_source() {
+    let out = call(json!({ "op": "lex", "source": "rule a {\n  if x # 1\n}" }));
+    assert_eq!(out["ok"], false);
+    let d = &out["diagnostics"][0];
+    assert_eq!((d["stage"].as_str(), d["severity"].as_str()), (Some("lex"), Some("error")));
+    assert_eq!(d["span"]["start"], json!({ "offset": 16, "line": 2, "col": 8 }));
+
+    let out = call(json!({ "op": "parse", "source": "rule a {\n  if x > then notify\n}" }));
+    let d = &out["diagnostics"][0];
+    assert_eq!(d["stage"], "parse");
+    assert_eq!(d["span"]["start"]["line"], 2);
+    assert_eq!(d["span"]["start"]["col"], 10, "at the unexpected `then`");
+
+    let out = call(json!({ "op": "parse", "source": "rule a {" }));
+    assert_eq!(out["diagnostics"][0]["span"]["start"]["offset"], 8, "just past the last token");
+}
+
+#[test]
+fn check_reports_type_errors_and_lints() {
+    let out = call(json!({ "op": "check", "source": "rule a {\n  if x > 1 and x < 0 then notify\n}" }));
+    assert_eq!(out["ok"], true, "lints that only warn do not fail the check");
+    let d = &out["diagnostics"][0];
+    assert_eq!((d["stage"].as_str(), d["code"].as_str()), (Some("lint"), Some("never-fires")));
+    assert_eq!(d["span"]["start"]["line"], 2);
+
+    let out = call(json!({ "op": "check", "source": "rule a {\n  if nope(x) then notify\n}" }));
+    assert_eq!(out["ok"], false);
+    let d = &out["diagnostics"][0];
+    assert_eq!(d["stage"], "check");
+    assert!(d.get("code").is_none());
+    assert_eq!(d["span"]["start"]["col"], 3);
+}
+
+#[test]
+fn evaluate_takes_records_and_a_clock() {
+    let src = "rule stale { if record.age > 30 then delete }";
+    let out = call(json!({ "op": "evaluate", "source": src, "now": 1_700_000_000, "records": [{ "record": { "age": 40 } }, { "record": { "age": 3 } }] }));
+    assert_eq!(out["ok"], true);
+    assert_eq!(out["results"][0]["decisions"], json!([
+        { "rule": "stale", "statement": 0, "action": "delete", "targets": [], "meta": {} },
+    ]));
+    assert_eq!(out["results"][1]["decisions"], json!([]));
+
+    let out = call(json!({ "op": "evaluate", "source": src, "records": [] }));
+    assert_eq!(out["diagnostics"][0]["stage"], "request");
+}
+
+#[test]
+fn bad_requests_are_diagnosed() {
+    for (request, message) in [
+        ("{", "invalid JSON"),
+        (r#"{"op": "lex"}"#, "missing \"source\" string"),
+        (r#"{"source": ""}"#, "missing \"op\" string"),
+        (r#"{"op": "fly", "source": ""}"#, "unknown op \"fly\""),
+    ] {
+        let out: Json = serde_json::from_str(&api::call(request)).unwrap();
+        assert_eq!(out["ok"], false);
+        let d = &out["diagnostics"][0];
+        assert_eq!(d["stage"], "request");
+        assert_eq!(d["span"], Json::Null);
+        assert!(d["message"].as_str().unwrap().starts_with(message), "{request}: {d}");
+    }
+}
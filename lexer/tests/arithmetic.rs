@@ -0,0 +1,93 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+/// Whether `condition` holds for `json`, or the evaluation error.
+fn holds(condition: &str, json: &str) -> Result<bool, String> {
+    let program = parse(&format!("rule r {{ if {condition} then delete }}"));
+    check_program(&program).map_err(|e| e.to_string())?;
+    Evaluator::with_now(1_000_000_000).evaluate(&program, &record(json))
+        .map(|decisions| !decisions.is_empty())
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn numbers_compared_with_durations() {
+    let r = r#"{"record": {"age_in_days": 40, "half": 1.5, "timeout": 90, "created_at": "2001-08-01"}}"#;
+    for (condition, expected) in [
+        // against a literal, a number counts the literal's unit
+        ("record.age_in_days > 30 days", true),
+        ("record.age_in_days > 50 days", false),
+        ("30 days < record.age_in_days", true),
+        ("record.timeout == 90 seconds", true),
+        ("record.half > 1 days", true),
+        ("record.half < 2 days", true),
+        ("record.half > 2 days", false),
+        // against any other duration, seconds
+        ("record.timeout > 1 minutes + 0 seconds", true),
+        ("record.timeout < now - record.created_at", true),
+        ("record.half < 2 seconds", true),
+        ("record.missing > 30 days", false),
+    ] {
+        assert_eq!(holds(condition, r), Ok(expected), "{condition}");
+    }
+}
+
+#[test]
+fn precedence_and_associativity() {
+    let r = r#"{"record": {"a": 2, "b": 3, "c": 4}}"#;
+    for condition in [
+        "record.a + record.b * record.c == 14",
+        "(record.a + record.b) * record.c == 20",
+        "record.c - record.b - record.a == -1",
+        "record.c / record.a * record.b == 6",
+        "-record.a * record.b == -6",
+        "record.c % record.b + record.a == 3",
+        "2 days + 1 days * record.b == 5 days",
+        "now - 1 days + 2 hours < now",
+    ] {
+        assert_eq!(holds(condition, r), Ok(true), "{condition}");
+    }
+}
+
+#[test]
+fn results_follow_the_checker_types() {
+    let r = r#"{"record": {"n": 7, "price": 2.5, "d": "2001-09-08"}}"#;
+    for (condition, expected) in [
+        ("record.n / 2 == 3", Ok(true)),
+        ("record.price * 2 == 5", Ok(true)),
+        ("record.n + record.price > 9", Ok(true)),
+        ("now - record.d > 1 days", Ok(true)),
+        ("10 days / 5 days == 2", Ok(true)),
+        ("record.n - 1 days > 0 seconds", Err("cannot apply '-' to number and duration")),
+        ("record.n + \"x\" == 1", Err("cannot apply '+' to number and string")),
+        ("record.missing * 2 == 0", Ok(false)),
+    ] {
+        let got = holds(condition, r);
+        match expected {
+            Ok(b) => assert_eq!(got, Ok(b), "{condition}"),
+            Err(message) => assert!(got.as_ref().is_err_and(|e| e.contains(message)), "{condition}: {got:?}"),
+        }
+    }
+}
+
+#[test]
+fn division_by_zero_and_overflow_are_errors() {
+    let r = r#"{"record": {"zero": 0, "big": 9223372036854775807, "small": -9223372036854775808, "x": 1.5}}"#;
+    for (condition, message) in [
+        ("10 / record.zero == 1", "division by zero"),
+        ("10 % record.zero == 1", "division by zero"),
+        ("1 days / record.zero > 0 seconds", "division by zero"),
+        ("record.x / record.zero > 0", "division by zero"),
+        ("record.big + 1 > 0", "overflow"),
+        ("record.small - 1 < 0", "overflow"),
+        ("record.big * 2 > 0", "overflow"),
+        ("-record.small > 0", "overflow"),
+        ("record.small / -1 > 0", "overflow"),
+        ("record.big * 1 days > 0 seconds", "overflow"),
+    ] {
+        let err = holds(condition, r).unwrap_err();
+        assert!(err.contains(message), "{condition}: {err}");
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::fs;
+use std::sync::Arc;
+
+use common::*;
+use lexer::*;
+
+const RECORD: &str = r#"{"record": {
+    "id": "u-17", "country": "de", "ssn": "123-45-6789", "age_in_days": 400,
+    "contacts": [{"phone": "+49 30 1234567"}]
+}}"#;
+
+const POLICY: &str = r#"
+    rule retention { if record.age_in_days > 365 and record.country == "de" then delete }
+    rule pii {
+        if record.ssn != "" then mask;
+        if record.**.phone starts_with "+49" then mask;
+        if record.country == "fr" then notify
+    }
+"#;
+
+const SECRETS: [&str; 2] = ["123-45-6789", "+49 30 1234567"];
+
+#[test]
+fn events_hash_values_unless_allowlisted() {
+    let program = parse(POLICY);
+    let sink = Arc::new(MemorySink::new());
+    let auditor = Auditor::new(sink.clone(), b"audit key").id_field("record.id").clear("record.country");
+    let decisions = auditor.evaluate(&Evaluator::with_now(1_700_000_000), &program, &record(RECORD)).unwrap();
+    assert_eq!(decisions.len(), 3);
+
+    let events = sink.events();
+    let summary: Vec<(&str, usize, Action)> = events.iter().map(|e| (e.rule.as_str(), e.statement, e.action)).collect();
+    assert_eq!(summary, [("retention", 0, Action::Delete), ("pii", 0, Action::Mask), ("pii", 1, Action::Mask)]);
+    assert!(events.iter().all(|e| e.record.as_deref() == Some("u-17") && e.timestamp == 1_700_000_000));
+    assert!(events.iter().all(|e| e.policy == policy_hash(&program)));
+
+    assert_eq!(events[0].fields, [
+        ("record.age_in_days".to_string(), Logged::Hash(auditor.hash(&Value::Number(400)))),
+        ("record.country".to_string(), Logged::Clear(Value::from("de"))),
+    ]);
+    // the pattern is logged as the element it matched
+    let paths: Vec<&str> = events[2].fields.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(paths, ["record.contacts[0].phone"]);
+    assert!(events.iter().flat_map(|e| &e.fields).all(|(p, v)| matches!(v, Logged::Clear(_)) == (p == "record.country")));
+}
+
+#[test]
+fn json_lines_never_contain_sensitive_values() {
+    let path = std::env::temp_dir().join(format!("policy-audit-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let program = parse(POLICY);
+    let auditor = Auditor::new(JsonLinesSink::open(&path).unwrap(), b"audit key").id_field("record.id");
+    auditor.evaluate(&Evaluator::with_now(0), &program, &record(RECORD)).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    for secret in SECRETS.iter().chain(&["\"de\""]) {
+        assert!(!text.contains(secret), "{secret} logged in clear:\n{text}");
+    }
+    let lines: Vec<serde_json::Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1]["time"], "1970-01-01T00:00:00Z");
+    assert_eq!(lines[1]["record"], "u-17");
+    assert_eq!(lines[1]["action"], "mask");
+    assert_eq!(lines[1]["fields"]["record.ssn"]["hmac"].as_str().unwrap().len(), 64);
+}
+
+#[test]
+fn hashes_depend_on_the_key_and_policy_hashes_on_the_rules() {
+    let a = Auditor::new(MemorySink::new(), b"one");
+    let b = Auditor::new(MemorySink::new(), b"two");
+    let ssn = Value::from("123-45-6789");
+    assert_eq!(a.hash(&ssn), a.hash(&ssn));
+    assert_ne!(a.hash(&ssn), b.hash(&ssn));
+    assert_ne!(a.hash(&ssn), a.hash(&Value::from("123-45-6780")));
+
+    let program = parse(POLICY);
+    assert_eq!(policy_hash(&program), policy_hash(&parse(&format!("// reformatted\n{}", POLICY.replace("    ", " ")))));
+    assert_ne!(policy_hash(&program), policy_hash(&parse(&POLICY.replace("365", "366"))));
+}
@@ -0,0 +1,21 @@
+//! Helpers shared by the integration tests. Each test crate uses a subset.
+#![allow(dead_code)]
+
+use lexer::*;
+
+pub fn parse(src: &str) -> Program {
+    Parser::new(lex(src).unwrap()).parse_program().unwrap()
+}
+
+/// The condition of `if <src> then delete`.
+pub fn condition(src: &str) -> Expr {
+    parse(&format!("rule r {{ if {src} then delete }}")).rules.remove(0).statements.remove(0).condition
+}
+
+pub fn record(json: &str) -> Value {
+    Value::from_json(&serde_json::from_str(json).unwrap()).unwrap()
+}
+
+pub fn field(path: &str) -> Field {
+    Field::from_dotted(path)
+}
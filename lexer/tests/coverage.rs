@@ -0,0 +1,109 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+fn cover(program: &Program, records: &[&str]) -> Coverage {
+    let evaluator = Evaluator::with_now(0);
+    let mut coverage = Coverage::new(program);
+    for r in records {
+        let r = record(r);
+        let decisions = evaluator.evaluate_covered(program, &r, &mut coverage).unwrap();
+        let plain = evaluator.evaluate(program, &r).unwrap();
+        assert_eq!(format!("{decisions:?}"), format!("{plain:?}"), "coverage doesn't change decisions");
+    }
+    coverage
+}
+
+#[test]
+fn counts_fired_statements_and_decisive_sides() {
+    let program = parse(r#"
+        rule r {
+            if record.a == 1 or record.b == 1 then delete;
+            if record.a == 1 and (record.b == 1 or record.c == 1) then notify;
+            if record.d == 1 then mask
+        }
+        rule never { if record.x == "y" then notify }
+    "#);
+    let coverage = cover(&program, &[
+        r#"{"record": {"a": 1, "b": 1}}"#,
+        r#"{"record": {"a": 1, "c": 1}}"#,
+        r#"{"record": {"a": 2, "b": 2}}"#,
+    ]);
+    assert_eq!(coverage.records, 3);
+    let r = &coverage.rules[0];
+    assert_eq!(r.statements.iter().map(|st| st.fired).collect::<Vec<_>>(), [2, 2, 0]);
+    assert!(r.fired());
+    assert!(!coverage.rules[1].fired());
+
+    // `a or b`: `a` made it true twice; `b` was never what made it true
+    let or = &r.statements[0].branches[0];
+    assert!(or.is_or);
+    assert_eq!(or.sides, ["record.a == 1".to_string(), "record.b == 1".to_string()]);
+    assert_eq!(or.decisive, [2, 0]);
+
+    // `a and (b or c)`: `a` made it false once, the group never did
+    let branches = &r.statements[1].branches;
+    assert_eq!(branches.len(), 2);
+    assert_eq!((branches[0].is_or, branches[0].decisive), (false, [1, 0]));
+    assert_eq!((branches[1].is_or, branches[1].decisive), (true, [1, 1]));
+}
+
+#[test]
+fn quantifiers_and_patterns_count_once_per_record() {
+    let program = parse(r#"
+        rule r {
+            if any c in record.contacts: (c.email ends_with ".ru" or c.email ends_with ".by") then mask;
+            if record.**.ssn != "" or record.flag == true then encrypt
+        }
+    "#);
+    let coverage = cover(&program, &[
+        r#"{"record": {"contacts": [{"email": "a@b.ru"}, {"email": "c@d.ru"}], "x": {"ssn": "1"}, "y": {"ssn": "2"}}}"#,
+        r#"{"record": {"contacts": [{"email": "a@b.by"}], "flag": true}}"#,
+    ]);
+    let [mask, encrypt] = &coverage.rules[0].statements[..] else { panic!() };
+    assert_eq!(mask.fired, 2);
+    assert_eq!(mask.branches[0].decisive, [1, 1]);
+    // no `ssn` location in the second record, so the condition never ran there
+    assert_eq!(encrypt.fired, 1);
+    assert_eq!(encrypt.branches[0].decisive, [1, 0]);
+}
+
+#[test]
+fn errors_leave_coverage_unchanged() {
+    let program = parse("rule r { if record.a == 1 then notify; if record.n / 0 > 1 then delete }");
+    let evaluator = Evaluator::with_now(0);
+    let mut coverage = Coverage::new(&program);
+    assert!(evaluator.evaluate_covered(&program, &record(r#"{"record": {"a": 1, "n": 1}}"#), &mut coverage).is_err());
+    assert_eq!(coverage.records, 0);
+    assert_eq!(coverage.rules[0].statements[0].fired, 0);
+}
+
+#[test]
+fn text_and_html_reports() {
+    let src = "rule keep {\n  if record.a == 1 or record.b == 1 then notify\n}\n\nrule dead {\n  if record.c == \"<x>\" then delete\n}\n";
+    let program = parse(src);
+    let coverage = cover(&program, &[r#"{"record": {"a": 1}}"#]);
+
+    assert_eq!(coverage.to_string(), "\
+1 record: 1 of 2 statements fired, 1 of 2 sides of `and`/`or` never decisive
+
+rule keep
+  [0] notify: fired for 1 record
+    never decisive: `record.b == 1`, right of `or`
+
+rule dead: never fired
+  [0] delete: never fired
+");
+
+    let html = coverage.html("p & q", src).unwrap();
+    assert!(html.contains("<title>p &amp; q</title>"));
+    assert!(html.contains(concat!(
+        r#"<span class="fired" title="fired for 1 of 1 record">if record.a == 1 "#,
+        r#"<span class="dead-side" title="never decisive: `record.b == 1`, right of `or`">or</span> record.b == 1 then notify</span>"#,
+    )));
+    assert!(html.contains(concat!(
+        r#"<span class="dead-rule" title="no statement fired">rule dead {"#, "\n  ",
+        r#"<span class="dead" title="never fired">if record.c == &quot;&lt;x&gt;&quot; then delete</span>"#, "\n}</span>",
+    )));
+}
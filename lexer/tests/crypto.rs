@@ -0,0 +1,179 @@
+#![cfg(feature = "crypto")]
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use lexer::*;
+
+const KEY_A: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const KEY_B: &str = "f0e0d0c0b0a090807060504030201000f0e0d0c0b0a090807060504030201000";
+
+fn keyring(text: &str) -> Keyring {
+    Keyring::parse(text).unwrap()
+}
+
+#[test]
+fn envelopes_round_trip_and_are_bound_to_their_field() {
+    let cipher = Cipher::new(keyring(&format!("# test keys\nold {KEY_A}\n\nnew {KEY_B}\n")));
+    let ssn = field("record.ssn");
+    for value in [Value::from("123-45-6789"), Value::Number(42), Value::Float(9.99),
+                  record(r#"{"a": [1, "two", null], "b": true}"#)] {
+        let sealed = cipher.encrypt(&ssn, &value).unwrap();
+        let Value::Str(text) = &sealed else { panic!("{sealed}") };
+        let parts: Vec<&str> = text.split(':').collect();
+        assert_eq!(parts[..3], ["enc", "v1", "new"], "encrypted under the current key");
+        assert_eq!(parts[3].len(), 16, "12-byte nonce in unpadded base64url");
+        assert_eq!(cipher.decrypt(&ssn, &sealed).unwrap(), value);
+        assert_ne!(cipher.encrypt(&ssn, &value).unwrap(), sealed, "nonces are random");
+    }
+    // the plaintext is JSON, so timestamps and durations come back as a JSON record holds them
+    for (value, decrypted) in [(Value::Timestamp(86400), Value::from("1970-01-02T00:00:00Z")), (Value::Duration(60), Value::Number(60))] {
+        assert_eq!(cipher.decrypt(&ssn, &cipher.encrypt(&ssn, &value).unwrap()).unwrap(), decrypted);
+    }
+
+    let sealed = cipher.encrypt(&ssn, &Value::from("123-45-6789")).unwrap();
+    let moved = cipher.decrypt(&field("record.name"), &sealed).unwrap_err();
+    assert!(matches!(moved, CryptoError::Tampered { .. }), "{moved}");
+    let Value::Str(text) = &sealed else { unreachable!() };
+    // not the last character, whose low bits may be padding
+    let mut bytes = text.clone().into_bytes();
+    let i = bytes.len() - 2;
+    bytes[i] = if bytes[i] == b'A' { b'B' } else { b'A' };
+    let tampered = Value::Str(String::from_utf8(bytes).unwrap());
+    assert!(matches!(cipher.decrypt(&ssn, &tampered), Err(CryptoError::Tampered { .. })));
+    let unknown = Value::Str(text.replacen(":new:", ":gone:", 1));
+    assert!(matches!(cipher.decrypt(&ssn, &unknown), Err(CryptoError::UnknownKey(id)) if id == "gone"));
+    assert!(matches!(cipher.decrypt(&ssn, &Value::from("enc:v1:new:xyz")), Err(CryptoError::Malformed { .. })));
+    // plain values pass through
+    assert_eq!(cipher.decrypt(&ssn, &Value::from("plain")).unwrap(), Value::from("plain"));
+}
+
+#[test]
+fn rotated_keys_still_decrypt_old_values() {
+    let old = Cipher::new(keyring(&format!("old {KEY_A}")));
+    let sealed = old.encrypt(&field("record.ssn"), &Value::from("123-45-6789")).unwrap();
+    let rotated = Cipher::new(keyring(&format!("old {KEY_A}\nnew {KEY_B}")));
+    assert_eq!(rotated.decrypt(&field("record.ssn"), &sealed).unwrap(), Value::from("123-45-6789"));
+    let other = Cipher::new(keyring(&format!("old {KEY_B}")));
+    assert!(matches!(other.decrypt(&field("record.ssn"), &sealed), Err(CryptoError::Tampered { .. })));
+}
+
+#[test]
+fn tokens_keep_the_format_and_reverse_with_the_same_key() {
+    let cipher = Cipher::new(keyring(&format!("k {KEY_A}\ntokenize record.** k"))).tokenize(field("record.**.card"));
+    let card = field("record.payment.card");
+    let number = Value::from("4111-1111-1111-1111");
+    let token = cipher.encrypt(&card, &number).unwrap();
+    let Value::Str(text) = &token else { panic!("{token}") };
+    assert_ne!(token, number);
+    assert!(text.chars().zip("4111-1111-1111-1111".chars()).all(|(t, p)| t.is_ascii_digit() == p.is_ascii_digit() && (t == p || t.is_ascii_digit())));
+    assert_eq!(cipher.encrypt(&card, &number).unwrap(), token, "tokens are deterministic");
+    assert_ne!(cipher.encrypt(&field("record.card"), &number).unwrap(), token, "the path is the tweak");
+    assert_eq!(cipher.decrypt(&card, &token).unwrap(), number);
+
+    let unbound = Cipher::new(keyring(&format!("k {KEY_A}"))).tokenize(field("record.**.card"));
+    assert!(matches!(unbound.decrypt(&card, &token), Err(CryptoError::Unbound { .. })));
+    assert!(matches!(unbound.encrypt(&card, &number), Err(CryptoError::Unbound { .. })));
+
+    assert!(matches!(cipher.encrypt(&card, &Value::from("12-345")), Err(CryptoError::TooFewDigits { .. })));
+    assert!(matches!(cipher.encrypt(&card, &Value::Number(4111111111111111)), Err(CryptoError::NotAString { found: "number", .. })));
+}
+
+#[test]
+fn tokens_stay_bound_to_their_key_across_rotation() {
+    let card = field("record.card");
+    let number = Value::from("4111-1111-1111-1111");
+    let before = Cipher::new(keyring(&format!("old {KEY_A}\ntokenize record.card old"))).tokenize(card.clone());
+    let token = before.encrypt(&card, &number).unwrap();
+
+    // a new current key, or another `--key`, changes envelopes but not tokens
+    let rotated = keyring(&format!("old {KEY_A}\nnew {KEY_B}\ntokenize record.card old"));
+    assert_eq!(rotated.current(), "new");
+    assert_eq!(rotated.token_key(&card), Some("old"));
+    for cipher in [Cipher::new(rotated), Cipher::new(keyring(&format!("old {KEY_A}\nnew {KEY_B}\ntokenize record.card old"))).with_key("old").unwrap()] {
+        let cipher = cipher.tokenize(card.clone());
+        assert_eq!(cipher.encrypt(&card, &number).unwrap(), token);
+        assert_eq!(cipher.decrypt(&card, &token).unwrap(), number);
+    }
+
+    // the first binding that matches wins
+    let keys = keyring(&format!("old {KEY_A}\nnew {KEY_B}\ntokenize record.card old\ntokenize record.* new"));
+    assert_eq!(keys.token_key(&card), Some("old"));
+    assert_eq!(keys.token_key(&field("record.iban")), Some("new"));
+    assert_eq!(keys.token_key(&field("other.card")), None);
+}
+
+#[test]
+fn encrypt_statements_protect_their_fields_in_records() {
+    let program = parse(r#"
+        rule protect {
+            if record.ssn != "" then encrypt;
+            if record.contacts.*.phone starts_with "+49" then encrypt;
+            if record.country == "de" then notify
+        }
+    "#);
+    let keys = format!("k {KEY_A}\ntokenize record.contacts.*.phone k");
+    let cipher = Cipher::new(keyring(&keys)).tokenize(field("record.contacts.*.phone"));
+    let original = record(r#"{"record": {"ssn": "123-45-6789", "country": "de",
+        "contacts": [{"phone": "+49 30 1234567"}, {"phone": "+33 1 23456789"}]}}"#);
+    let mut protected = original.clone();
+    let decisions = Evaluator::with_now(0).evaluate(&program, &protected).unwrap();
+    let done = cipher.apply(&program, &decisions, &mut protected).unwrap();
+    let done: Vec<String> = done.iter().map(ToString::to_string).collect();
+    assert_eq!(done, ["record.ssn", "record.contacts[0].phone"]);
+
+    let json = protected.to_json().to_string();
+    assert!(!json.contains("123-45-6789") && !json.contains("1234567"), "{json}");
+    assert!(json.contains("+33 1 23456789") && json.contains("\"de\""), "unmatched fields are untouched: {json}");
+    let Value::Str(phone) = protected.get(&field("record.contacts[0].phone")) else { panic!("{protected}") };
+    assert!(phone.chars().map(|c| c.is_ascii_digit()).eq("+49 30 1234567".chars().map(|c| c.is_ascii_digit())), "{phone}");
+
+    // only the phone that was tokenized is listed, or the other would be taken for a token too
+    let cipher = Cipher::new(keyring(&keys)).tokenize(field("record.contacts[0].phone"));
+    let mut restored = protected;
+    assert_eq!(cipher.decrypt_record(&mut restored).unwrap(), 2);
+    assert_eq!(restored, original);
+}
+
+#[test]
+fn fields_only_read_to_decide_stay_in_the_clear() {
+    let program = parse(r#"rule r { if record.ssn != "" and record.age > 18 then encrypt }"#);
+    let cipher = Cipher::new(keyring(&format!("k {KEY_A}")));
+    let mut r = record(r#"{"record": {"ssn": "123-45-6789", "age": 40}}"#);
+    let decisions = Evaluator::with_now(0).evaluate(&program, &r).unwrap();
+    assert_eq!(cipher.apply(&program, &decisions, &mut r).unwrap(), [field("record.ssn")]);
+    assert_eq!(r.get(&field("record.age")), &Value::Number(40));
+    assert!(matches!(r.get(&field("record.ssn")), Value::Str(s) if s.starts_with("enc:v1:k:")));
+}
+
+#[test]
+fn keyrings_are_validated() {
+    for (text, message) in [
+        ("", "keyring has no keys"),
+        ("# only a comment\n", "keyring has no keys"),
+        ("k", "keyring line 1: expected `<id> <key>`"),
+        (&format!("bad:id {KEY_A}"), "keyring line 1: key ids may only contain ASCII letters, digits, `-` and `_`"),
+        (&format!("k {KEY_A}\n\nk {KEY_B}"), "keyring line 3: duplicate key id"),
+        ("k 0011", "keyring line 1: keys are 64 hex digits"),
+        (&format!("k {KEY_A}\ntokenize record.card gone"), "keyring line 2: unknown key 'gone'"),
+    ] {
+        assert_eq!(Keyring::parse(text).err().map(|e| e.to_string()).as_deref(), Some(message), "{text:?}");
+    }
+    assert_eq!(keyring(&format!("a {KEY_A}\nb {KEY_B}")).current(), "b");
+    assert!(Cipher::new(keyring(&format!("a {KEY_A}"))).with_key("b").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn keyring_files_must_be_private() {
+    use std::os::unix::fs::PermissionsExt;
+    let path = std::env::temp_dir().join(format!("policy-keyring-{}", std::process::id()));
+    fs::write(&path, format!("k {KEY_A}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    assert!(matches!(Keyring::load(&path), Err(CryptoError::Permissions(_))));
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(Keyring::load(&path).unwrap().current(), "k");
+    fs::remove_file(&path).unwrap();
+}
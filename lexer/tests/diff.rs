@@ -0,0 +1,107 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+fn diff(old: &str, new: &str) -> Vec<String> {
+    diff_programs(&parse(old), &parse(new)).iter().map(|c| c.to_string()).collect()
+}
+
+#[test]
+fn rules_are_added_removed_and_renamed() {
+    let old = "rule a { if x > 1 then delete } rule b { if y == 2 then mask }";
+    let new = "rule b2 { if (y == 2) then mask } rule c { if z < 3 then notify }";
+    assert_eq!(diff(old, new), [
+        "- rule a",
+        "~ rule b renamed to b2",
+        "+ rule c",
+    ]);
+}
+
+#[test]
+fn equivalent_rewrites_are_not_changes() {
+    let old = "rule r { if not (a > 1 or b == 2) then delete }";
+    let new = "rule r { if not a > 1 and not b == 2 then delete }";
+    assert!(diff(old, new).is_empty());
+}
+
+#[test]
+fn statements_are_aligned_and_compared() {
+    let old = r#"
+        rule r {
+            if user.failed_logins > 5 then notify;
+            if record.age > 30 days then delete;
+            if field == "ssn" then mask
+        }
+    "#;
+    let new = r#"
+        rule r {
+            if user.country == "de" then notify;
+            if user.failed_logins > 5 then notify;
+            if record.age > 60 days then delete;
+            if field == "ssn" then encrypt
+        }
+    "#;
+    let changes = diff_programs(&parse(old), &parse(new));
+    assert!(matches!(&changes[0], Change::StatementAdded { index: 0, .. }));
+    assert!(matches!(&changes[1], Change::ConditionChanged { index: 2, effect: Effect::Narrowed, .. }));
+    assert!(matches!(&changes[2], Change::ActionChanged { index: 3, old: Action::Mask, new: Action::Encrypt, .. }));
+    assert_eq!(changes.len(), 3);
+    assert_eq!(changes[1].to_string(), "~ r[2]: condition narrowed: `record.age > 30 days` -> `record.age > 60 days`");
+}
+
+#[test]
+fn broadened_and_unrelated_conditions() {
+    let old = "rule r { if x > 10 then delete; if y == 1 then mask }";
+    let new = "rule r { if x > 5 then delete; if z == 1 then mask }";
+    let effects: Vec<_> = diff_programs(&parse(old), &parse(new)).into_iter().map(|c| match c {
+        Change::ConditionChanged { effect, .. } => effect,
+        other => panic!("unexpected {other}"),
+    }).collect();
+    assert_eq!(effects, [Effect::Broadened, Effect::Changed]);
+}
+
+#[test]
+fn records_treated_differently() {
+    let old = parse("rule r { if record.age > 10 then delete }");
+    let new = parse("rule renamed { if record.age > 20 then delete }");
+    let records = records_from_jsonl(r#"
+        {"record": {"id": "a", "age": 5}}
+        {"record": {"id": "b", "age": 15}}
+        {"record": {"id": "c", "age": 25}}
+    "#).unwrap();
+    let changed = diff_records(&old, &new, &records, &Evaluator::new()).unwrap();
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].id.as_deref(), Some("b"));
+    assert_eq!(changed[0].to_string(), "record 2 (id b): delete (r[0]) -> nothing");
+}
+
+#[test]
+fn records_keep_floats_and_date_strings() {
+    let records = records_from_jsonl(r#"
+        {"record": {"price": 9.99, "big": 18446744073709551615, "created_at": "2024-03-05", "seen": "2024-03-05T10:00:00"}}
+    "#).unwrap();
+    let r = &records[0];
+    assert_eq!(r.get(&Field::from_dotted("record.price")), &Value::Float(9.99));
+    assert_eq!(r.get(&Field::from_dotted("record.created_at")), &Value::from("2024-03-05"));
+    // untouched fields come back as they were written
+    assert_eq!(r.to_json()["record"]["created_at"], "2024-03-05");
+    assert_eq!(r.to_json()["record"]["seen"], "2024-03-05T10:00:00");
+    assert_eq!(r.to_json()["record"]["price"], 9.99);
+
+    let evaluator = Evaluator::with_now(lexer::time::parse_timestamp("2024-04-05").unwrap());
+    for (condition, fires) in [
+        ("record.price > 9", true),
+        ("record.price * 2 < 20", true),
+        ("record.price >= 10", false),
+        ("record.big > 1", true),
+        ("record.created_at < now", true),
+        ("now - record.created_at > 30 days", true),
+        ("record.seen - 1 days > record.created_at", false),
+        ("age(record.created_at) > 40 days", false),
+        ("record.created_at == \"2024-03-05\"", true),
+    ] {
+        let program = parse(&format!("rule r {{ if {condition} then delete }}"));
+        assert_eq!(evaluator.evaluate(&program, r).unwrap().len(), usize::from(fires), "{condition}");
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::*;
+use lexer::docs::{describe, field_index, fields, html, markdown};
+use lexer::*;
+
+#[test]
+fn conditions_read_as_plain_english() {
+    for (src, english) in [
+        ("record.age_in_days > 30 days", "record age in days is greater than 30 days"),
+        ("not user.failed_logins >= 5", "user failed logins is less than 5"),
+        ("not field in [ssn, iban]", "field is not one of \"ssn\", \"iban\""),
+        ("not is_email(user.email)", "user email is not email"),
+        ("not (a == 1 or b == 2)", "it is not the case that (a is 1 or b is 2)"),
+        ("all x in user.tags: x starts_with \"p\"", "for every x in user tags, x starts with \"p\""),
+        ("len(user.name) * 2 < -(now - user.created_at)", "the length of user name times 2 is less than minus (the current time minus user created at)"),
+    ] {
+        assert_eq!(describe(&condition(src)), english, "{src}");
+    }
+}
+
+#[test]
+fn fields_skip_quantifier_variables_and_index_by_rule() {
+    let program = parse(r#"
+        rule a { if any e in user.emails: e.domain == "x" and user.age > 3 then mask; if user.age < 1 then delete }
+        rule b { if user.age > 18 then notify }
+    "#);
+    assert_eq!(fields(&program.rules[0]), ["user.emails", "user.age"]);
+    let index = field_index(&program);
+    assert_eq!(index["user.age"], ["a", "b"]);
+    assert_eq!(index["user.emails"], ["a"]);
+    assert_eq!(index.len(), 2);
+}
+
+#[test]
+fn doc_comments_attach_to_rules() {
+    let program = parse("// not a doc\n/// Masks *everything*.\n///\n/// Really.\nrule r { if x == \"<b>\" then mask }");
+    assert_eq!(program.rules[0].doc.as_deref(), Some("Masks *everything*.\n\nReally."));
+    assert_eq!(parse(&program.to_string()), program);
+
+    let md = markdown("Policy", &program);
+    assert!(md.contains("Masks *everything*.\n\nReally.\n"), "{md}");
+    assert!(md.contains("- **Mask** when x is \"\\<b\\>\"\n"), "{md}");
+    let page = html("Policy", &program);
+    assert!(page.contains("<p>Masks *everything*.</p>\n<p>Really.</p>"), "{page}");
+    assert!(page.contains("<li><strong>Mask</strong> when x is &quot;&lt;b&gt;&quot;</li>"), "{page}");
+    assert!(page.contains("<tr><td><code>x</code></td><td><a href=\"#rule-r\">r</a></td></tr>"), "{page}");
+}
+
+#[test]
+fn doc_comments_must_precede_a_rule() {
+    let err = Parser::new(lex("/// dangling\ntest \"t\" { given {} expect nothing }").unwrap()).parse_program().unwrap_err();
+    assert!(matches!(err, ParseError::Expected { .. }), "{err:?}");
+    assert!(Parser::new(lex("rule r { /// inside\n if x == 1 then mask }").unwrap()).parse_program().is_err());
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use lexer::*;
+
+/// A policy file in a fresh temporary directory.
+fn policy_file(name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("policy-engine-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("policy.policy");
+    fs::write(&path, text).unwrap();
+    path
+}
+
+/// Replace the file atomically, as editors and deploy tools do, so the watcher
+/// never sees it half-written.
+fn replace(path: &PathBuf, text: &str) {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text).unwrap();
+    fs::rename(tmp, path).unwrap();
+}
+
+fn record() -> Value {
+    [("user", [("failed_logins", Value::Number(7))].into_iter().collect::<Value>())].into_iter().collect()
+}
+
+fn rules(decisions: &[Decision]) -> Vec<&str> {
+    decisions.iter().map(|d| d.rule.as_str()).collect()
+}
+
+const V1: &str = "rule a { if user.failed_logins > 5 then notify }";
+const V2: &str = "rule b { if user.failed_logins > 5 then mask }";
+
+#[test]
+fn reload_swaps_valid_versions_and_keeps_the_old_one_on_error() {
+    let path = policy_file("reload", V1);
+    let engine = PolicyEngine::load(&path).unwrap();
+    let evaluator = Evaluator::with_now(0);
+    assert_eq!(rules(&engine.evaluate(&evaluator, &record()).unwrap()), ["a"]);
+    assert!(engine.reload().unwrap().is_none(), "unchanged text is not recompiled");
+
+    let before = engine.snapshot();
+    fs::write(&path, V2).unwrap();
+    assert_eq!(engine.reload().unwrap().unwrap().version, 2);
+    assert_eq!(rules(&engine.evaluate(&evaluator, &record()).unwrap()), ["b"]);
+    // a snapshot taken earlier still sees its own version
+    assert_eq!(before.version, 1);
+    assert_eq!(rules(&evaluator.evaluate(&before.program, &record()).unwrap()), ["a"]);
+
+    for (broken, message) in [
+        ("rule c { if user.name == \"x then mask }", "policy.policy:1:26: unterminated string starting at byte 25"),
+        ("rule c { if user.name == then mask }", "expected identifier, found Keyword(Then)"),
+        ("rule c { if nope(user.name) then mask }", "rule 'c', statement 0"),
+    ] {
+        fs::write(&path, broken).unwrap();
+        let err = engine.reload().unwrap_err();
+        assert!(err.to_string().contains(message), "{err}");
+        assert!(engine.last_error().is_some());
+        assert_eq!(engine.snapshot().version, 2);
+        assert_eq!(rules(&engine.evaluate(&evaluator, &record()).unwrap()), ["b"]);
+    }
+    // the same broken text is not reported again
+    assert!(engine.reload().unwrap().is_none());
+
+    fs::write(&path, V1).unwrap();
+    assert_eq!(engine.reload().unwrap().unwrap().version, 3);
+    assert!(engine.last_error().is_none());
+
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(*engine.reload().unwrap_err(), EngineError::Io { .. }));
+    assert!(engine.reload().unwrap().is_none());
+    assert_eq!(engine.snapshot().version, 3);
+}
+
+#[test]
+fn load_fails_on_an_invalid_file() {
+    let path = policy_file("load", "rule r { if x > then delete }");
+    assert!(matches!(PolicyEngine::load(&path), Err(EngineError::Parse { .. })));
+    assert!(matches!(PolicyEngine::load(path.with_extension("missing")), Err(EngineError::Io { .. })));
+}
+
+#[test]
+fn watcher_picks_up_changes() {
+    let path = policy_file("watch", V1);
+    let engine = PolicyEngine::load(&path).unwrap();
+    let (tx, rx) = mpsc::channel();
+    let watcher = engine.watch(Duration::from_millis(5), move |outcome| {
+        tx.send(outcome.map(|s| s.version).map_err(|e| e.to_string())).unwrap();
+    });
+    replace(&path, "rule broken {");
+    assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap().is_err());
+    replace(&path, V2);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), Ok(2));
+    drop(watcher);
+    replace(&path, V1);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(engine.snapshot().version, 2, "dropping the watcher stops it");
+}
+
+#[test]
+fn evaluations_never_mix_versions() {
+    // every rule of a version fires, so a consistent evaluation sees all of one version's rules
+    let version = |tag: &str| (0..20).map(|i| format!("rule {tag}{i} {{ if user.failed_logins > 5 then notify }}\n")).collect::<String>();
+    let (va, vb) = (version("a"), version("b"));
+    let path = policy_file("consistent", &va);
+    let engine = PolicyEngine::load(&path).unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let readers: Vec<_> = (0..4).map(|_| {
+        let (engine, done) = (engine.clone(), done.clone());
+        thread::spawn(move || {
+            let evaluator = Evaluator::with_now(0);
+            while !done.load(Ordering::Relaxed) {
+                let decisions = engine.evaluate(&evaluator, &record()).unwrap();
+                let first = decisions[0].rule.as_bytes()[0];
+                assert_eq!(decisions.len(), 20);
+                assert!(decisions.iter().all(|d| d.rule.as_bytes()[0] == first));
+            }
+        })
+    }).collect();
+    for i in 0..50 {
+        fs::write(&path, if i % 2 == 0 { &vb } else { &va }).unwrap();
+        engine.reload().unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    for r in readers { r.join().unwrap(); }
+    assert_eq!(engine.snapshot().version, 51);
+}
+
+#[test]
+fn directories_compile_their_policy_files_in_name_order() {
+    let dir = policy_file("directory", V2).parent().unwrap().to_path_buf();
+    fs::write(dir.join("a.policy"), V1).unwrap();
+    fs::write(dir.join("readme.md"), "not a policy").unwrap();
+    let engine = PolicyEngine::load(&dir).unwrap();
+    let evaluator = Evaluator::with_now(0);
+    assert_eq!(rules(&engine.evaluate(&evaluator, &record()).unwrap()), ["a", "b"]);
+
+    // a new file is a change; an error names the file
+    fs::write(dir.join("c.policy"), "rule c {").unwrap();
+    let err = engine.reload().unwrap_err();
+    assert!(err.to_string().starts_with(&dir.join("c.policy").display().to_string()), "{err}");
+    replace(&dir.join("c.policy"), "rule c { if user.failed_logins > 5 then delete }");
+    assert_eq!(engine.reload().unwrap().unwrap().version, 2);
+    // `b` is in policy.policy
+    assert_eq!(rules(&engine.evaluate(&evaluator, &record()).unwrap()), ["a", "c", "b"]);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+#[test]
+fn null_arguments_short_circuit_every_function() {
+    let evaluator = Evaluator::with_now(0);
+    let r = record(r#"{"user": {"name": "Ann", "tags": ["a", "b"]}}"#);
+    for (condition, fires) in [
+        ("len(user.name) == 3", true),
+        ("len(user.tags) == 2", true),
+        // `len` takes anything, and still sees no `null`
+        ("len(user.email) > 3", false),
+        ("not len(user.email) > 3", true),
+        ("len(user.email) == 0", false),
+        ("lower(user.email) == \"\"", false),
+        ("is_email(user.email)", false),
+        ("age(user.born) > 0 seconds", false),
+    ] {
+        let program = parse(&format!("rule r {{ if {condition} then notify }}"));
+        check_program(&program).unwrap();
+        assert_eq!(evaluator.evaluate(&program, &r).unwrap().len(), usize::from(fires), "{condition}");
+    }
+    let err = evaluator.evaluate(&parse("rule r { if len(user) > 1 then notify }"), &r).unwrap_err();
+    assert_eq!(err.to_string(), "in call to 'len': expected string or list, found map");
+}
+
+#[test]
+fn host_functions_check_and_evaluate() {
+    let mut evaluator = Evaluator::with_now(0);
+    evaluator.register("domain", vec![Type::String], Type::String, |_, args| match &args[0] {
+        Value::Str(s) => Ok(Value::from(s.rsplit_once('@').map_or("", |(_, d)| d))),
+        _ => unreachable!("argument types are checked before the call"),
+    });
+    let program = parse(r#"rule r { if domain(user.email) == "example.com" then mask }"#);
+
+    // the built-ins don't know it, the evaluator that calls it does
+    assert!(check_program(&program).unwrap_err().to_string().contains("unknown function 'domain'"));
+    evaluator.check(&program).unwrap();
+    let bad = parse("rule r { if domain(len(user.name)) == \"x\" then mask }");
+    assert!(evaluator.check(&bad).unwrap_err().to_string().contains("argument 0 of 'domain' must be string, found number"));
+
+    let fired = |json: &str| evaluator.evaluate(&program, &record(json)).unwrap().len();
+    assert_eq!(fired(r#"{"user": {"email": "ann@example.com"}}"#), 1);
+    assert_eq!(fired(r#"{"user": {"email": "ann@example.org"}}"#), 0);
+    assert_eq!(fired(r#"{"user": {}}"#), 0);
+
+    let request = serde_json::json!({
+        "op": "evaluate", "source": program.to_string(), "now": 0,
+        "records": [{ "user": { "email": "ann@example.com" } }],
+    });
+    assert_eq!(api::handle(&request)["ok"], false);
+    let out = api::handle_with(&request, &evaluator);
+    assert_eq!(out["results"][0]["decisions"][0]["action"], "mask", "{out}");
+}
@@ -0,0 +1,71 @@
+//! Golden snapshots of `lex`, `Parser::parse_program` and `docs::markdown`
+//! output for the sample policies in `tests/golden`. Set `UPDATE_GOLDEN=1` to
+//! rewrite the snapshots.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lexer::{docs, lex, Parser};
+
+fn policies(dir: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(dir);
+    let mut paths: Vec<_> = fs::read_dir(dir).unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "policy"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no sample policies found");
+    paths
+}
+
+fn check_snapshot(path: &Path, actual: &str) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(path)
+        .unwrap_or_else(|_| panic!("missing snapshot {}; run with UPDATE_GOLDEN=1", path.display()));
+    assert_eq!(expected, actual, "snapshot {} is out of date", path.display());
+}
+
+#[test]
+fn sample_policies_match_snapshots() {
+    for path in policies("") {
+        let src = fs::read_to_string(&path).unwrap();
+        let tokens = lex(&src).unwrap();
+        let listing: String = tokens.iter().map(|t| format!("{t:?}\n")).collect();
+        check_snapshot(&path.with_extension("tokens"), &listing);
+
+        let program = Parser::new(tokens).parse_program().unwrap();
+        check_snapshot(&path.with_extension("ast"), &format!("{program:#?}\n"));
+
+        let title = path.file_stem().unwrap().to_string_lossy();
+        check_snapshot(&path.with_extension("md"), &docs::markdown(&title, &program));
+    }
+}
+
+#[test]
+fn sample_policies_print_back_to_the_same_ast() {
+    for path in policies("") {
+        let src = fs::read_to_string(&path).unwrap();
+        let program = Parser::new(lex(&src).unwrap()).parse_program().unwrap();
+        let printed = program.to_string();
+        let reparsed = Parser::new(lex(&printed).unwrap()).parse_program().unwrap();
+        assert_eq!(program, reparsed, "{}:\n{printed}", path.display());
+    }
+}
+
+#[test]
+fn invalid_policies_match_error_snapshots() {
+    for path in policies("errors") {
+        let src = fs::read_to_string(&path).unwrap();
+        let err = match lex(&src) {
+            Err(e) => format!("lex error: {e}\n"),
+            Ok(tokens) => match Parser::new(tokens).parse_program() {
+                Err(e) => format!("parse error: {e}\n"),
+                Ok(_) => panic!("{} parsed successfully", path.display()),
+            },
+        };
+        check_snapshot(&path.with_extension("err"), &err);
+    }
+}
@@ -0,0 +1,130 @@
+//! Property: a `Document` after any sequence of edits holds the same tokens,
+//! spans and program (or error) as a full lex and parse of its text.
+
+use lexer::*;
+use proptest::prelude::*;
+
+const SAMPLE: &str = r#"rule delete_old_data {
+    if record.age_in_days > 30 days then delete;
+    if field in [ssn, credit_card] and not user.is_admin == true then mask
+}
+
+rule alert_weird {
+    if (user.country == "blocked") or user.failed_logins >= 5 then notify
+}
+
+test "admins" {
+    given { user.is_admin: true, field: ssn }
+    expect no mask
+}
+
+// comments are whitespace; doc comments attach to the next rule
+/// Names longer than three characters.
+rule last { if len(user.name) > 3 then encrypt }
+"#;
+
+fn assert_matches_full(doc: &Document) {
+    let text = doc.text();
+    let spanned: Result<Vec<_>, _> = Lexer::new(text).collect();
+    let spans: Vec<_> = spanned.as_ref().map(|ts| ts.iter().map(|(_, s)| s.clone()).collect()).unwrap_or_default();
+    let tokens = spanned.map(|ts| ts.into_iter().map(|(t, _)| t).collect::<Vec<_>>());
+    let program = match &tokens {
+        Ok(ts) => Parser::new(ts.clone()).parse_program().map_err(DocumentError::from),
+        Err(e) => Err(e.clone().into()),
+    };
+    assert_eq!(doc.tokens().map(<[Token]>::to_vec).map_err(Clone::clone), tokens, "tokens of {text:?}");
+    assert_eq!(doc.spans(), spans.as_slice(), "spans of {text:?}");
+    assert_eq!(doc.program(), program, "program of {text:?}");
+}
+
+/// Snap a byte offset down to a character boundary.
+fn floor(text: &str, mut i: usize) -> usize {
+    i = i.min(text.len());
+    while !text.is_char_boundary(i) { i -= 1; }
+    i
+}
+
+fn snippet() -> impl Strategy<Value = String> {
+    let pieces = prop::sample::select(vec![
+        "", " ", "\n", "x", "rule", "rule r {", "}", "{", "\"", "\\", "==", "=", ">", "and", "or not ",
+        "if a > 1 then mask;", "//", "///", "/// doc\n", "test \"t\" { given { a: 1 } expect nothing }", "12", "days", "é", "(", ")", ";",
+    ]);
+    prop::collection::vec(pieces, 0..3).prop_map(|ps| ps.concat())
+}
+
+proptest! {
+    #[test]
+    fn edits_match_a_full_reparse(edits in prop::collection::vec((0usize..400, 0usize..12, snippet()), 1..8)) {
+        let mut doc = Document::new(SAMPLE);
+        for (at, len, text) in edits {
+            let start = floor(doc.text(), at);
+            let end = floor(doc.text(), start + len);
+            doc.edit(start..end, &text);
+            assert_matches_full(&doc);
+        }
+    }
+}
+
+#[test]
+fn editing_one_rule_reparses_only_that_rule() {
+    let mut doc = Document::new(SAMPLE);
+    let at = SAMPLE.find("5 then notify").unwrap();
+    doc.edit(at..at + 1, "10");
+    assert_eq!(doc.stats(), EditStats { relexed_tokens: 1, reparsed_items: 1 });
+    assert_matches_full(&doc);
+    let program = doc.program().unwrap();
+    assert_eq!(program.rules[1].statements[0].to_string(),
+               "if (user.country == \"blocked\") or user.failed_logins >= 10 then notify");
+}
+
+#[test]
+fn whitespace_edits_reparse_nothing() {
+    let mut doc = Document::new(SAMPLE);
+    let at = SAMPLE.find("\n\nrule alert_weird").unwrap();
+    doc.edit(at + 1..at + 1, "\n\n   ");
+    assert_eq!(doc.stats(), EditStats { relexed_tokens: 0, reparsed_items: 0 });
+    assert_matches_full(&doc);
+}
+
+#[test]
+fn unterminated_strings_and_recovery() {
+    let mut doc = Document::new(SAMPLE);
+    let at = SAMPLE.find("\"blocked\"").unwrap();
+    doc.edit(at..at + 1, "");
+    assert!(doc.tokens().is_err());
+    assert_matches_full(&doc);
+    doc.edit(at..at, "\"");
+    assert_eq!(doc.text(), SAMPLE);
+    assert_matches_full(&doc);
+}
+
+#[test]
+fn opening_and_closing_comments() {
+    let mut doc = Document::new(SAMPLE);
+    let at = SAMPLE.find("rule last").unwrap();
+    // the doc comment is left without a rule
+    doc.edit(at..at, "// ");
+    assert!(doc.program().is_err());
+    assert_matches_full(&doc);
+    doc.edit(at..at + 3, "");
+    assert_eq!(doc.program().unwrap().rules[2].doc.as_deref(), Some("Names longer than three characters."));
+    assert_matches_full(&doc);
+    // joining the comment line with the next one swallows the rule's doc
+    let newline = SAMPLE.find("rule\n").unwrap() + 4;
+    doc.edit(newline..newline + 1, " ");
+    assert_matches_full(&doc);
+    assert_eq!(doc.program().unwrap().rules[2].doc, None);
+}
+
+#[test]
+fn splitting_and_merging_rules() {
+    let mut doc = Document::new(SAMPLE);
+    let at = SAMPLE.find("rule alert_weird").unwrap();
+    // delete the closing brace of the first rule: it swallows the next one
+    doc.edit(at - 3..at - 2, "");
+    assert!(doc.program().is_err());
+    assert_matches_full(&doc);
+    doc.edit(at - 3..at - 3, "}");
+    assert_eq!(doc.program().unwrap().rules.len(), 3);
+    assert_matches_full(&doc);
+}
@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+use lexer::*;
+
+#[test]
+fn identifiers_and_plain_strings_borrow_from_the_source() {
+    let src = r#"rule r { if user.name == "bob" or x == "a\"b" then mask }"#;
+    let tokens = lex(src).unwrap();
+    assert!(tokens.iter().all(|t| match t {
+        Token::Ident(s) => matches!(s, Cow::Borrowed(_)),
+        Token::Str(s) => matches!(s, Cow::Borrowed(_)) == !s.contains('"'),
+        _ => true,
+    }));
+    assert_eq!(tokens[0], Token::Keyword(Keyword::Rule));
+    assert!(tokens.contains(&Token::Str(Cow::Owned("a\"b".into()))));
+}
+
+#[test]
+fn keywords_and_operators_are_enums() {
+    for k in Keyword::ALL {
+        assert_eq!(Keyword::lookup(k.as_str()), Some(k));
+        assert_eq!(lex(k.as_str()).unwrap(), [Token::Keyword(k)]);
+    }
+    assert_eq!(Keyword::lookup("rules"), None);
+    let ops: Vec<_> = lex("== != >= <= ** = > < + - * / % >==").unwrap();
+    use Op::*;
+    assert_eq!(ops, [EqEq, NotEq, GtEq, LtEq, StarStar, Eq, Gt, Lt, Plus, Minus, Star, Slash, Percent, GtEq, Eq]
+        .map(Token::Operator));
+}
+
+#[test]
+fn spans_are_byte_ranges() {
+    let src = "é_x  >=\t\"s\" 42";
+    let spans: Vec<_> = Lexer::new(src).map(|t| t.unwrap().1).collect();
+    assert_eq!(spans, [0..4, 6..8, 9..12, 13..15]);
+    assert_eq!(&src[spans[0].clone()], "é_x");
+}
+
+#[test]
+fn errors_report_byte_offsets() {
+    assert_eq!(lex("é ?").unwrap_err(), LexError::UnexpectedChar('?', 3));
+    assert_eq!(lex("\"é\\n\"").unwrap_err(), LexError::UnexpectedChar('n', 4));
+    assert_eq!(lex("x = \"open").unwrap_err(), LexError::Unterminated(4));
+}
+
+#[test]
+fn identifiers_follow_uax31() {
+    assert_eq!(lex("l·l _x ñ2 日本").unwrap(),
+               ["l·l", "_x", "ñ2", "日本"].map(|s| Token::Ident(s.into())));
+    // combining marks continue an identifier but cannot start one
+    assert_eq!(lex("e\u{301}").unwrap(), [Token::Ident("e\u{301}".into())]);
+    assert_eq!(lex("\u{301}e").unwrap_err(), LexError::UnexpectedChar('\u{301}', 0));
+    assert_eq!(lex("a🙂").unwrap_err(), LexError::UnexpectedChar('🙂', 1));
+}
+
+#[test]
+fn line_index_converts_offsets() {
+    let src = "rule r {\n  if né > 1\n}";
+    let index = LineIndex::new(src);
+    let at = |line, col| LineCol { line, col };
+    assert_eq!(index.line_col(0), at(1, 1));
+    assert_eq!(index.line_col(8), at(1, 9));
+    assert_eq!(index.line_col(9), at(2, 1));
+    // `é` is two bytes but one column
+    let gt = src.find('>').unwrap();
+    assert_eq!(index.line_col(gt), at(2, 9));
+    assert_eq!(index.line_col(src.len()), at(3, 2));
+    for offset in (0..=src.len()).filter(|&i| src.is_char_boundary(i)) {
+        assert_eq!(index.offset(index.line_col(offset)), Some(offset), "{offset}");
+    }
+    assert_eq!(index.offset(at(2, 20)), None);
+    assert_eq!(index.offset(at(4, 1)), None);
+    assert_eq!(index.offset(at(0, 1)), None);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+fn date(s: &str) -> Value {
+    Value::Timestamp(lexer::time::parse_timestamp(s).unwrap())
+}
+
+#[test]
+fn strategies_on_strings_numbers_and_dates() {
+    use MaskStrategy::*;
+    let masker = Masker::new(b"salt");
+    let f = field("record.x");
+    let mask = |strategy, value: Value| masker.mask(&f, strategy, &value).map_err(|e| e.to_string());
+    for (strategy, value, expected) in [
+        (Redact, Value::from("secret"), Value::from("******")),
+        (Redact, Value::from("naïve"), Value::from("*****")),
+        (Redact, Value::Number(42), Value::Null),
+        (Redact, date("2024-05-17"), Value::Null),
+        (KeepLast(4), Value::from("4111 1111 1111 1234"), Value::from("***************1234")),
+        (KeepLast(4), Value::from("12"), Value::from("12")),
+        (KeepLast(2), Value::Number(-98765), Value::from("****65")),
+        (KeepLast(0), Value::from("abc"), Value::from("***")),
+        (Email, Value::from("alice@example.com"), Value::from("a****@example.com")),
+        (Email, Value::from("é@x.org"), Value::from("é@x.org")),
+        (Email, Value::from("\"a@b\"@example.com"), Value::from("\"****@example.com")),
+        (Email, Value::from("not an address"), Value::from("**************")),
+        (Month, date("2024-05-17T13:45:00Z"), date("2024-05-01")),
+        (Year, date("2024-05-17T13:45:00Z"), date("2024-01-01")),
+        (Year, date("1969-12-31T23:59:59Z"), date("1969-01-01")),
+    ] {
+        assert_eq!(mask(strategy, value.clone()), Ok(expected), "{strategy} of {value}");
+    }
+    for (strategy, value, message) in [
+        (KeepLast(4), date("2024-05-17"), "record.x: cannot mask a timestamp with `last 4`"),
+        (Email, Value::Number(1), "record.x: cannot mask a number with `email`"),
+        (Month, Value::from("2024-05"), "record.x: cannot mask a string with `month`"),
+    ] {
+        assert_eq!(mask(strategy, value), Err(message.to_string()));
+    }
+}
+
+#[test]
+fn hashes_are_salted_and_stable_so_joins_still_work() {
+    let f = field("record.customer_id");
+    let (a, b) = (Masker::new(b"one"), Masker::new(b"two"));
+    let hash = |m: &Masker, v: Value| m.mask(&f, MaskStrategy::Hash, &v).unwrap();
+
+    let id = hash(&a, Value::Number(1234));
+    assert!(matches!(id, Value::Number(n) if n >= 0), "numbers hash to numbers: {id}");
+    assert_eq!(hash(&a, Value::Number(1234)), id);
+    assert_ne!(hash(&a, Value::Number(1235)), id);
+    assert_ne!(hash(&b, Value::Number(1234)), id);
+
+    let email = hash(&a, Value::from("alice@example.com"));
+    assert!(matches!(&email, Value::Str(s) if s.len() == 64 && s.bytes().all(|c| c.is_ascii_hexdigit())), "{email}");
+    assert_eq!(hash(&a, Value::from("alice@example.com")), email);
+    assert!(matches!(hash(&a, date("2024-05-17")), Value::Str(_)));
+}
+
+#[test]
+fn statements_choose_strategies_and_types_pick_defaults() {
+    let program = parse(r#"
+        rule support {
+            if record.card != "" then mask(last 4);
+            if record.contacts.*.email ends_with ".com" then mask(email);
+            if record.birth_date < now then mask;
+            if record.name != "" and record.vip == false then mask;
+            if record.card != "" then notify
+        }
+    "#);
+    let masker = Masker::new(b"salt").default_for(Type::Timestamp, MaskStrategy::Year);
+    let mut r = record(r#"{"record": {"card": "4111-1111-1111-1234", "name": "Alice", "vip": false,
+        "birth_date": "1990-07-04", "notes": "untouched",
+        "contacts": [{"email": "alice@example.com"}, {"email": "alice@example.org"}]}}"#);
+    let decisions = Evaluator::with_now(2_000_000_000).evaluate(&program, &r).unwrap();
+    let masked = masker.apply(&program, &decisions, &mut r).unwrap();
+    let masked: Vec<String> = masked.iter().map(ToString::to_string).collect();
+    assert_eq!(masked, ["record.card", "record.contacts[0].email", "record.birth_date", "record.name"]);
+    assert_eq!(r, record(r#"{"record": {"card": "***************1234", "name": "*****", "vip": false,
+        "birth_date": "1990-01-01", "notes": "untouched",
+        "contacts": [{"email": "a****@example.com"}, {"email": "alice@example.org"}]}}"#));
+
+    // a strategy the value doesn't support is an error, not a silent pass-through
+    let program = parse("rule r { if record.signup < now then mask(email) }");
+    let mut r = record(r#"{"record": {"signup": "2020-01-01"}}"#);
+    let decisions = Evaluator::with_now(2_000_000_000).evaluate(&program, &r).unwrap();
+    let err = Masker::new(b"").apply(&program, &decisions, &mut r).unwrap_err();
+    assert_eq!(err.to_string(), "record.signup: cannot mask a timestamp with `email`");
+}
+
+#[test]
+fn fields_only_read_to_decide_are_left_alone() {
+    let program = parse(r#"rule r { if record.ssn != "" and record.age > 18 then mask; if record.items.*.code != "" and record.id > 0 then mask }"#);
+    let mut r = record(r#"{"record": {"ssn": "123-45-6789", "age": 40, "id": 7, "items": [{"code": "ab"}]}}"#);
+    let decisions = Evaluator::with_now(0).evaluate(&program, &r).unwrap();
+    let masked = Masker::new(b"salt").apply(&program, &decisions, &mut r).unwrap();
+    assert_eq!(masked, [field("record.ssn"), field("record.items[0].code")]);
+    assert_eq!(r, record(r#"{"record": {"ssn": "***********", "age": 40, "id": 7, "items": [{"code": "**"}]}}"#));
+}
+
+#[test]
+fn strategies_parse_print_and_diff() {
+    let old = parse("rule r { if record.card != \"\" then mask(last 4); if record.ssn != \"\" then mask }");
+    assert_eq!(old.rules[0].statements[0].mask, Some(MaskStrategy::KeepLast(4)));
+    assert_eq!(old.rules[0].statements[0].to_string(), "if record.card != \"\" then mask(last 4)");
+    for (src, message) in [
+        ("rule r { if a > 1 then mask(last) }", "expected character count, found Symbol(')')"),
+        ("rule r { if a > 1 then mask(last 99999999999) }", "expected character count, found Number(99999999999)"),
+        ("rule r { if a > 1 then delete(redact) }", "expected"),
+    ] {
+        let err = Parser::new(lex(src).unwrap()).parse_program().unwrap_err();
+        assert!(err.to_string().contains(message), "{src}: {err}");
+    }
+
+    let new = parse("rule r { if record.card != \"\" then mask(last 2); if record.ssn != \"\" then mask(hash) }");
+    let changes: Vec<String> = diff_programs(&old, &new).iter().map(ToString::to_string).collect();
+    assert_eq!(changes, ["~ r[0]: mask strategy last 4 -> last 2", "~ r[1]: mask strategy default -> hash"]);
+
+    let (_, errors) = to_sql(&old, &Schema::new("users").row("record").protect("card"), Dialect::Sqlite);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().contains("mask(last 4)"), "{}", errors[0]);
+}
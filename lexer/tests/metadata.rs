@@ -0,0 +1,58 @@
+use lexer::*;
+use serde_json::json;
+
+fn parse(src: &str) -> Result<Program, ParseError> {
+    Parser::new(lex(src).unwrap()).parse_program()
+}
+
+const SOURCE: &str = r#"
+    rule delete_old_data [owner: "data-team", severity: high, tags: [gdpr, retention]] {
+        if record.old == true then delete
+    }
+    rule mask_ssn [tags: pii] { if record.ssn != "" then mask(redact) }
+    rule plain { if record.x == 1 then notify }
+"#;
+
+#[test]
+fn metadata_parses_and_prints() {
+    let program = parse(SOURCE).unwrap();
+    let rule = &program.rules[0];
+    assert_eq!(rule.meta("owner"), Some(&Literal::Str("data-team".into())));
+    assert_eq!(rule.meta("severity"), Some(&Literal::Str("high".into())));
+    assert_eq!(rule.meta("missing"), None);
+    assert_eq!(rule.tags(), ["gdpr", "retention"]);
+    assert_eq!(program.rules[1].tags(), ["pii"]);
+    assert!(program.rules[2].tags().is_empty());
+
+    let printed = program.to_string();
+    assert!(printed.contains(r#"rule delete_old_data [owner: "data-team", severity: "high", tags: ["gdpr", "retention"]] {"#), "{printed}");
+    assert_eq!(parse(&printed).unwrap(), program);
+}
+
+#[test]
+fn bad_metadata_is_a_parse_error() {
+    for (src, expected) in [
+        (r#"rule r [owner: "a", owner: "b"] {}"#, "metadata key 'owner' given twice"),
+        (r#"rule r [owner "a"] {}"#, "':'"),
+        (r#"rule r [owner: "a",] {}"#, "identifier"),
+    ] {
+        let err = parse(src).unwrap_err();
+        assert!(err.to_string().contains(expected), "{src}: {err}");
+    }
+}
+
+#[test]
+fn decisions_carry_their_rule_metadata() {
+    let program = parse(SOURCE).unwrap();
+    let record = Value::from_json(&json!({ "record": { "old": true, "x": 1 } })).unwrap();
+    let decisions = Evaluator::with_now(0).evaluate(&program, &record).unwrap();
+    let rule = decisions[0].rule(&program).unwrap();
+    assert_eq!(rule.meta("owner"), Some(&Literal::Str("data-team".into())));
+
+    let out: serde_json::Value = serde_json::from_str(&api::call(&json!({
+        "op": "evaluate", "source": SOURCE, "records": [{ "record": { "old": true, "x": 1 } }], "now": 0,
+    }).to_string())).unwrap();
+    let decisions = &out["results"][0]["decisions"];
+    assert_eq!(decisions[0]["meta"], json!({ "owner": "data-team", "severity": "high", "tags": ["gdpr", "retention"] }));
+    assert_eq!(decisions.as_array().unwrap().last().unwrap()["meta"], json!({}));
+}
@@ -0,0 +1,49 @@
+//! `lex` and `Parser::parse_program` must reject bad input with an error, never
+//! a panic. See also the `fuzz/` targets, which run the same check under libFuzzer.
+
+use lexer::{lex, LexError, ParseError, Parser, MAX_DEPTH};
+use proptest::prelude::*;
+
+fn lex_and_parse(src: &str) {
+    if let Ok(tokens) = lex(src) {
+        let _ = Parser::new(tokens).parse_program();
+    }
+}
+
+/// Fragments of DSL syntax, so random input gets past the lexer more often.
+fn token_soup() -> impl Strategy<Value = String> {
+    let fragment = prop::sample::select(vec![
+        "rule", "r", "{", "}", "if", "then", "delete", "mask", "and", "or", "not", "in", "any", "all",
+        "now", "(", ")", "[", "]", ",", ".", ":", ";", "*", "**", "-", "+", "==", ">", "1", "30 days",
+        "\"s\"", "f(", "x", "starts_with",
+    ]);
+    prop::collection::vec(fragment, 0..64).prop_map(|parts| parts.join(" "))
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        lex_and_parse(&String::from_utf8_lossy(&bytes));
+    }
+
+    #[test]
+    fn token_soup_never_panics(src in token_soup()) {
+        lex_and_parse(&src);
+    }
+}
+
+#[test]
+fn huge_number_is_a_lex_error() {
+    let err = lex("rule r { if x > 99999999999999999999 then delete }").unwrap_err();
+    assert!(matches!(err, LexError::NumberOverflow(16)), "{err:?}");
+}
+
+#[test]
+fn deep_nesting_is_a_parse_error() {
+    for (open, close) in [("(", ")"), ("not ", ""), ("-", "")] {
+        let depth = MAX_DEPTH * 4;
+        let src = format!("rule r {{ if {}x > 1{} then delete }}", open.repeat(depth), close.repeat(depth));
+        let err = Parser::new(lex(&src).unwrap()).parse_program().unwrap_err();
+        assert!(matches!(err, ParseError::TooDeep), "{open}: {err:?}");
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+fn overlay(src: &str) -> Overlay {
+    Parser::new(lex(src).unwrap()).parse_overlay().unwrap()
+}
+
+const BASE: &str = r#"
+    /// Old records go.
+    rule retention { if now - record.created_at > 365 days then delete }
+    rule pii { if record.ssn != "" then mask }
+    rule audit { if record.flagged == true then notify }
+    test "old" { given { record.created_at: "2000-01-01" } expect delete }
+"#;
+
+#[test]
+fn overlays_add_override_and_disable_rules() {
+    let acme = overlay(r#"
+        rule cards { if record.card starts_with "4" then encrypt }
+        override rule retention { if now - record.created_at > 90 days then delete }
+        disable rule audit;
+        test "cards" { given { record.card: "4111" } expect encrypt }
+    "#);
+    let program = resolve(&parse(BASE), &acme).unwrap();
+    let names: Vec<&str> = program.rules.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["retention", "pii", "cards"]);
+    // an override without a doc comment keeps the base rule's
+    assert_eq!(program.rules[0].doc.as_deref(), Some("Old records go."));
+    assert_eq!(program.rules[0].statements[0].condition.to_string(), "now - record.created_at > 90 days");
+    assert_eq!(program.tests.len(), 2);
+    assert!(run_tests(&program, &Evaluator::with_now(time::parse_timestamp("2026-01-01").unwrap())).unwrap().iter().all(|r| r.passed()));
+}
+
+#[test]
+fn mistakes_are_errors() {
+    let base = parse(BASE);
+    for (src, expected) in [
+        ("rule pii { if record.a == 1 then mask }", OverlayError::AlreadyInBase("pii".into())),
+        ("override rule pi { if record.a == 1 then mask }", OverlayError::NotInBase { verb: "override", rule: "pi".into() }),
+        ("disable rule nope", OverlayError::NotInBase { verb: "disable", rule: "nope".into() }),
+        ("disable rule pii; disable rule pii", OverlayError::Repeated("pii".into())),
+    ] {
+        assert_eq!(resolve(&base, &overlay(src)), Err(expected), "{src}");
+    }
+    let err = Parser::new(lex("override test \"t\" { given {} expect nothing }").unwrap()).parse_overlay().unwrap_err();
+    assert!(err.to_string().contains("rule after `override`"), "{err}");
+    // overlay directives aren't part of plain programs
+    assert!(Parser::new(lex("disable rule pii").unwrap()).parse_program().is_err());
+}
+
+#[test]
+fn overlays_print_back_to_the_same_overlay() {
+    let o = overlay("/// Shorter.\noverride rule retention on sunday { if record.a == 1 then delete }\ndisable rule audit\nrule x { }\n");
+    assert_eq!(o.to_string(), "\
+/// Shorter.
+override rule retention on sunday {
+    if record.a == 1 then delete;
+}
+
+disable rule audit;
+
+rule x {
+}
+");
+    assert_eq!(overlay(&o.to_string()), o);
+}
+
+#[test]
+fn report_shows_where_tenants_differ() {
+    let tenants = Tenants::new(parse(BASE))
+        .tenant("globex", Overlay::default())
+        .tenant("acme", overlay(r#"
+            override rule pii { if record.ssn != "" then mask(last 4) }
+            disable rule audit
+            rule audit_all { if record.flagged == true then notify }
+        "#));
+    let reports: Vec<String> = tenants.report().unwrap().iter().map(|r| r.to_string()).collect();
+    // overridden rules are compared on their own, so disabling one rule and
+    // adding an identical one isn't reported as a rename
+    assert_eq!(reports, [
+        "tenant acme: 3 changes\n  ~ pii[0]: mask strategy default -> last 4\n  - rule audit\n  + rule audit_all\n",
+        "tenant globex: same as base\n",
+    ]);
+    assert_eq!(tenants.effective("globex").unwrap(), *tenants.base());
+    assert_eq!(tenants.effective("initech"), Err(TenantError::Unknown("initech".into())));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+const PEOPLE: &str = r#"{"record": {"ssn": "1", "owner": {"ssn": "2", "name": "Ann"},
+    "people": [{"ssn": "3", "kids": [{"ssn": ""}]}, {"name": "Bo"}], "meta": {"ssn_note": "x"}}}"#;
+
+fn paths(fields: &[Field]) -> Vec<String> {
+    fields.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn patterns_expand_to_existing_locations_in_document_order() {
+    let r = record(PEOPLE);
+    for (pattern, expected) in [
+        ("record.*.ssn", &["record.owner.ssn"][..]),
+        ("record.people.*.ssn", &["record.people[0].ssn"]),
+        ("record.people.*.name", &["record.people[1].name"]),
+        ("record.**.ssn", &["record.ssn", "record.owner.ssn", "record.people[0].ssn", "record.people[0].kids[0].ssn"]),
+        ("**.name", &["record.owner.name", "record.people[1].name"]),
+        ("record.people[0].*", &["record.people[0].kids", "record.people[0].ssn"]),
+        ("record.missing.**.ssn", &[]),
+    ] {
+        assert_eq!(paths(&glob::expand(&r, &field(pattern))), expected, "{pattern}");
+    }
+}
+
+#[test]
+fn statements_target_each_location_where_they_hold() {
+    let program = parse(r#"
+        rule pii {
+            if record.**.ssn != "" then mask;
+            if record.people.*.name == "Bo" then notify;
+            if record.*.ssn == "nobody" then delete
+        }
+    "#);
+    let decisions = Evaluator::with_now(0).evaluate(&program, &record(PEOPLE)).unwrap();
+    let targets: Vec<(Action, Vec<String>)> = decisions.iter().map(|d| (d.action, paths(&d.targets))).collect();
+    assert_eq!(targets, [
+        // the empty kid's ssn doesn't match, so it isn't targeted
+        (Action::Mask, vec!["record.ssn".to_string(), "record.owner.ssn".to_string(), "record.people[0].ssn".to_string()]),
+        (Action::Notify, vec!["record.people[1].name".to_string()]),
+    ]);
+    assert_eq!(decisions[0].to_string(), "mask [record.ssn, record.owner.ssn, record.people[0].ssn] (pii[0])");
+}
+
+#[test]
+fn patterns_parse_and_print() {
+    let condition = condition("record.**.ssn != \"\" and record.people.*.name == \"x\"");
+    assert_eq!(condition.to_string(), "record.**.ssn != \"\" and record.people.*.name == \"x\"");
+    assert_eq!(paths(&glob::pattern_fields(&condition)), ["record.**.ssn", "record.people.*.name"]);
+    // quantifier variables are items, not patterns over the record
+    assert!(glob::pattern_fields(&parse("rule r { if any p in record.people: p.name == \"Bo\" then notify }")
+        .rules[0].statements[0].condition).is_empty());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+const ORDER: &str = r#"{"user": {"emails": ["ann@gmail.com", "ann@work.org"], "tags": ["vip"]},
+    "order": {"items": [{"price": 30, "sku": "A-1"}, {"price": 5, "sku": "B-2", "gift": true}], "empty": [], "note": "x"}}"#;
+
+/// Whether `src` holds for `ORDER`, or the evaluation error.
+fn holds(src: &str) -> Result<bool, String> {
+    Evaluator::with_now(0).eval_expr(&condition(src), &record(ORDER)).map_err(|e| e.to_string())
+}
+
+#[test]
+fn any_and_all_range_over_lists() {
+    for (src, expected) in [
+        (r#"any e in user.emails: e ends_with "@gmail.com""#, true),
+        (r#"all e in user.emails: e ends_with "@gmail.com""#, false),
+        (r#"all e in user.emails: e contains "@""#, true),
+        ("any i in order.items: (i.price > 20 and i.sku starts_with \"A\")", true),
+        ("any i in order.items: (i.price > 20 and i.sku starts_with \"B\")", false),
+        ("all i in order.items: i.price > 20", false),
+        ("any i in order.items: i.gift == true", true),
+        // the body is a single predicate, so `and` ends it
+        (r#"any i in order.items: i.price < 10 and user.tags contains "vip""#, true),
+        ("any i in order.items: i.price > 20 and i.sku == \"B-2\"", false),
+        // the body sees the record as well as the item, and nested
+        // quantifiers see the outer variable
+        (r#"any i in order.items: (user.tags contains "vip" and i.price == 5)"#, true),
+        (r#"any i in order.items: any e in user.emails: (e starts_with "ann" and i.price == 5)"#, true),
+        // empty and missing lists: nothing matches `any`, everything `all`
+        ("any i in order.empty: i.price > 0", false),
+        ("all i in order.empty: i.price > 0", true),
+        ("any i in order.missing: i.price > 0", false),
+        ("all i in order.missing: i.price > 0", true),
+    ] {
+        assert_eq!(holds(src), Ok(expected), "{src}");
+    }
+    assert_eq!(holds("any c in order.note: c == \"x\""), Err("quantifier needs a list, found string".to_string()));
+}
+
+#[test]
+fn indexes_address_list_items() {
+    for (src, expected) in [
+        ("order.items[0].price == 30", true),
+        ("order.items[1].sku == \"B-2\"", true),
+        ("user.emails[1] ends_with \".org\"", true),
+        // out of range is null, like a missing field
+        ("order.items[5].price == 30", false),
+        ("not order.items[5].price == 30", true),
+        ("order.items[0].gift == true", false),
+    ] {
+        assert_eq!(holds(src), Ok(expected), "{src}");
+    }
+    assert_eq!(condition("order.items[0].price > 1").to_string(), "order.items[0].price > 1");
+}
+
+#[test]
+fn string_operators_and_list_membership() {
+    for (src, expected) in [
+        ("order.note starts_with \"\"", true),
+        ("user.emails[0] contains \"@gmail\"", true),
+        ("user.tags contains \"vip\"", true),
+        ("user.tags contains \"admin\"", false),
+        // a missing string contains nothing
+        ("user.missing contains \"a\"", false),
+    ] {
+        assert_eq!(holds(src), Ok(expected), "{src}");
+    }
+    assert!(holds("order.items[0].price starts_with \"3\"").unwrap_err().contains("cannot compare number starts_with string"));
+}
+
+#[test]
+fn the_checker_rejects_quantifiers_over_scalars() {
+    for (src, message) in [
+        ("any x in 3: x > 1", "quantifier needs a list, found number"),
+        ("all x in now: x > 1", "quantifier needs a list, found timestamp"),
+        ("user.name starts_with 1", "cannot compare any starts_with number"),
+    ] {
+        let err = check_program(&parse(&format!("rule r {{ if {src} then notify }}"))).unwrap_err();
+        assert!(err.to_string().contains(message), "{src}: {err}");
+    }
+}
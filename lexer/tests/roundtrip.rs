@@ -0,0 +1,144 @@
+//! Property: printing a parsed AST and parsing it again yields the same AST.
+
+use lexer::*;
+use proptest::prelude::*;
+
+const RESERVED: &[&str] = &[
+    "rule", "if", "then", "in", "and", "or", "not", "delete", "mask", "notify", "encrypt",
+    "now", "any", "all", "starts_with", "ends_with", "contains", "true", "false",
+];
+
+fn ident() -> impl Strategy<Value = String> {
+    "[a-z_][a-z0-9_]{0,6}".prop_filter("keyword", |s| !RESERVED.contains(&s.as_str()))
+}
+
+fn field() -> impl Strategy<Value = Field> {
+    let segment = prop_oneof![
+        3 => ident().prop_map(Segment::Key),
+        1 => (0usize..10).prop_map(Segment::Index),
+        1 => Just(Segment::Wildcard),
+        1 => Just(Segment::Glob),
+    ];
+    (ident(), prop::collection::vec(segment, 0..3)).prop_map(|(first, rest)| {
+        let mut segments = vec![Segment::Key(first)];
+        segments.extend(rest);
+        Field { segments }
+    })
+}
+
+fn operand() -> impl Strategy<Value = Operand> {
+    let unit = prop::sample::select(vec!["seconds", "minute", "hours", "day", "weeks"]);
+    let leaf = prop_oneof![
+        (0..=i64::MAX).prop_map(Operand::Number),
+        "[ -~]{0,8}".prop_map(Operand::Str),
+        any::<bool>().prop_map(Operand::Bool),
+        (0i64..1000, unit).prop_map(|(value, unit)| Operand::Duration { value, unit: unit.to_string() }),
+        field().prop_map(Operand::Field),
+        Just(Operand::Now),
+    ];
+    let arith_op = prop::sample::select(vec![ArithOp::Add, ArithOp::Sub, ArithOp::Mul, ArithOp::Div, ArithOp::Rem]);
+    leaf.prop_recursive(4, 24, 3, move |inner| prop_oneof![
+        inner.clone().prop_map(|o| Operand::Neg(Box::new(o))),
+        (inner.clone(), arith_op.clone(), inner.clone())
+            .prop_map(|(l, op, r)| Operand::Arith { left: Box::new(l), op, right: Box::new(r) }),
+        (ident(), prop::collection::vec(inner, 0..3)).prop_map(|(name, args)| Operand::Call(Call { name, args })),
+    ])
+}
+
+fn comp_op() -> impl Strategy<Value = CompOp> {
+    use CompOp::*;
+    prop::sample::select(vec![Eq, Ne, Gt, Lt, Ge, Le, StartsWith, EndsWith, Contains])
+}
+
+/// Conditions in the shape the parser produces: `Or`/`And` lean left and only
+/// `Group` wraps a looser-binding expression.
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        (operand(), comp_op(), operand()).prop_map(|(left, op, right)| Expr::Compare { left, op, right }),
+        (field(), prop::collection::vec(ident(), 1..4)).prop_map(|(field, set)| Expr::In { field, set }),
+        (ident(), prop::collection::vec(operand(), 0..3)).prop_map(|(name, args)| Expr::Call(Call { name, args })),
+        prop::sample::select(Detector::ALL.to_vec()).prop_map(Expr::LooksLike),
+    ];
+    leaf.prop_recursive(4, 32, 3, |inner| {
+        let predicate = prop_oneof![
+            inner.clone(),
+            inner.clone().prop_map(|e| Expr::Group(Box::new(e))),
+        ];
+        let quantifier = prop::sample::select(vec![Quantifier::Any, Quantifier::All]);
+        let unary = prop_oneof![
+            predicate.clone().prop_filter("binary", |e| !matches!(e, Expr::Or(..) | Expr::And(..))),
+            predicate.clone().prop_filter("binary", |e| !matches!(e, Expr::Or(..) | Expr::And(..)))
+                .prop_map(|e| Expr::Not(Box::new(e))),
+            (quantifier, ident(), operand(), predicate.prop_filter("binary", |e| !matches!(e, Expr::Or(..) | Expr::And(..))))
+                .prop_map(|(quantifier, var, list, body)| Expr::Quantified { quantifier, var, list, body: Box::new(body) }),
+        ];
+        let conjunction = prop::collection::vec(unary, 1..3).prop_map(|es| {
+            es.into_iter().reduce(|a, b| Expr::And(Box::new(a), Box::new(b))).unwrap()
+        });
+        prop::collection::vec(conjunction, 1..3).prop_map(|es| {
+            es.into_iter().reduce(|a, b| Expr::Or(Box::new(a), Box::new(b))).unwrap()
+        })
+    })
+}
+
+fn program() -> impl Strategy<Value = Program> {
+    let action = prop::sample::select(vec![Action::Delete, Action::Mask, Action::Notify, Action::Encrypt]);
+    let strategy = prop_oneof![
+        Just(MaskStrategy::Redact), (0u32..20).prop_map(MaskStrategy::KeepLast), Just(MaskStrategy::Email),
+        Just(MaskStrategy::Hash), Just(MaskStrategy::Month), Just(MaskStrategy::Year),
+    ];
+    let statement = (expr(), action, prop::option::of(strategy)).prop_map(|(condition, action, mask)| {
+        // only `mask` takes a strategy
+        Statement { condition, action, mask: mask.filter(|_| action == Action::Mask) }
+    });
+    let doc = prop::option::of(prop::collection::vec("[ -~]{0,12}", 1..3).prop_map(|lines| lines.join("\n")));
+    let metadata = prop::collection::btree_map(ident(), literal(), 0..3).prop_map(|m| m.into_iter().collect());
+    let rule = (doc, ident(), metadata, schedule(), prop::collection::vec(statement, 0..4))
+        .prop_map(|(doc, name, metadata, schedule, statements)| Rule { doc, name, metadata, schedule, statements });
+    (prop::collection::vec(rule, 0..3), prop::collection::vec(test_block(), 0..2))
+        .prop_map(|(rules, tests)| Program { rules, tests })
+}
+
+fn schedule() -> impl Strategy<Value = Schedule> {
+    // whole days as well as arbitrary seconds, which print differently
+    let date = prop_oneof![(0i64..40_000).prop_map(|d| d * 86_400), 0i64..4_000_000_000];
+    let days = prop::collection::btree_set(prop::sample::select(Weekday::ALL.to_vec()), 0..4);
+    let hours = prop::option::of((0u32..1440, 0u32..1440));
+    (prop::option::of(date.clone()), prop::option::of(date), days, hours)
+        .prop_map(|(from, until, days, hours)| Schedule { from, until, days: days.into_iter().collect(), hours })
+}
+
+fn literal() -> impl Strategy<Value = Literal> {
+    let unit = prop::sample::select(vec!["seconds", "minute", "hours", "day", "weeks"]);
+    let leaf = prop_oneof![
+        any::<bool>().prop_map(Literal::Bool),
+        (-i64::MAX..=i64::MAX).prop_map(Literal::Number),
+        "[ -~]{0,8}".prop_map(Literal::Str),
+        (0i64..1000, unit).prop_map(|(value, unit)| Literal::Duration { value, unit: unit.to_string() }),
+    ];
+    leaf.prop_recursive(2, 8, 3, |inner| prop::collection::vec(inner, 0..3).prop_map(Literal::List))
+}
+
+fn test_block() -> impl Strategy<Value = Test> {
+    let key = prop::collection::vec(ident(), 1..3)
+        .prop_map(|keys| Field { segments: keys.into_iter().map(Segment::Key).collect() });
+    let action = prop::sample::select(vec![Action::Delete, Action::Mask, Action::Notify, Action::Encrypt]);
+    let expectation = prop_oneof![
+        action.clone().prop_map(Expectation::Fires),
+        action.prop_map(Expectation::NotFires),
+        Just(Expectation::Nothing),
+    ];
+    let at = prop::option::of(0i64..4_000_000_000);
+    ("[ -~]{0,8}", at, prop::collection::vec((key, literal()), 0..3), prop::collection::vec(expectation, 1..3))
+        .prop_map(|(name, at, given, expect)| Test { name, at, given, expect })
+}
+
+proptest! {
+    #[test]
+    fn printed_program_parses_to_the_same_ast(program in program()) {
+        let src = program.to_string();
+        let tokens = lex(&src).map_err(|e| TestCaseError::fail(format!("{e}\n{src}")))?;
+        let reparsed = Parser::new(tokens).parse_program().map_err(|e| TestCaseError::fail(format!("{e}\n{src}")))?;
+        prop_assert_eq!(reparsed, program, "{}", src);
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+#[test]
+fn detectors() {
+    let cases = [
+        (Detector::Ssn, &["123-45-6789", "123 45 6789", " 123456789 "][..], &["000-12-3456", "666-12-3456", "912-34-5678", "123-00-4567", "123-45-0000", "123-45 6789", "12-345-6789"][..]),
+        (Detector::CreditCard, &["4111 1111 1111 1111", "4111-1111-1111-1111", "378282246310005"], &["4111 1111 1111 1112", "0000 0000 0000 0000", "4111  1111 1111 1111", "411111111111"]),
+        (Detector::Email, &["ada@example.com"], &["ada@", "ada example.com"]),
+        (Detector::Phone, &["+1 (555) 123-4567", "020 7946 0958", "555.123.4567"], &["12345", "(555 123-4567", "call 555-123-4567", "+1 555 123 4567 8901 2"]),
+    ];
+    for (detector, yes, no) in cases {
+        for s in yes { assert!(detector.matches(s), "{} should match {s:?}", detector.name()); }
+        for s in no { assert!(!detector.matches(s), "{} should not match {s:?}", detector.name()); }
+    }
+}
+
+#[test]
+fn looks_like_parses_and_prints() {
+    let program = parse("rule r { if looks_like(credit_card) and not looks_like(phone) then notify }");
+    let condition = &program.rules[0].statements[0].condition;
+    assert_eq!(condition.detectors(), [Detector::CreditCard, Detector::Phone]);
+    assert_eq!(condition.to_string(), "looks_like(credit_card) and not looks_like(phone)");
+
+    let err = Parser::new(lex("rule r { if looks_like(iban) then notify }").unwrap()).parse_program().unwrap_err();
+    assert!(err.to_string().contains("expected detector"), "{err}");
+}
+
+#[test]
+fn scan_tries_each_string_value() {
+    let program = parse(r#"
+        rule cards { if looks_like(credit_card) and not (record.env == "test") then notify }
+        rule contacts { if looks_like(email) then notify; if looks_like(email) or looks_like(phone) then mask }
+        rule plain { if record.env == "prod" then delete }
+    "#);
+    let evaluator = Evaluator::with_now(0);
+    let r = record(r#"{"record": {"env": "prod", "card": "4111 1111 1111 1111", "contacts": [{"email": "a@b.com"}, {"phone": "020 7946 0958"}]}}"#);
+    let findings: Vec<(String, usize, String)> = evaluator.scan(&program, &r).unwrap().into_iter()
+        .map(|f| (f.rule, f.statement, f.field.to_string()))
+        .collect();
+    assert_eq!(findings, [
+        ("cards".to_string(), 0, "record.card".to_string()),
+        ("contacts".to_string(), 0, "record.contacts[0].email".to_string()),
+        ("contacts".to_string(), 1, "record.contacts[0].email".to_string()),
+        ("contacts".to_string(), 1, "record.contacts[1].phone".to_string()),
+    ]);
+
+    // fields are read as usual while scanning
+    let test = record(r#"{"record": {"env": "test", "card": "4111 1111 1111 1111"}}"#);
+    assert!(evaluator.scan(&program, &test).unwrap().is_empty());
+    // and outside scanning there is no value to test
+    assert!(matches!(evaluator.evaluate(&program, &r), Err(EvalError::NotScanning)));
+}
+
+#[test]
+fn report_generalizes_list_indexes() {
+    let program = parse(r#"
+        rule ids [tags: [pii]] { if looks_like(ssn) then notify }
+        rule contacts { if looks_like(email) then notify; if looks_like(email) then mask }
+    "#);
+    let evaluator = Evaluator::with_now(0);
+    let mut report = ScanReport::new();
+    for r in [
+        r#"{"record": {"tax": "123-45-6789", "emails": ["a@b.com", "c@d.org"]}}"#,
+        r#"{"record": {"tax": "n/a", "emails": ["none"]}}"#,
+    ] {
+        let r = record(r);
+        report.add(&r, &evaluator.scan(&program, &r).unwrap());
+    }
+    assert_eq!(report.records, 2);
+    let candidates = report.candidates();
+    assert_eq!(candidates.iter().map(|c| (c.field.to_string(), c.matches, c.values)).collect::<Vec<_>>(), [
+        ("record.emails.*".to_string(), 2, 3),
+        ("record.tax".to_string(), 1, 2),
+    ]);
+    assert_eq!(report.to_string(), "\
+2 records: 2 candidate fields
+
+record.emails.*
+  contacts: 2 of 3 values
+
+record.tax
+  ids: 1 of 2 values
+");
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::*;
+use lexer::*;
+
+fn ts(s: &str) -> i64 {
+    time::parse_timestamp(s).unwrap()
+}
+
+fn schedule(src: &str) -> Schedule {
+    parse(&format!("rule r {src} {{}}")).rules.remove(0).schedule
+}
+
+#[test]
+fn dates_are_inclusive_then_exclusive() {
+    let s = schedule(r#"from "2026-01-01" until "2026-02-01""#);
+    assert!(!s.allows(ts("2025-12-31T23:59:59")));
+    assert!(s.allows(ts("2026-01-01")));
+    assert!(s.allows(ts("2026-01-31T23:59:59")));
+    assert!(!s.allows(ts("2026-02-01")));
+}
+
+#[test]
+fn weekdays_and_daily_windows() {
+    assert_eq!(Weekday::of(ts("2026-01-03")), Weekday::Saturday);
+    assert_eq!(Weekday::of(ts("1969-12-31T23:00:00")), Weekday::Wednesday);
+
+    let s = schedule(r#"on sunday, saturday between "01:00" and "05:00""#);
+    assert_eq!(s.days, [Weekday::Saturday, Weekday::Sunday]);
+    assert!(s.allows(ts("2026-01-03T01:00:00")));
+    assert!(!s.allows(ts("2026-01-03T05:00:00")));
+    assert!(!s.allows(ts("2026-01-05T02:00:00")));
+
+    // a window spanning midnight belongs to the day it opens
+    let s = schedule(r#"on friday between "22:00" and "02:00""#);
+    assert!(s.allows(ts("2026-01-02T23:00:00")));
+    assert!(s.allows(ts("2026-01-03T01:59:00")));
+    assert!(!s.allows(ts("2026-01-02T01:00:00")));
+    assert!(!s.allows(ts("2026-01-02T12:00:00")));
+}
+
+#[test]
+fn bad_schedules_are_parse_errors() {
+    for (src, expected) in [
+        (r#"rule r from "soon" {}"#, "date like"),
+        (r#"rule r on sunday, {}"#, "day of the week"),
+        (r#"rule r between "1:00" and "05:00" {}"#, "time of day"),
+        (r#"rule r between "24:00" and "05:00" {}"#, "time of day"),
+        (r#"rule r on monday from "2026-01-01" {}"#, "'{'"),
+    ] {
+        let err = Parser::new(lex(src).unwrap()).parse_program().unwrap_err();
+        assert!(err.to_string().contains(expected), "{src}: {err}");
+    }
+}
+
+#[test]
+fn rules_out_of_schedule_do_not_fire() {
+    let program = parse(r#"
+        rule purge on saturday { if record.old == true then delete }
+        rule always { if record.old == true then notify }
+    "#);
+    let record = Value::from_json(&serde_json::json!({"record": {"old": true}})).unwrap();
+    let actions = |now: &str| -> Vec<Action> {
+        Evaluator::with_now(ts(now)).evaluate(&program, &record).unwrap().iter().map(|d| d.action).collect()
+    };
+    assert_eq!(actions("2026-01-03T12:00:00"), [Action::Delete, Action::Notify]);
+    assert_eq!(actions("2026-01-04T12:00:00"), [Action::Notify]);
+
+    let traces = Evaluator::with_now(ts("2026-01-04")).explain(&program, &record).unwrap();
+    assert!(!traces[0].in_force && !traces[0].fired);
+    assert_eq!(traces[0].to_string(), "purge[0] delete: not in force at this time\n");
+}
+
+#[test]
+fn tests_can_pin_the_clock() {
+    let program = parse(r#"
+        rule purge on saturday { if record.old == true then delete }
+        test "saturday" at "2026-01-03" { given { record.old: true } expect delete }
+        test "sunday" at "2026-01-04" { given { record.old: true } expect nothing }
+        test "unpinned" { given { record.old: true } expect delete }
+    "#);
+    // the evaluator's clock is a Monday, so only the pinned tests pass
+    let results = run_tests(&program, &Evaluator::with_now(ts("2026-01-05"))).unwrap();
+    let passed: Vec<bool> = results.iter().map(|r| r.failures.is_empty()).collect();
+    assert_eq!(passed, [true, true, false]);
+}
+
+#[test]
+fn schedules_in_lints_diffs_and_sql() {
+    let program = parse(r#"
+        rule never from "2026-01-01" until "2025-01-01" { if record.a == 1 then delete }
+        rule weekend on saturday { if record.b == 1 then delete }
+        rule later { if record.b == 1 and record.c == 1 then delete }
+    "#);
+    let lints = analyze(&program, &LintConfig::default());
+    assert_eq!(lints.len(), 1, "{lints:?}");
+    assert_eq!((lints[0].code, lints[0].statement), (LintCode::NeverFires, None));
+    assert!(lints[0].message.contains(r#"from "2026-01-01" until "2025-01-01""#));
+
+    let old = parse("rule r { if record.a == 1 then delete }");
+    let new = parse(r#"rule r on sunday { if record.a == 1 then delete }"#);
+    let changes: Vec<String> = diff_programs(&old, &new).iter().map(|c| c.to_string()).collect();
+    assert_eq!(changes, ["~ rule r: schedule always -> `on sunday`"]);
+
+    let schema = Schema::new("t").row("record").column("a", "a");
+    let (stmts, errors) = to_sql(&new, &schema, Dialect::Sqlite);
+    assert!(stmts.is_empty());
+    assert!(errors[0].to_string().contains("cannot translate `on sunday`"));
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
+use lexer::*;
+use serde_json::{json, Value as Json};
+
+/// A fresh temporary directory holding the given policy files.
+fn policy_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("policy-server-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, text) in files {
+        fs::write(dir.join(file), text).unwrap();
+    }
+    dir
+}
+
+fn serve(dir: &PathBuf) -> SocketAddr {
+    let server = Server::bind("127.0.0.1:0", PolicyEngine::load(dir).unwrap(), Evaluator::new()).unwrap();
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+    addr
+}
+
+/// Send a raw request; the status and the body as JSON.
+fn request(addr: SocketAddr, raw: &str) -> (u16, Json) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(raw.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+fn post(addr: SocketAddr, target: &str, body: &str) -> (u16, Json) {
+    request(addr, &format!("POST {target} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}", body.len()))
+}
+
+const RETENTION: &str = "/// Old records go.\nrule retention { if now - record.created_at > 30 days then delete }\n";
+const WEEKEND: &str = "rule weekend on saturday { if record.card starts_with \"4\" then mask(last 4) }\n";
+
+#[test]
+fn evaluates_records_against_a_policy_directory() {
+    let addr = serve(&policy_dir("evaluate", &[("a.policy", RETENTION), ("b.policy", WEEKEND), ("notes.txt", "not a policy")]));
+    let record = r#"{"record": {"created_at": "2025-01-01", "card": "4111"}}"#;
+
+    let (status, body) = post(addr, "/evaluate?now=2026-01-03T12%3A00%3A00", record);
+    assert_eq!(status, 200);
+    assert_eq!(body["version"], 1);
+    assert_eq!(body["decisions"], json!([
+        {"rule": "retention", "statement": 0, "action": "delete", "targets": [], "meta": {}},
+        {"rule": "weekend", "statement": 0, "action": "mask", "targets": [], "meta": {}},
+    ]));
+    assert!(body.get("trace").is_none());
+
+    // a Sunday, in seconds, with the trace
+    let (_, body) = post(addr, "/evaluate?now=1767484800&trace=true", record);
+    assert_eq!(body["decisions"].as_array().unwrap().len(), 1);
+    assert_eq!(body["trace"][1]["in_force"], false);
+    assert_eq!(body["trace"][0]["traces"][0]["values"][0]["name"], "now - record.created_at");
+
+    let (status, body) = post(addr, "/evaluate", r#"{"record": {"created_at": "2025-01-01", "card": 4}}"#);
+    assert_eq!((status, body["decisions"].as_array().map(Vec::len)), (200, Some(1)));
+}
+
+#[test]
+fn validates_sources_and_lists_rules() {
+    let addr = serve(&policy_dir("rules", &[("a.policy", RETENTION), ("b.policy", WEEKEND)]));
+
+    let (status, body) = post(addr, "/validate", "rule r {\n  if x > then delete\n}");
+    assert_eq!((status, &body["ok"]), (200, &json!(false)));
+    assert_eq!(body["diagnostics"][0]["stage"], "parse");
+    assert_eq!(body["diagnostics"][0]["span"]["start"]["line"], 2);
+    let (_, body) = post(addr, "/validate", RETENTION);
+    assert_eq!(body["ok"], true);
+
+    let (status, body) = request(addr, "GET /rules HTTP/1.1\r\n\r\n");
+    assert_eq!(status, 200);
+    assert_eq!(body["rules"][0]["doc"], "Old records go.");
+    assert_eq!(body["rules"][1]["schedule"], "on saturday");
+    assert_eq!(body["rules"][1]["statements"][0]["mask"], "last 4");
+}
+
+#[test]
+fn bad_requests_get_errors() {
+    let addr = serve(&policy_dir("errors", &[("a.policy", "rule r { if record.n / 0 > 1 then delete }")]));
+    for (raw, expected) in [
+        ("GET /nowhere HTTP/1.1\r\n\r\n".to_string(), 404),
+        ("GET /evaluate HTTP/1.1\r\n\r\n".to_string(), 405),
+        ("POST /rules HTTP/1.1\r\nContent-Length: 0\r\n\r\n".to_string(), 405),
+        ("nonsense\r\n\r\n".to_string(), 400),
+        ("POST /evaluate HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_string(), 501),
+        (format!("POST /evaluate HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1), 413),
+    ] {
+        let (status, body) = request(addr, &raw);
+        assert_eq!(status, expected, "{raw:?}: {body}");
+        assert!(body["error"].is_string());
+    }
+    for (target, body, expected) in [
+        ("/evaluate", "{", 400),
+        ("/evaluate?now=yesterday", "{}", 400),
+        ("/evaluate?trace=maybe", "{}", 400),
+        ("/evaluate", r#"{"record": {"n": 1}}"#, 422),
+    ] {
+        assert_eq!(post(addr, target, body).0, expected, "{target} {body}");
+    }
+}
@@ -0,0 +1,171 @@
+//! The SQL backend against an in-memory SQLite database: translated predicates
+//! must select exactly the rows the evaluator matches.
+
+mod common;
+
+use common::*;
+use lexer::sql::predicate;
+use lexer::*;
+use rusqlite::{params_from_iter, types::Value as SqlValue, Connection};
+
+const DAY: i64 = 86400;
+
+fn bind(params: &[Param]) -> impl Iterator<Item = SqlValue> + '_ {
+    params.iter().map(|p| match p {
+        Param::Int(n) => SqlValue::Integer(*n),
+        Param::Text(s) => SqlValue::Text(s.clone()),
+        Param::Bool(b) => SqlValue::Integer(*b as i64),
+    })
+}
+
+struct Row {
+    id: i64,
+    name: Option<&'static str>,
+    country: Option<&'static str>,
+    failed_logins: Option<i64>,
+    is_admin: Option<bool>,
+    age_days: Option<i64>,
+}
+
+const ROWS: [Row; 6] = [
+    Row { id: 1, name: Some("alice"), country: Some("de"), failed_logins: Some(0), is_admin: Some(true), age_days: Some(1) },
+    Row { id: 2, name: Some("bob_test"), country: Some("blocked"), failed_logins: Some(7), is_admin: Some(false), age_days: Some(40) },
+    Row { id: 3, name: Some("test_carol"), country: Some("fr"), failed_logins: Some(5), is_admin: None, age_days: Some(400) },
+    Row { id: 4, name: None, country: None, failed_logins: None, is_admin: None, age_days: None },
+    Row { id: 5, name: Some(""), country: Some("de"), failed_logins: Some(12), is_admin: Some(false), age_days: Some(10) },
+    Row { id: 6, name: Some("O'Neil"), country: Some("it"), failed_logins: Some(3), is_admin: Some(true), age_days: Some(100) },
+];
+
+fn database(now: i64) -> Connection {
+    let db = Connection::open_in_memory().unwrap();
+    db.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, country TEXT, failed_logins INTEGER,
+                                          is_admin INTEGER, created_at INTEGER, ssn TEXT, email TEXT)").unwrap();
+    for row in &ROWS {
+        db.execute("INSERT INTO users VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'ssn-' || ?1, ?2 || '@example.com')",
+                   rusqlite::params![row.id, row.name, row.country, row.failed_logins, row.is_admin,
+                                     row.age_days.map(|d| now - d * DAY)]).unwrap();
+    }
+    db
+}
+
+fn record(row: &Row, now: i64) -> Value {
+    let mut fields = Vec::new();
+    if let Some(v) = row.name { fields.push(("name", Value::from(v))); }
+    if let Some(v) = row.country { fields.push(("country", Value::from(v))); }
+    if let Some(v) = row.failed_logins { fields.push(("failed_logins", Value::Number(v))); }
+    if let Some(v) = row.is_admin { fields.push(("is_admin", Value::Bool(v))); }
+    if let Some(d) = row.age_days { fields.push(("created_at", Value::Timestamp(now - d * DAY))); }
+    [("record", fields.into_iter().collect::<Value>())].into_iter().collect()
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+#[test]
+fn predicates_select_what_the_evaluator_matches() {
+    let now = now();
+    let db = database(now);
+    let schema = Schema::new("users").row("record");
+    let evaluator = Evaluator::with_now(now);
+    for src in [
+        "record.failed_logins > 5",
+        "not record.failed_logins > 5",
+        "record.failed_logins >= 5 and record.country != \"blocked\"",
+        "record.country == \"de\" or not record.country in [de, fr, blocked]",
+        "record.is_admin == true",
+        "not record.is_admin == true",
+        "record.is_admin != false",
+        "now - record.created_at > 30 days",
+        "not (record.created_at < now - 2 weeks)",
+        "record.failed_logins >= 5 days",
+        "record.name starts_with \"test\" or record.name ends_with \"_test\"",
+        "record.name contains \"'\"",
+        "record.name starts_with \"\"",
+        "upper(record.country) != \"DE\" and lower(record.name) > \"b\"",
+        "(record.failed_logins + 1) % 3 == -(-1)",
+        "record.failed_logins / 2 == 2",
+    ] {
+        let (sql, params) = predicate(&condition(src), &schema, Dialect::Sqlite, None).unwrap();
+        let mut stmt = db.prepare(&format!("SELECT id FROM users WHERE {sql} ORDER BY id")).unwrap();
+        let selected: Vec<i64> = stmt.query_map(params_from_iter(bind(&params)), |r| r.get(0)).unwrap()
+            .map(Result::unwrap).collect();
+        let expected: Vec<i64> = ROWS.iter()
+            .filter(|row| evaluator.eval_expr(&condition(src), &record(row, now)).unwrap())
+            .map(|row| row.id).collect();
+        assert_eq!(selected, expected, "{src}\n{sql}");
+    }
+}
+
+#[test]
+fn rules_become_delete_and_update_statements() {
+    let now = now();
+    let db = database(now);
+    db.create_scalar_function("mask", 1, rusqlite::functions::FunctionFlags::SQLITE_DETERMINISTIC, |ctx| {
+        Ok(ctx.get::<Option<String>>(0)?.map(|s| "*".repeat(s.chars().count())))
+    }).unwrap();
+    let program = parse(r#"
+        rule retention { if now - record.created_at > 365 days then delete }
+        rule pii { if field in [ssn] and not record.is_admin == true then mask }
+    "#);
+    let schema = Schema::new("users").row("record").column_var("field").protect("ssn").protect("email");
+    let (statements, errors) = to_sql(&program, &schema, Dialect::Sqlite);
+    assert!(errors.is_empty(), "{errors:?}");
+    let sql: Vec<&str> = statements.iter().map(|s| s.sql.as_str()).collect();
+    assert_eq!(sql, [
+        "DELETE FROM \"users\" WHERE (((CAST(strftime('%s', 'now') AS INTEGER) - \"created_at\") > ?1) IS TRUE)",
+        "UPDATE \"users\" SET \"ssn\" = mask(\"ssn\") WHERE ((?1 IN (?2)) IS TRUE) AND NOT (\"is_admin\" IS ?3)",
+        "UPDATE \"users\" SET \"email\" = mask(\"email\") WHERE ((?1 IN (?2)) IS TRUE) AND NOT (\"is_admin\" IS ?3)",
+    ]);
+    for st in &statements {
+        db.execute(&st.sql, params_from_iter(bind(&st.params))).unwrap();
+    }
+    let rows: Vec<(i64, String, String)> = db.prepare("SELECT id, ssn, email FROM users ORDER BY id").unwrap()
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get::<_, Option<String>>(2)?.unwrap_or_default()))).unwrap()
+        .map(Result::unwrap).collect();
+    // row 3 is over a year old; admins keep their ssn; emails are never in the masked set
+    assert_eq!(rows, [
+        (1, "ssn-1".to_string(), "alice@example.com".to_string()),
+        (2, "*****".to_string(), "bob_test@example.com".to_string()),
+        (4, "*****".to_string(), String::new()),
+        (5, "*****".to_string(), "@example.com".to_string()),
+        (6, "ssn-6".to_string(), "O'Neil@example.com".to_string()),
+    ]);
+}
+
+#[test]
+fn untranslatable_statements_are_reported() {
+    let program = parse(r#"
+        rule r {
+            if any e in record.emails: e ends_with ".ru" then notify;
+            if is_email(record.name) then notify;
+            if user.role == "guest" then delete;
+            if record.**.ssn != "" then delete;
+            if field == "ssn" then delete;
+            if record.country == "de" then encrypt;
+            if record.name contains record.country then notify;
+            if record.country == "de" then notify
+        }
+    "#);
+    let (statements, errors) = to_sql(&program, &Schema::new("users").row("record").column_var("field"), Dialect::Sqlite);
+    assert_eq!(statements.len(), 1);
+    assert_eq!(statements[0].to_string(), "-- r[7] params: 'de'\nSELECT * FROM \"users\" WHERE (\"country\" IS ?1);");
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(messages, [
+        "rule 'r', statement 0: cannot translate `any e in record.emails: e ends_with \".ru\"` to SQL: quantifiers need list columns",
+        "rule 'r', statement 1: cannot translate `is_email(record.name)` to SQL: functions used as conditions have no SQL equivalent",
+        "rule 'r', statement 2: field 'user.role' is not mapped to a column",
+        "rule 'r', statement 3: cannot translate `record.**.ssn` to SQL: `*` and `**` paths need nested data",
+        "rule 'r', statement 4: cannot translate `field` to SQL: the column variable only applies to mask and encrypt",
+        "rule 'r', statement 5: no protected columns to encrypt",
+        "rule 'r', statement 6: cannot translate `record.name contains record.country` to SQL: string matching needs a string literal on the right",
+    ]);
+}
+
+#[test]
+fn postgres_uses_numbered_parameters_and_intervals() {
+    let schema = Schema::new("users").column("user.signup", "created_at");
+    let (sql, params) = predicate(&condition("now - user.signup > 2 days and user.signup != now"), &schema, Dialect::Postgres, None).unwrap();
+    assert_eq!(sql, "(((now() - \"created_at\") > ($1 * INTERVAL '1 second')) IS TRUE) AND (\"created_at\" IS DISTINCT FROM now())");
+    assert_eq!(params, [Param::Int(2 * DAY)]);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::*;
+use lexer::testing::given_record;
+use lexer::*;
+
+const POLICY: &str = r#"
+    rule protect_pii {
+        if field in [ssn, credit_card] and not user.is_admin == true then mask
+    }
+    rule cleanup {
+        if record.age > 30 days then delete
+    }
+"#;
+
+fn run(tests: &str) -> Vec<TestResult> {
+    run_tests(&parse(&format!("{POLICY}\n{tests}")), &Evaluator::with_now(0)).unwrap()
+}
+
+#[test]
+fn given_builds_nested_records() {
+    let program = parse(r#"test "t" { given { user.is_admin: true, user.name: bob, record.age: 40 days, when: "2024-01-02" } expect nothing }"#);
+    let record = given_record(&program.tests[0]).unwrap();
+    let expected: Value = [
+        ("user", [("is_admin", Value::Bool(true)), ("name", Value::from("bob"))].into_iter().collect::<Value>()),
+        ("record", [("age", Value::Duration(40 * 86400))].into_iter().collect()),
+        ("when", Value::from("2024-01-02")),
+    ].into_iter().collect();
+    assert_eq!(record, expected);
+}
+
+#[test]
+fn passing_tests() {
+    let results = run(r#"
+        test "admins are not masked" { given { user.is_admin: true, field: ssn } expect no mask }
+        test "others are" { given { user.is_admin: false, field: ssn } expect mask and no delete }
+        test "old data goes" { given { record.age: 31 days } expect delete }
+        test "nothing to do" { given { field: email } expect nothing }
+    "#);
+    assert!(results.iter().all(TestResult::passed), "{results:#?}");
+}
+
+#[test]
+fn failures_carry_messages_and_traces() {
+    let results = run(r#"
+        test "wrong" { given { user.is_admin: false, field: ssn } expect no mask }
+        test "missing" { given { record.age: 3 days } expect delete and no notify and notify }
+    "#);
+    assert_eq!(results[0].failures, ["expected no mask, but protect_pii[0] fired"]);
+    assert_eq!(results[0].trace.len(), 1);
+    assert_eq!(results[0].trace[0].to_string(), "\
+protect_pii[0] mask: fired
+  field in [ssn, credit_card] and not user.is_admin == true => true
+    field in [ssn, credit_card] => true  (field = \"ssn\")
+    not user.is_admin == true => true
+      user.is_admin == true => false  (user.is_admin = false)
+");
+    assert_eq!(results[1].failures, [
+        "expected delete, but none of cleanup[0] fired",
+        "expected notify, but no statement has that action",
+    ]);
+}
+
+#[test]
+fn traces_short_circuit_and_show_quantifier_items() {
+    let program = parse(r#"rule r { if x > 1 or any e in emails: e ends_with "@corp.com" then notify }"#);
+    let record: Value = [("x", Value::from(0)), ("emails", Value::List(vec!["a@home.org".into(), "b@corp.com".into(), "c@x".into()]))]
+        .into_iter().collect();
+    let trace = Evaluator::with_now(0).explain(&program, &record).unwrap();
+    assert!(trace[0].fired);
+    let quantified = &trace[0].traces[0].children[1];
+    assert_eq!(quantified.children.len(), 2, "stops at the first match");
+    assert_eq!(quantified.children[1].values[0], ("e".to_string(), Value::from("b@corp.com")));
+}
@@ -0,0 +1,49 @@
+// JavaScript bindings for the WebAssembly build of the policy library. See
+// src/wasm.rs for how to build it and src/api.rs for requests and responses.
+//
+//   const policy = await Policy.load(await fetch("lexer.wasm").then(r => r.arrayBuffer()));
+//   const { ok, diagnostics } = policy.check(source);
+
+export class Policy {
+  static async load(bytes) {
+    const { instance } = await WebAssembly.instantiate(bytes, {});
+    return new Policy(instance.exports);
+  }
+
+  constructor(exports) {
+    this.exports = exports;
+  }
+
+  /// Send one request object and return the parsed response.
+  call(request) {
+    const { memory, policy_alloc, policy_call, policy_free } = this.exports;
+    const input = new TextEncoder().encode(JSON.stringify(request));
+    const ptr = policy_alloc(input.length);
+    new Uint8Array(memory.buffer, ptr, input.length).set(input);
+    const packed = BigInt.asUintN(64, policy_call(ptr, input.length));
+    policy_free(ptr, input.length);
+    // views are taken after the call, which may have grown (and so replaced) the memory
+    const [out, len] = [Number(packed >> 32n), Number(packed & 0xffffffffn)];
+    const text = new TextDecoder().decode(new Uint8Array(memory.buffer, out, len));
+    policy_free(out, len);
+    return JSON.parse(text);
+  }
+
+  lex(source) {
+    return this.call({ op: "lex", source });
+  }
+
+  parse(source) {
+    return this.call({ op: "parse", source });
+  }
+
+  /// Type errors and lints.
+  check(source) {
+    return this.call({ op: "check", source });
+  }
+
+  /// `now` is in seconds since the Unix epoch; the module has no clock of its own.
+  evaluate(source, records, now = Math.floor(Date.now() / 1000)) {
+    return this.call({ op: "evaluate", source, records, now });
+  }
+}
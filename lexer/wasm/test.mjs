@@ -0,0 +1,84 @@
+// Headless tests of the WebAssembly build:
+//
+//   cargo build --lib --release --target wasm32-unknown-unknown --no-default-features
+//   node wasm/test.mjs [path/to/lexer.wasm]
+
+import assert from "node:assert/strict";
+import { readFileSync } from "node:fs";
+import { test } from "node:test";
+
+import { Policy } from "./policy.mjs";
+
+const path = process.argv[2] ?? new URL("../target/wasm32-unknown-unknown/release/lexer.wasm", import.meta.url);
+const policy = await Policy.load(readFileSync(path));
+
+const SOURCE = `/// Old records go.
+rule retention {
+    if now - record.created_at > 30 days then delete;
+    if record.contacts.*.email ends_with ".ru" then mask(email)
+}
+`;
+
+test("lex returns tokens with spans", () => {
+  const { ok, tokens } = policy.lex("rule r { if é > 1 then delete }");
+  assert.equal(ok, true);
+  assert.deepEqual(tokens.slice(0, 2).map(t => [t.kind, t.text]), [["keyword", "rule"], ["ident", "r"]]);
+  const e = tokens[4];
+  assert.deepEqual([e.text, e.span.start, e.span.end], ["é", { offset: 12, line: 1, col: 13 }, { offset: 14, line: 1, col: 14 }]);
+});
+
+test("lex and parse errors point at the source", () => {
+  const lexed = policy.lex("rule r {\n  if x == \"oops then delete }");
+  assert.equal(lexed.ok, false);
+  assert.deepEqual(lexed.diagnostics[0].span.start, { offset: 19, line: 2, col: 11 });
+
+  const parsed = policy.parse("rule r {\n  if x == then delete\n}");
+  assert.equal(parsed.ok, false);
+  const [d] = parsed.diagnostics;
+  assert.equal(d.stage, "parse");
+  assert.match(d.message, /expected identifier/);
+  assert.deepEqual([d.span.start.line, d.span.start.col, d.span.end.col], [2, 11, 15]);
+});
+
+test("parse describes rules and statements", () => {
+  const { ok, rules, formatted } = policy.parse(SOURCE);
+  assert.equal(ok, true);
+  assert.equal(rules[0].name, "retention");
+  assert.equal(rules[0].doc, "Old records go.");
+  assert.deepEqual(rules[0].statements.map(s => [s.action, s.mask, s.span.start.line]), [["delete", null, 3], ["mask", "email", 4]]);
+  assert.match(formatted, /then mask\(email\)/);
+});
+
+test("check reports type errors and lints with spans", () => {
+  const clean = policy.check(SOURCE);
+  assert.deepEqual(clean, { ok: true, diagnostics: [] });
+
+  const { ok, diagnostics } = policy.check("rule r {\n  if x > 1 then notify;\n  if nope(x) then delete\n}");
+  assert.equal(ok, false);
+  const check = diagnostics.find(d => d.stage === "check");
+  assert.match(check.message, /unknown function 'nope'/);
+  assert.deepEqual([check.span.start.line, check.span.start.col, check.span.end.col], [3, 3, 25]);
+
+  const lint = policy.check("rule r { if 1 > 2 then notify }").diagnostics[0];
+  assert.deepEqual([lint.severity, lint.code], ["warning", "never-fires"]);
+});
+
+test("evaluate returns decisions per record", () => {
+  const records = [
+    { record: { created_at: "2020-01-01T00:00:00Z", contacts: [{ email: "a@b.ru" }, { email: "c@d.de" }] } },
+    { record: { created_at: "2024-01-01T00:00:00Z", contacts: [] } },
+  ];
+  const now = Date.parse("2024-01-10T00:00:00Z") / 1000;
+  const { ok, results } = policy.evaluate(SOURCE, records, now);
+  assert.equal(ok, true);
+  assert.deepEqual(results[0].decisions.map(d => [d.action, d.targets]), [["delete", []], ["mask", ["record.contacts[0].email"]]]);
+  assert.deepEqual(results[1].decisions, [], "nine days old, with no contacts");
+});
+
+test("bad requests are diagnosed, not thrown", () => {
+  assert.equal(policy.call({ op: "fly", source: "" }).diagnostics[0].message, "unknown op \"fly\"");
+  assert.equal(policy.call({ op: "evaluate", source: "", records: [] }).diagnostics[0].stage, "request");
+  // a large request exercises memory growth
+  const big = "rule r { if x > 1 then notify }\n".repeat(2000).replace(/rule r/g, (_, i) => `rule r${i}`);
+  assert.equal(policy.parse(big).rules.length, 2000);
+});